@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+use sha2::digest::DynDigest;
+
+// algorithms which can be used for the 'OC-Checksum' header of nextcloud
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha1,
+    Md5
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().replace('-', "").as_str() {
+            "SHA256" => Ok(ChecksumAlgorithm::Sha256),
+            "SHA1" => Ok(ChecksumAlgorithm::Sha1),
+            "MD5" => Ok(ChecksumAlgorithm::Md5),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown checksum algorithm '{}'", s)))
+        }
+    }
+}
+
+impl ChecksumAlgorithm {
+    // all supported algorithms in the order they are preferred when comparing checksums
+    pub const ALL: [ChecksumAlgorithm; 3] = [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Sha1, ChecksumAlgorithm::Md5];
+
+    // returns the name of the algorithm used by nextcloud, e.g. 'SHA256'
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "SHA256",
+            ChecksumAlgorithm::Sha1 => "SHA1",
+            ChecksumAlgorithm::Md5 => "MD5"
+        }
+    }
+
+    fn create_hasher(&self) -> Box<dyn DynDigest + Send> {
+        match self {
+            ChecksumAlgorithm::Sha256 => Box::new(sha2::Sha256::default()),
+            ChecksumAlgorithm::Sha1 => Box::new(sha1::Sha1::default()),
+            ChecksumAlgorithm::Md5 => Box::new(md5::Md5::default())
+        }
+    }
+}
+
+// computes a checksum step by step from the parts of a file, e.g. while the parts are streamed to nextcloud
+pub struct Hasher {
+    hasher: Box<dyn DynDigest + Send>
+}
+
+impl Hasher {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Hasher {
+        Hasher {
+            hasher: algorithm.create_hasher()
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    // adds 'length' bytes of a file starting at 'offset', e.g. a chunk which does not need to be uploaded again
+    pub fn update_from_file(&mut self, path: &Path, offset: u64, length: u64) -> Result<(), io::Error> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = file.take(length);
+        let mut buffer = vec![0u8; 64 * 1024];
+
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break
+            }
+            self.hasher.update(&buffer[..read]);
+        }
+        Ok(())
+    }
+
+    // returns the checksum as lowercase hex string
+    pub fn finalize(self) -> String {
+        self.hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+// computes the checksum of a file by streaming its content and returns it as lowercase hex string
+pub fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> Result<String, io::Error> {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update_from_file(path, 0, u64::MAX)?;
+    Ok(hasher.finalize())
+}
+
+// formats a checksum the way nextcloud expects it in the 'OC-Checksum' header, e.g. 'SHA256:9f86d08...'
+pub fn format_checksum(algorithm: ChecksumAlgorithm, hash: &str) -> String {
+    format!("{}:{}", algorithm.get_name(), hash)
+}
+
+// parses the value of the 'oc:checksums' property, e.g. 'SHA1:a94a8fe... MD5:098f6bc...'
+// and returns the checksums by the name of their algorithm
+pub fn parse_checksums(value: &str) -> HashMap<String, String> {
+    value.split_whitespace()
+        .filter_map(|checksum| checksum.split_once(':'))
+        .map(|(algorithm, hash)| (algorithm.to_uppercase(), hash.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hash_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("test.txt");
+        fs::write(&path, "test").unwrap();
+
+        // assert that the checksums equal the well known checksums of 'test'
+        assert_eq!("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08", hash_file(&path, ChecksumAlgorithm::Sha256).unwrap());
+        assert_eq!("a94a8fe5ccb19ba61c4c0873d391e987982fbbd3", hash_file(&path, ChecksumAlgorithm::Sha1).unwrap());
+        assert_eq!("098f6bcd4621d373cade4e832627b4f6", hash_file(&path, ChecksumAlgorithm::Md5).unwrap());
+    }
+
+    #[test]
+    fn test_parse_checksums() {
+        let checksums = parse_checksums("SHA1:A94A8FE5CCB19BA61C4C0873D391E987982FBBD3 md5:098f6bcd4621d373cade4e832627b4f6 invalid");
+
+        // assert that the names of the algorithms are normalized and invalid entries are ignored
+        assert_eq!(2, checksums.len());
+        assert_eq!(Some(&"a94a8fe5ccb19ba61c4c0873d391e987982fbbd3".to_string()), checksums.get("SHA1"));
+        assert_eq!(Some(&"098f6bcd4621d373cade4e832627b4f6".to_string()), checksums.get("MD5"));
+    }
+}
@@ -0,0 +1,316 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::metadata::MetadataDate;
+
+// order in which the sources are asked for the date of a file if the user does not configure it
+pub const DEFAULT_DATE_SOURCES: &str = "exif,quicktime,exiftool,filename,xmp,folder,mtime";
+
+// date of a file name or folder name like IMG-20230514-WA0003, Screenshot_2022-01-02-10-11-12 or 2023.05.14 Holiday.
+// The digits must not be part of a longer number and the time and milliseconds are optional
+const NAME_DATE_PATTERN: &str = r"(?:^|[^0-9])(?P<year>(?:19|20)[0-9]{2})[-_.]?(?P<month>[0-9]{2})[-_.]?(?P<day>[0-9]{2})(?:[-_ T.]?(?P<hour>[0-9]{2})[-_.:]?(?P<minute>[0-9]{2})[-_.:]?(?P<second>[0-9]{2})(?:[0-9]{3})?)?(?:[^0-9]|$)";
+
+// date properties of a XMP sidecar, either written as attribute or as element
+const XMP_DATE_PATTERN: &str = r#"(?P<tag>exif:DateTimeOriginal|photoshop:DateCreated|xmp:CreateDate)\s*(?:=\s*["']|>)\s*(?P<value>[^"'<]+)"#;
+// the XMP properties from the most to the least preferred
+const XMP_DATE_TAGS: [&str; 3] = ["exif:DateTimeOriginal", "photoshop:DateCreated", "xmp:CreateDate"];
+
+// a source the date of a file can be determined from
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum DateSource {
+    // DateTimeOriginal of the EXIF data read by the built-in metadata reader
+    Exif,
+    // creation date of a QuickTime or MP4 video read by the built-in metadata reader
+    QuickTime,
+    // dates of the formats the built-in metadata reader does not support
+    Exiftool,
+    // date in the file name like IMG-20230514-WA0003.jpg
+    Filename,
+    // date of a sidecar file like IMG_0001.jpg.xmp or IMG_0001.xmp
+    Xmp,
+    // date in the name of the parent folder like '2023-05-14 Holiday'
+    Folder,
+    // modification date of the file system, which always exists
+    Mtime
+}
+
+impl FromStr for DateSource {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "exif" => Ok(DateSource::Exif),
+            "quicktime" => Ok(DateSource::QuickTime),
+            "exiftool" => Ok(DateSource::Exiftool),
+            "filename" => Ok(DateSource::Filename),
+            "xmp" => Ok(DateSource::Xmp),
+            "folder" => Ok(DateSource::Folder),
+            "mtime" => Ok(DateSource::Mtime),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown date source '{}'", s)))
+        }
+    }
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DateSource::Exif => "exif",
+            DateSource::QuickTime => "quicktime",
+            DateSource::Exiftool => "exiftool",
+            DateSource::Filename => "filename",
+            DateSource::Xmp => "xmp",
+            DateSource::Folder => "folder",
+            DateSource::Mtime => "mtime"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// parses a comma separated list of date sources like 'exif,filename,mtime'. The order of the list is the order
+// in which the sources are asked
+pub fn parse_date_sources(s: &str) -> Result<Vec<DateSource>, io::Error> {
+    let mut sources: Vec<DateSource> = Vec::new();
+    for name in s.split(',').filter(|name| !name.trim().is_empty()) {
+        let source: DateSource = name.parse()?;
+        if sources.contains(&source) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The date source '{}' is listed twice", source)))
+        }
+        sources.push(source);
+    }
+    if sources.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "At least one date source is required"))
+    }
+    Ok(sources)
+}
+
+// time zone used for dates which were stored without one, like most EXIF dates of cameras and dates in file names
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Timezone {
+    // time zone of the computer running the upload
+    Local,
+    // fixed offset to UTC like +02:00
+    Fixed(FixedOffset),
+    // time zone of the tz database like Europe/Berlin, which considers daylight saving time
+    Named(Tz)
+}
+
+impl FromStr for Timezone {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "local" => return Ok(Timezone::Local),
+            "utc" | "z" => return Ok(Timezone::Fixed(FixedOffset::east_opt(0).unwrap())),
+            _ => {}
+        }
+        if let Ok(offset) = s.parse::<FixedOffset>() {
+            return Ok(Timezone::Fixed(offset))
+        }
+        s.parse::<Tz>()
+            .map(Timezone::Named)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown time zone '{}', use local, UTC, an offset like +02:00 or a name like Europe/Berlin", s)))
+    }
+}
+
+impl Timezone {
+    // returns the point in time of a date without time zone. A date skipped by the change to daylight saving time is
+    // moved by the skipped hour and an ambiguous date uses the earlier point in time
+    pub fn resolve(&self, date_time: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self.localize(date_time) {
+            LocalResult::None => self.localize(&(*date_time + Duration::hours(1))).earliest(),
+            result => result.earliest()
+        }
+    }
+
+    // returns the date of a unix timestamp in this time zone
+    pub fn at(&self, timestamp: i64) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Timezone::Local => Local.timestamp_opt(timestamp, 0).single().map(|date| date.fixed_offset()),
+            Timezone::Fixed(offset) => offset.timestamp_opt(timestamp, 0).single(),
+            Timezone::Named(tz) => tz.timestamp_opt(timestamp, 0).single().map(|date| date.fixed_offset())
+        }
+    }
+
+    fn localize(&self, date_time: &NaiveDateTime) -> LocalResult<DateTime<FixedOffset>> {
+        match self {
+            Timezone::Local => Local.from_local_datetime(date_time).map(|date| date.fixed_offset()),
+            Timezone::Fixed(offset) => offset.from_local_datetime(date_time),
+            Timezone::Named(tz) => tz.from_local_datetime(date_time).map(|date| date.fixed_offset())
+        }
+    }
+}
+
+fn name_date_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(NAME_DATE_PATTERN).unwrap())
+}
+
+fn xmp_date_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(XMP_DATE_PATTERN).unwrap())
+}
+
+// returns the first valid date in a file or folder name. A name without time is dated to midnight
+fn parse_name_date(name: &str) -> Option<NaiveDateTime> {
+    for captures in name_date_pattern().captures_iter(name) {
+        let number = |group: &str| captures.name(group).and_then(|value| value.as_str().parse::<u32>().ok());
+        let (Some(year), Some(month), Some(day)) = (number("year"), number("month"), number("day")) else {
+            continue
+        };
+        // numbers like 20231399 are no date
+        let Some(date) = NaiveDate::from_ymd_opt(year as i32, month, day) else {
+            continue
+        };
+        let time = match (number("hour"), number("minute"), number("second")) {
+            (Some(hour), Some(minute), Some(second)) => date.and_hms_opt(hour, minute, second),
+            _ => None
+        };
+        return Some(time.unwrap_or_else(|| date.and_hms_opt(0, 0, 0).unwrap()))
+    }
+    None
+}
+
+// returns the date in the name of the file
+pub fn read_filename_date(path: &Path) -> Option<MetadataDate> {
+    let name = path.file_stem()?.to_str()?;
+    parse_name_date(name).map(|date_time| MetadataDate::new(date_time, None))
+}
+
+// returns the date in the name of the folder containing the file
+pub fn read_folder_date(path: &Path) -> Option<MetadataDate> {
+    let name = path.parent()?.file_name()?.to_str()?;
+    parse_name_date(name).map(|date_time| MetadataDate::new(date_time, None))
+}
+
+// returns the paths a sidecar of the file could have, e.g. IMG_0001.jpg.xmp and IMG_0001.xmp
+fn get_sidecar_paths(path: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for ext in ["xmp", "XMP"] {
+        let mut appended = path.as_os_str().to_owned();
+        appended.push(format!(".{}", ext));
+        paths.push(PathBuf::from(appended));
+        paths.push(path.with_extension(ext));
+    }
+    paths
+}
+
+// returns the date of the XMP sidecar of the file or None if the file has no sidecar with a date
+pub fn read_xmp_date(path: &Path) -> Result<Option<MetadataDate>, io::Error> {
+    let Some(sidecar) = get_sidecar_paths(path).into_iter().find(|sidecar| sidecar != path && sidecar.is_file()) else {
+        return Ok(None)
+    };
+    let content = fs::read_to_string(sidecar)?;
+
+    let mut dates: Vec<(usize, MetadataDate)> = Vec::new();
+    for captures in xmp_date_pattern().captures_iter(&content) {
+        let priority = XMP_DATE_TAGS.iter().position(|tag| *tag == &captures["tag"]).unwrap_or(XMP_DATE_TAGS.len());
+        if let Some(date) = parse_xmp_date(captures["value"].trim()) {
+            dates.push((priority, date));
+        }
+    }
+    Ok(dates.into_iter().min_by_key(|(priority, _)| *priority).map(|(_, date)| date))
+}
+
+// parses a XMP date like '2023-05-14T15:30:12+02:00'. The seconds, the time and the time zone are optional
+fn parse_xmp_date(value: &str) -> Option<MetadataDate> {
+    for format in ["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M%:z"] {
+        if let Ok(date) = DateTime::parse_from_str(value.replace('Z', "+00:00").as_str(), format) {
+            return Some(MetadataDate::new(date.naive_local(), Some(*date.offset())))
+        }
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(MetadataDate::new(date_time, None))
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date_time| MetadataDate::new(date_time, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+    use tempfile::tempdir;
+
+    fn utc() -> Timezone {
+        Timezone::Fixed(FixedOffset::east_opt(0).unwrap())
+    }
+
+    fn timestamp(path: &str) -> Option<i64> {
+        read_filename_date(Path::new(path)).and_then(|date| date.resolve(&utc())).map(|date| date.timestamp())
+    }
+
+    #[test]
+    fn test_read_filename_date() {
+        // 2023-05-14 00:00:00 and 15:30:12 UTC
+        assert_eq!(Some(1684022400), timestamp("/chats/IMG-20230514-WA0003.jpg"));
+        assert_eq!(Some(1684078212), timestamp("/camera/VID_20230514_153012.mp4"));
+        assert_eq!(Some(1684078212), timestamp("/camera/PXL_20230514_153012345.jpg"));
+        // 2022-01-02 10:11:12 UTC
+        assert_eq!(Some(1641118272), timestamp("/screenshots/Screenshot_2022-01-02-10-11-12.png"));
+
+        // assert that invalid dates and parts of longer numbers are ignored
+        assert_eq!(None, timestamp("/camera/DSC_20231399.jpg"));
+        assert_eq!(None, timestamp("/camera/1202305140.jpg"));
+        assert_eq!(None, timestamp("/camera/IMG_0001.jpg"));
+
+        // assert that the folder name is used for the folder source
+        assert_eq!(Some(1684022400), read_folder_date(Path::new("/photos/2023.05.14 Holiday/IMG_0001.jpg")).and_then(|date| date.resolve(&utc())).map(|date| date.timestamp()));
+    }
+
+    #[test]
+    fn test_read_xmp_date() {
+        let temp_dir = tempdir().unwrap();
+        let photo = temp_dir.path().join("IMG_0001.CR2");
+        fs::write(&photo, "raw").unwrap();
+        assert!(read_xmp_date(&photo).unwrap().is_none());
+
+        // assert that DateTimeOriginal is preferred and the time zone is applied
+        fs::write(temp_dir.path().join("IMG_0001.xmp"), r#"<rdf:Description xmp:CreateDate="2020-01-01T00:00:00"
+            exif:DateTimeOriginal="2023-05-14T17:30:12+02:00"/>"#).unwrap();
+        assert_eq!(Some(1684078212), read_xmp_date(&photo).unwrap().and_then(|date| date.resolve(&utc())).map(|date| date.timestamp()));
+
+        // assert that a sidecar with the full file name is preferred and elements are read as well
+        fs::write(temp_dir.path().join("IMG_0001.CR2.xmp"), "<photoshop:DateCreated>2023-05-14</photoshop:DateCreated>").unwrap();
+        assert_eq!(Some(1684022400), read_xmp_date(&photo).unwrap().and_then(|date| date.resolve(&utc())).map(|date| date.timestamp()));
+    }
+
+    #[test]
+    fn test_resolve_timezone() {
+        assert_eq!(utc(), "UTC".parse::<Timezone>().unwrap());
+        assert_eq!(Timezone::Fixed(FixedOffset::east_opt(9 * 3600).unwrap()), "+09:00".parse::<Timezone>().unwrap());
+        assert!("Mars/Olympus_Mons".parse::<Timezone>().is_err());
+
+        // a photo taken at 00:30 in Tokyo was taken on the previous day in UTC
+        let date_time = NaiveDate::from_ymd_opt(2024, 3, 16).unwrap().and_hms_opt(0, 30, 0).unwrap();
+        let date = "+09:00".parse::<Timezone>().unwrap().resolve(&date_time).unwrap();
+        assert_eq!((16, 1710516600), (date.day(), date.timestamp()));
+        assert_eq!(15, date.with_timezone(&FixedOffset::east_opt(0).unwrap()).day());
+
+        // assert that named time zones consider daylight saving time and skipped dates are moved by an hour
+        let berlin: Timezone = "Europe/Berlin".parse().unwrap();
+        let summer = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap().and_hms_opt(23, 59, 0).unwrap();
+        assert_eq!(7200, berlin.resolve(&summer).unwrap().offset().local_minus_utc());
+        let skipped = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap().and_hms_opt(2, 30, 0).unwrap();
+        assert_eq!("2024-03-31T03:30:00+02:00", berlin.resolve(&skipped).unwrap().to_rfc3339());
+    }
+
+    #[test]
+    fn test_parse_date_sources() {
+        assert_eq!(vec![DateSource::Filename, DateSource::Mtime], parse_date_sources("filename, MTIME").unwrap());
+        assert_eq!(7, parse_date_sources(DEFAULT_DATE_SOURCES).unwrap().len());
+        assert!(parse_date_sources("exif,exif").is_err());
+        assert!(parse_date_sources("exif,gps").is_err());
+        assert!(parse_date_sources("").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use colored::*;
+use log::error;
+
+use crate::nextcloud::{HttpError, NextcloudClient};
+use crate::queue::WorkQueue;
+use crate::retry::{self, Decision, RetryPolicy};
+use crate::sync::scan;
+use crate::upload::common;
+use crate::output;
+
+// a remote file which is downloaded to 'local_path'
+struct Download {
+    remote_path: PathBuf,
+    local_path: PathBuf,
+    size: u64,
+    mtime: i64
+}
+
+impl Download {
+    // a local file with the same size and modification date was already downloaded
+    fn is_downloaded(&self) -> bool {
+        match fs::metadata(&self.local_path) {
+            Ok(metadata) => {
+                let mtime = metadata.modified().ok()
+                    .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|mtime| mtime.as_secs() as i64);
+                metadata.is_file() && metadata.len() == self.size && mtime == Some(self.mtime)
+            }
+            Err(_) => false
+        }
+    }
+}
+
+// lists the remote folder recursively, creates its folder structure in the local folder and returns the files which are not downloaded yet
+fn get_downloads(remote_root: &Path, local_root: &Path, client: &NextcloudClient) -> Result<Vec<Download>, Box<dyn Error>> {
+    let tree = scan::scan_remote(remote_root, client, None)?;
+
+    fs::create_dir_all(local_root)?;
+    for folder in &tree.folders {
+        fs::create_dir_all(local_root.join(folder))?;
+    }
+
+    let mut downloads: Vec<Download> = Vec::new();
+    let mut num_skipped: usize = 0;
+    for (path, remote) in tree.files {
+        let download = Download {
+            remote_path: remote_root.join(&path),
+            local_path: local_root.join(&path),
+            size: remote.get_size(),
+            mtime: remote.get_mtime()
+        };
+        if download.is_downloaded() {
+            num_skipped += 1;
+            continue
+        }
+        downloads.push(download);
+    }
+
+    if num_skipped > 0 {
+        output::println(format!("Skipping {} file(s) which were already downloaded.", num_skipped).yellow());
+    }
+    Ok(downloads)
+}
+
+// downloads the files of the queue from nextcloud until the queue is empty and updates the progress bar
+fn download_files(queue: Arc<WorkQueue<Download>>, client: Arc<NextcloudClient>, retry: Arc<RetryPolicy>, total_size: u64, shared_downloaded_size: Arc<Mutex<u64>>, shared_failed: Arc<Mutex<Vec<PathBuf>>>) -> Result<(), Box<HttpError>> {
+    while let Some(download) = queue.pop() {
+        // the progress bar advances while the file content is received from the server
+        let received_size = Arc::new(AtomicU64::new(0));
+        let progress = common::create_progress_callback(|downloaded_size, total_size| common::update_progress_bar("Downloading", downloaded_size, total_size), Arc::clone(&shared_downloaded_size), Arc::clone(&received_size), total_size);
+
+        // failed attempts are repeated automatically and continue the partially downloaded file
+        let result = retry.run(|| client.is_online().unwrap_or(false), || {
+            let result = client.download_file(&download.remote_path, &download.local_path, download.mtime, Some(Arc::clone(&progress)));
+            if result.is_err() {
+                // remove the already received bytes of the failed attempt from the progress bar
+                *shared_downloaded_size.lock().unwrap() -= received_size.swap(0, Ordering::Relaxed);
+            }
+            result
+        });
+        if let Err(e) = result {
+            // stop this worker if the server can not handle any further request
+            let e = match e.downcast::<HttpError>() {
+                Ok(http_err) if retry::classify(http_err.as_ref()) == Decision::Fatal => {
+                    let mut failed = shared_failed.lock().unwrap();
+                    failed.push(download.remote_path);
+                    failed.extend(queue.drain().into_iter().map(|download| download.remote_path));
+                    return Err(http_err)
+                }
+                Ok(http_err) => http_err as Box<dyn Error>,
+                Err(e) => e
+            };
+            error!("{:?}: {}", download.remote_path, e);
+            shared_failed.lock().unwrap().push(download.remote_path);
+            continue
+        }
+
+        // make sure the progress bar shows the downloaded file completely
+        let mut downloaded_size = shared_downloaded_size.lock().unwrap();
+        *downloaded_size += download.size.saturating_sub(received_size.load(Ordering::Relaxed));
+        common::update_progress_bar("Downloading", *downloaded_size, total_size);
+    }
+    Ok(())
+}
+
+// mirrors a folder on nextcloud including its sub folders to a local folder using 'num_threads' parallel downloads.
+// Each file is tried up to 'max_attempts' times. Returns the number of files which could not be downloaded
+pub fn download(remote_path: String, local_path: String, client: NextcloudClient, num_threads: usize, max_attempts: u32) -> Result<usize, Box<dyn Error>> {
+    let remote_root = PathBuf::from(remote_path);
+    let local_root = PathBuf::from(local_path);
+    if !client.exists_folder(&remote_root)? {
+        return Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("The folder {:?} does not exist on your Nextcloud instance.", remote_root))))
+    }
+
+    output::print("Listing remote folder ... ".green());
+    let downloads = get_downloads(&remote_root, &local_root, &client)?;
+    output::println("done".green());
+    if downloads.is_empty() {
+        output::println("Everything is already downloaded.".green());
+        return Ok(0)
+    }
+
+    let total_size: u64 = downloads.iter().map(|download| download.size).sum();
+    let shared_client = Arc::new(client);
+    let shared_downloaded_size: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
+    let shared_failed: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(vec![]));
+    let retry = Arc::new(RetryPolicy::new(max_attempts));
+
+    // print initial progress bar
+    common::update_progress_bar("Downloading", 0, total_size);
+
+    // the workers pull the files from a shared queue, starting with the largest files
+    let num_workers = num_threads.min(downloads.len());
+    let queue = Arc::new(WorkQueue::largest_first(downloads, |download| download.size));
+
+    // spawning the downloading threads
+    let mut threads: Vec<JoinHandle<Result<(), Box<HttpError>>>> = vec![];
+    for _ in 0..num_workers {
+        let queue_clone = Arc::clone(&queue);
+        let downloaded_size = Arc::clone(&shared_downloaded_size);
+        let client_clone = Arc::clone(&shared_client);
+        let failed_clone = Arc::clone(&shared_failed);
+        let retry_clone = Arc::clone(&retry);
+        threads.push(std::thread::spawn(move || {
+            download_files(queue_clone, client_clone, retry_clone, total_size, downloaded_size, failed_clone)
+        }));
+    }
+
+    // joining the downloading threads
+    let mut err: Option<Box<HttpError>> = None;
+    for thread in threads {
+        match thread.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => err = Some(e),
+            Err(_e) => return Err(Box::new(io::Error::other("Failed to join download threads!")))
+        }
+    }
+    output::println("");
+
+    let failed = shared_failed.lock().unwrap();
+    if !failed.is_empty() {
+        output::println(format!("{} file(s) could not be downloaded, run the command again to continue:", failed.len()).red());
+        for path in failed.iter() {
+            output::println(format!("{:?}", path).red());
+        }
+    }
+    if let Some(e) = err {
+        return Err(e)
+    }
+    Ok(failed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_is_downloaded() {
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("a.jpg");
+        let mut download = Download {
+            remote_path: PathBuf::from("/Photos/a.jpg"),
+            local_path: local_path.clone(),
+            size: 3,
+            mtime: 1700000000
+        };
+
+        // assert that a missing file is downloaded
+        assert!(!download.is_downloaded());
+
+        // assert that a file with the same size and modification date is skipped
+        let file = fs::File::create(&local_path).unwrap();
+        fs::write(&local_path, "abc").unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000000)).unwrap();
+        assert!(download.is_downloaded());
+
+        // assert that a changed remote file is downloaded again
+        download.mtime = 1700000001;
+        assert!(!download.is_downloaded());
+    }
+}
@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+use log::warn;
+
+// line exiftool prints after the output of each command in batch mode
+const READY_MARKER: &str = "{ready}";
+
+// a running exiftool process in batch mode, which reads the arguments of each command from stdin.
+// Starting the Perl interpreter once instead of once per file is what makes exiftool fast for large folders
+struct ExiftoolProcess {
+    child: Child,
+    // taken when the process is dropped, so exiftool reads EOF even if it ignores the stop command
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>
+}
+
+impl ExiftoolProcess {
+    fn start(exiftool: &str) -> Result<ExiftoolProcess, io::Error> {
+        let mut child = Command::new(exiftool)
+            .args(["-stay_open", "True", "-@", "-", "-common_args", "-charset", "filename=utf8"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // errors of single files would fill the pipe if nobody reads them, the missing output is reported instead
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("Failed to open stdin of exiftool"))?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("Failed to open stdout of exiftool"))?;
+        Ok(ExiftoolProcess {
+            child,
+            stdin: Some(stdin),
+            stdout: BufReader::new(stdout)
+        })
+    }
+
+    // runs a command with one argument per line and returns its output without the ready marker
+    fn execute(&mut self, args: &[&str]) -> Result<String, io::Error> {
+        let mut command = String::new();
+        for arg in args {
+            if arg.contains(['\n', '\r']) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The argument {:?} contains a line break", arg)))
+            }
+            command.push_str(arg);
+            command.push('\n');
+        }
+        command.push_str("-execute\n");
+        let stdin = self.stdin.as_mut().ok_or_else(|| io::Error::other("stdin of exiftool is closed"))?;
+        stdin.write_all(command.as_bytes())?;
+        stdin.flush()?;
+
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "exiftool terminated unexpectedly"))
+            }
+            if line.trim_end() == READY_MARKER {
+                return Ok(output.trim_end().to_string())
+            }
+            output.push_str(&line);
+        }
+    }
+}
+
+impl Drop for ExiftoolProcess {
+    fn drop(&mut self) {
+        // ask exiftool to terminate and wait for it, so no zombie process is left behind
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.write_all(b"-stay_open\nFalse\n");
+            let _ = stdin.flush();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+// pool of exiftool processes. Each worker takes its own process for a command and returns it afterwards,
+// so there are never more processes than workers extracting metadata at the same time
+pub struct Exiftool {
+    path: String,
+    idle: Mutex<Vec<ExiftoolProcess>>
+}
+
+impl Exiftool {
+    pub fn new(path: String) -> Exiftool {
+        Exiftool {
+            path,
+            idle: Mutex::new(Vec::new())
+        }
+    }
+
+    // returns the lower case extensions of the formats exiftool can write, which are the formats it reliably reads dates from
+    pub fn list_writable_formats(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let output = Command::new(&self.path)
+            .arg("-listwf")
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to start exiftool {}: {}", self.path, e)))?;
+        if !output.status.success() {
+            return Err(Box::new(io::Error::other(format!("exiftool -listwf failed with {}: {}",
+                output.status, String::from_utf8_lossy(&output.stderr).trim_end()))))
+        }
+
+        // the first line is a header like 'Writable file extensions:', the extensions follow separated by whitespace
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines()
+            .skip(1)
+            .flat_map(|line| line.split_whitespace())
+            .map(|ext| ext.to_lowercase())
+            .collect())
+    }
+
+    // runs exiftool with the given arguments and returns stdout. An empty output is reported as error, because
+    // exiftool prints nothing if the file does not exist or has none of the requested tags
+    pub fn execute(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let mut process = match idle {
+            Some(process) => process,
+            None => ExiftoolProcess::start(&self.path)?
+        };
+
+        match process.execute(args) {
+            Ok(output) => {
+                self.idle.lock().unwrap().push(process);
+                if output.is_empty() {
+                    return Err(Box::new(io::Error::other(format!("exiftool returned no output for {:?}", args))))
+                }
+                Ok(output)
+            }
+            // a process in an unknown state is dropped, the next command starts a new one
+            Err(e) => {
+                warn!("Restarting exiftool after an error: {}", e);
+                Err(Box::new(e))
+            }
+        }
+    }
+}
+
+// converts a path into an argument for exiftool. Arguments are passed one per line, so paths with line breaks can not be
+// passed, and paths starting with '-' would be read as option
+pub fn path_arg(path: &Path) -> Result<String, io::Error> {
+    let path_str = path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("The path {:?} is not valid UTF-8", path)))?;
+    if path_str.contains(['\n', '\r']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The path {:?} contains a line break", path)))
+    }
+    if path_str.starts_with('-') {
+        return Ok(format!("./{}", path_str))
+    }
+    Ok(path_str.to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_execute_batch_mode() {
+        // fake exiftool which prints the last argument of each command followed by the ready marker
+        let temp_dir = tempdir().unwrap();
+        let script = temp_dir.path().join("exiftool");
+        fs::write(&script, "#!/bin/sh\nwhile read line; do\n  if [ \"$line\" = \"-execute\" ]; then echo \"$last\"; echo \"{ready}\"; else last=\"$line\"; fi\ndone\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let exiftool = Exiftool::new(script.to_str().unwrap().to_string());
+
+        // assert that several commands are answered by the same process
+        assert_eq!("/photos/a b.jpg", exiftool.execute(&["-s3", "/photos/a b.jpg"]).unwrap());
+        assert_eq!("/photos/c.jpg", exiftool.execute(&["-s3", "/photos/c.jpg"]).unwrap());
+        assert_eq!(1, exiftool.idle.lock().unwrap().len());
+
+        // assert that arguments with line breaks are rejected
+        assert!(exiftool.execute(&["a\nb"]).is_err());
+    }
+
+    #[test]
+    fn test_path_arg() {
+        // assert that paths are never read as option and line breaks are rejected
+        assert_eq!("./-delete_original.jpg", path_arg(Path::new("-delete_original.jpg")).unwrap());
+        assert_eq!("/photos/$(id) `id`.jpg", path_arg(Path::new("/photos/$(id) `id`.jpg")).unwrap());
+        assert!(path_arg(Path::new("/photos/a\n-delete_original.jpg")).is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use crate::date_source::DateSource;
 use crate::media::get_metadata;
 
 #[derive(Debug)]
@@ -9,6 +13,7 @@ pub struct Folder{
 }
 
 impl Folder {
+    #[allow(clippy::needless_return, clippy::redundant_field_names)]
     pub fn new(name: String) -> Self {
         return Folder {
             name: name,
@@ -55,6 +60,7 @@ impl Folder {
     }
 
     // wrapper for recursive method has_subfolder_intern
+    #[allow(clippy::needless_return)]
     pub fn has_subfolder(&self, path_folder: &Path) -> bool {
         // split the path into the single folders, remove the root folder and push them into a vec
         let root = Path::new(&self.name);
@@ -77,6 +83,7 @@ impl Folder {
     }
 
     // recursively returns whether a folder and its sub folders have a sub folder 'path_folder'
+    #[allow(clippy::len_zero)]
     fn has_subfolder_intern(&self, folders: &mut Vec<String>) -> bool {
         // if folders is not empty and .get_subfolder() returns a Folder the sub folder 'path_folder' exists
         if let Some(sub_folder_name) = folders.pop() {
@@ -135,6 +142,7 @@ impl Folder {
 }
 
 impl PartialEq for Folder {
+    #[allow(clippy::bool_comparison)]
     fn eq(&self, other: &Self) -> bool {
         // check for equality by comparing names and numbers of sub folders
         if self.sub_folders.len() != other.sub_folders.len() || self.name != other.name {
@@ -156,12 +164,27 @@ impl PartialEq for Folder {
 #[derive(Clone)]
 pub struct File {
     local_path: PathBuf,
+    // target of the symlinks in 'local_path', None if the path contains no followed symlink
+    resolved_path: Option<PathBuf>,
     remote_parent: PathBuf,
+    // name of the file on nextcloud if it differs from the local file name
+    remote_name: Option<String>,
     mtime: i64,
+    // source the mtime was read from, None if the file was not scanned but restored e.g. from an upload session
+    date_source: Option<DateSource>,
+    // true if none of the date sources knew the date and the mtime is only used for the upload
+    unknown_date: bool,
+    // offset to UTC of the place the file was recorded, None if it is unknown
+    utc_offset: Option<FixedOffset>,
+    // true if the upload replaces a different file which already exists on nextcloud
+    replaces_existing: bool,
+    // checksum sent with the upload in the format of the 'OC-Checksum' header, kept so a retry does not hash the file again
+    checksum: OnceLock<String>,
     size: u64
 }
 
 impl File {
+    #[allow(clippy::needless_return, clippy::redundant_field_names)]
     pub fn new(local_path: &Path, mtime: i64) -> File {
         let mut size: u64 = 0;
         if let Ok(meta_data) = get_metadata(local_path.to_str().unwrap()) {
@@ -169,8 +192,15 @@ impl File {
         }
         return File {
             local_path: local_path.to_owned(),
+            resolved_path: None,
             remote_parent: PathBuf::new(),
+            remote_name: None,
             mtime: mtime,
+            date_source: None,
+            unknown_date: false,
+            utc_offset: None,
+            replaces_existing: false,
+            checksum: OnceLock::new(),
             size: size
         }
     }
@@ -182,6 +212,17 @@ impl File {
     pub fn get_local_path(&self) -> &Path {
         &self.local_path
     }
+
+    // returns the path the content of the file is read from, which is the local path if it contains no symlink
+    pub fn get_resolved_path(&self) -> &Path {
+        self.resolved_path.as_deref().unwrap_or(&self.local_path)
+    }
+
+    pub fn set_resolved_path(&mut self, resolved_path: PathBuf) {
+        if resolved_path != self.local_path {
+            self.resolved_path = Some(resolved_path);
+        }
+    }
     
     pub fn get_remote_parent(&self) -> &Path {
         &self.remote_parent
@@ -191,13 +232,148 @@ impl File {
         self.remote_parent.push(remote_parent);
     }
 
+    // returns the name of the file on nextcloud which defaults to the local file name
+    pub fn get_remote_name(&self) -> Option<String> {
+        if self.remote_name.is_some() {
+            return self.remote_name.clone()
+        }
+        self.local_path.file_name().and_then(|name| name.to_str()).map(|name| name.to_string())
+    }
+
+    pub fn set_remote_name(&mut self, remote_name: String) {
+        self.remote_name = Some(remote_name);
+    }
+
+    // returns the path of the file on nextcloud
+    pub fn get_remote_path(&self) -> PathBuf {
+        self.remote_parent.join(self.get_remote_name().unwrap_or_default())
+    }
+
     pub fn get_mtime(&self) -> i64 {
         self.mtime
     }
+
+    pub fn get_date_source(&self) -> Option<DateSource> {
+        self.date_source
+    }
+
+    pub fn set_date_source(&mut self, date_source: DateSource) {
+        self.date_source = Some(date_source);
+    }
+
+    pub fn has_unknown_date(&self) -> bool {
+        self.unknown_date
+    }
+
+    pub fn set_unknown_date(&mut self) {
+        self.unknown_date = true;
+    }
+
+    // returns the mtime in the time zone the file was recorded in, or in the local time zone if it is unknown
+    pub fn get_date(&self) -> Option<DateTime<FixedOffset>> {
+        match self.utc_offset {
+            Some(offset) => offset.timestamp_opt(self.mtime, 0).single(),
+            None => Local.timestamp_opt(self.mtime, 0).single().map(|date| date.fixed_offset())
+        }
+    }
+
+    pub fn set_utc_offset(&mut self, utc_offset: FixedOffset) {
+        self.utc_offset = Some(utc_offset);
+    }
+
+    pub fn replaces_existing(&self) -> bool {
+        self.replaces_existing
+    }
+
+    pub fn set_replaces_existing(&mut self) {
+        self.replaces_existing = true;
+    }
+
+    // returns the checksum of the upload if it was already computed with the algorithm with the given name, e.g. 'SHA256'
+    pub fn get_checksum(&self, algorithm: &str) -> Option<&str> {
+        self.checksum.get()
+            .filter(|checksum| checksum.split_once(':').is_some_and(|(name, _)| name == algorithm))
+            .map(|checksum| checksum.as_str())
+    }
+
+    // the checksum is set while the file is uploaded, which only has shared access to the file
+    pub fn set_checksum(&self, checksum: String) {
+        let _ = self.checksum.set(checksum);
+    }
     
 }
 
+// file or folder on the nextcloud server
+#[derive(Clone, Debug)]
+pub struct RemoteFile {
+    name: String,
+    is_folder: bool,
+    size: u64,
+    mtime: i64,
+    // changes whenever the content of the file changes on nextcloud
+    etag: String,
+    // stays the same when the file is moved or renamed on nextcloud
+    file_id: String,
+    checksums: HashMap<String, String>
+}
+
+impl RemoteFile {
+    pub fn new(name: String, is_folder: bool, size: u64, mtime: i64) -> RemoteFile {
+        RemoteFile {
+            name,
+            is_folder,
+            size,
+            mtime,
+            etag: String::new(),
+            file_id: String::new(),
+            checksums: HashMap::new()
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.is_folder
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_mtime(&self) -> i64 {
+        self.mtime
+    }
+
+    pub fn get_etag(&self) -> &str {
+        &self.etag
+    }
+
+    pub fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+
+    pub fn get_file_id(&self) -> &str {
+        &self.file_id
+    }
+
+    pub fn set_file_id(&mut self, file_id: String) {
+        self.file_id = file_id;
+    }
+
+    // returns the checksum the server stored for the algorithm with the given name, e.g. 'SHA256'
+    pub fn get_checksum(&self, algorithm: &str) -> Option<&str> {
+        self.checksums.get(algorithm).map(|hash| hash.as_str())
+    }
+
+    pub fn set_checksums(&mut self, checksums: HashMap<String, String>) {
+        self.checksums = checksums;
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -2,10 +2,15 @@ use std::io;
 use std::io::Write;
 use std::error::Error;
 use std::path::Path;
-use log::error;
+
+use crate::output;
 
 
 pub fn progress_bar(iteration: u64, total: u64, prefix: &str, suffix: &str) {
+    // the JSON output reports the progress with events instead
+    if output::is_json() {
+        return
+    }
     let fill = '█';
     let length = 50;
     let percent = 100.0 * (iteration as f64 / total as f64);
@@ -20,6 +25,37 @@ pub fn progress_bar(iteration: u64, total: u64, prefix: &str, suffix: &str) {
     }
 }
 
+// calculates the 64 bit FNV-1a hash of the given data, which unlike the std hashers is stable across releases
+pub fn hash_fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// decodes the percent encoded characters of an url segment
+pub fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let high = (bytes[i + 1] as char).to_digit(16);
+            let low = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
 // convertes a &Path to &str
 pub fn path_to_str(path: &Path) -> Result<String, Box<dyn Error>> {
     if let Some(path_str) = path.to_str() {
@@ -40,33 +76,20 @@ fn remove_extended_prefix(path: String) -> String {
 
 // determines the path to the folder containing the files or a file containing the paths to the files which will be uploaded
 // returning whether the path points to the folder a the file
-pub fn get_path_folder_or_file(path_upload: &mut String, local_path: Option<&String>, file_path: Option<&String>, working_dir: &Path) -> bool {
+pub fn get_path_folder_or_file(path_upload: &mut String, local_path: Option<&String>, file_path: Option<&String>, working_dir: &Path) -> Result<bool, Box<dyn Error>> {
     let mut from_folder = true;
     if let Some(local) = local_path {
-        // resolving local to a absolute path
-        let combinded_path = working_dir.join(&local);
-        let resolved_path = match combinded_path.canonicalize() {
-            Ok(absolute_path) => {
-                let absolute_path = remove_extended_prefix(absolute_path.to_str().unwrap().to_string());
-                absolute_path
-            }
-            Err(e) => {
-                error!("Failed resolving {} to an absolute path", e);
-                String::new()
-            }
-        };
-        // terminate programm if local could not be resolved to a absolute path
-        if resolved_path.is_empty() {
-            panic!()
-        }
-        *path_upload = resolved_path;
+        // resolving local to a absolute path, the command is stopped if local could not be resolved
+        let combinded_path = working_dir.join(local);
+        let absolute_path = combinded_path.canonicalize()
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed resolving {:?} to an absolute path: {}", combinded_path, e)))?;
+        *path_upload = remove_extended_prefix(path_to_str(&absolute_path)?);
     } else if let Some(file) = file_path {
         from_folder = false;
         *path_upload = file.to_string()
     } else {
-        error!("--local or --file is requried");
-        panic!()
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "--local or --file is requried")))
     };
 
-    from_folder
+    Ok(from_folder)
 }
\ No newline at end of file
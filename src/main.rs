@@ -1,24 +1,79 @@
 mod nextcloud;
 mod media;
+mod exiftool;
+mod metadata;
+mod date_source;
 mod filesystem;
 mod helpers;
 mod upload;
+mod session;
+mod checksum;
+mod verify;
+mod sync;
+mod download;
+mod queue;
+mod retry;
+mod prompt;
+mod output;
 
 use clap::builder::ValueParser;
 use nextcloud::NextcloudClient;
 use media::Extractor;
+use date_source::{DateSource, Timezone};
 use upload::sorted::upload_sorted;
 use upload::unsorted::upload_unsorted;
+use upload::common::{resume_upload, UploadOptions};
+use upload::collision::CollisionPolicy;
+use upload::conflict::ConflictPolicy;
+use upload::layout::{Layout, DEFAULT_UNKNOWN_DATE_FOLDER};
+use upload::filter::ScanFilter;
+use upload::plan::PlanFormat;
+use upload::apply::apply_plan;
+use checksum::ChecksumAlgorithm;
+use verify::verify;
+use sync::engine::{sync, SyncOptions};
+use sync::plan::SyncConflictPolicy;
+use download::download;
+use prompt::{CreateRoot, PromptMode};
+use output::{Event, OutputFormat};
 
 use std::env;
+use std::error::Error;
 use std::path::{Path, PathBuf};
-use flexi_logger::{Logger, Duplicate, FileSpec, WriteMode};
-use log::error;
-use clap::{Arg, ArgGroup, Command};
+use flexi_logger::{Logger, LoggerHandle, Duplicate, FileSpec, WriteMode};
+use log::{error, warn};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
 use colored::*;
 
 
-fn init_logger(config_folder: &Path) {
+// exit codes which let scripts tell a complete run, a run with failed files and a run which stopped apart.
+// Invalid command line options exit with 2
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FATAL: i32 = 1;
+const EXIT_PARTIAL: i32 = 3;
+
+// reports the result of the command, i.e. the number of files which failed or the error which stopped it, and terminates
+// the process with the matching exit code
+fn finish(command: &str, result: Result<usize, Box<dyn Error>>) -> ! {
+    let (status, exit_code, failed, error) = match result {
+        Ok(0) => ("success", EXIT_SUCCESS, 0, None),
+        Ok(num_failed) => {
+            warn!("Finished with {} failed file(s)", num_failed);
+            ("partial", EXIT_PARTIAL, num_failed, None)
+        }
+        Err(e) => {
+            error!("{}", e);
+            ("fatal", EXIT_FATAL, 0, Some(e.to_string()))
+        }
+    };
+    output::emit(&Event::Summary { command, status, exit_code, failed, error });
+
+    // write the buffered log messages before terminating
+    log::logger().flush();
+    std::process::exit(exit_code)
+}
+
+fn init_logger(config_folder: &Path) -> LoggerHandle {
     let log_filename = config_folder.join("process.log");
 
     // Initialize the logger
@@ -31,58 +86,65 @@ fn init_logger(config_folder: &Path) {
         .duplicate_to_stdout(Duplicate::Warn) // Optional: also output warnings and above to stdout
         .format_for_files(flexi_logger::detailed_format) // Format similar to Python's logging
         .start()
-        .unwrap();
+        .unwrap()
 }
 
-fn main() {
-    // get the current working directory
-    let working_dir: PathBuf;
-    match env::current_dir() {
-        Ok(val) => working_dir = val,
-        Err(e) => {
-            error!("Could not determine the current working directory: {}", e);
-            panic!()
-        }
-    }
-    
-    // get parent folder of executable
-    let mut exe_path: PathBuf;
-    match env::current_exe() {
-        Ok(val) => exe_path = val,
-        Err(e) => {
-            error!("{}", e);
-            panic!()
-        }
-    }
-    exe_path.pop();
-
-    let config_folder = exe_path.join("_nextsyncengine_");
-    init_logger(&config_folder);
+// creates the filter of the local files from --include, --exclude, --no-default-excludes, --skip-hidden,
+// --follow-symlinks and --max-depth
+fn get_scan_filter(matches: &ArgMatches) -> Result<ScanFilter, std::io::Error> {
+    let get_patterns = |name: &str| -> Vec<String> {
+        matches.get_many::<String>(name).map(|patterns| patterns.cloned().collect()).unwrap_or_default()
+    };
+    let mut scan_filter = ScanFilter::new(&get_patterns("include"), &get_patterns("exclude"), !matches.get_flag("no_default_excludes"))?;
+    scan_filter.set_skip_hidden(matches.get_flag("skip_hidden"));
+    scan_filter.set_follow_symlinks(matches.get_flag("follow_symlinks"));
+    scan_filter.set_max_depth(matches.get_one::<usize>("max_depth").copied());
+    Ok(scan_filter)
+}
 
-    let path = config_folder.join(".env");
-    dotenv::from_path(path).expect("Failed to read .env file");
+// reads the options shared by the upload commands and sets the chunking and the checksum algorithm of 'client'.
+// --on-conflict, --on-collision, the filter of the local files, --dry-run and --save-plan only exist for some of the
+// commands, the others overwrite existing files, skip colliding files and upload every file
+fn get_upload_options(matches: &ArgMatches, client: &mut NextcloudClient, config_folder: &Path, prompt: PromptMode, create_root: CreateRoot, retry_failed: Option<u32>) -> Result<UploadOptions, Box<dyn Error>> {
+    let has_arg = |id: &str| matches.try_contains_id(id).is_ok();
 
-    // Helper function to retrieve environment variables
-    fn get_env_var(var_name: &str) -> String {
-        env::var(var_name).unwrap_or_else(|e| {
-            error!("Error while reading '{}': {}", var_name, e);
-            String::new()
-        })
-    }
+    let chunk_threshold = matches.get_one::<u64>("chunk_threshold").expect("--chunk-threshold was not set");
+    let chunk_size = matches.get_one::<u64>("chunk_size").expect("--chunk-size was not set");
+    client.set_chunking(chunk_threshold * 1_000_000, chunk_size * 1_000_000);
+    client.set_checksum_algorithm(matches.get_one::<String>("checksum").expect("--checksum was not set").parse::<ChecksumAlgorithm>()?);
 
-    let server_url = get_env_var("SERVER_URL");
-    let username = get_env_var("NC_USERNAME");
-    let password = get_env_var("PASSWORD");
-    let exiftool = get_env_var("EXIFTOOL");
+    let on_conflict = match has_arg("on_conflict") {
+        true => matches.get_one::<String>("on_conflict").expect("--on-conflict was not set").parse::<ConflictPolicy>()?,
+        false => ConflictPolicy::Overwrite
+    };
+    let on_collision = match has_arg("on_collision") {
+        true => matches.get_one::<String>("on_collision").expect("--on-collision was not set").parse::<CollisionPolicy>()?,
+        false => CollisionPolicy::Skip
+    };
+    let scan_filter = match has_arg("include") {
+        true => get_scan_filter(matches)?,
+        false => ScanFilter::default()
+    };
 
-    let client = NextcloudClient::new(server_url, username.clone(), password);
-    let mut extractor = Extractor::new(exiftool);
-    if let Err(e) = extractor.get_supported_formats() {
-        error!("{}", e);
-        log::logger().flush();
-        panic!()
-    }
+    Ok(UploadOptions {
+        num_threads: *matches.get_one::<usize>("threads").expect("--threads was not set"),
+        on_conflict,
+        on_collision,
+        max_attempts: *matches.get_one::<u32>("max_attempts").expect("--max-attempts was not set"),
+        // journal of the current upload session which is used to resume an interrupted upload
+        session_path: config_folder.join("session.jsonl"),
+        // names chosen for local files with the same name which are uploaded to the same remote folder
+        names_path: config_folder.join("names.json"),
+        scan_filter,
+        dry_run: matches.try_get_one::<PlanFormat>("dry_run").ok().flatten().copied(),
+        save_plan: matches.try_get_one::<PathBuf>("save_plan").ok().flatten().cloned(),
+        prompt,
+        create_root,
+        retry_failed
+    })
+}
 
+fn main() {
     // common args between upload:sorted and upload:unsorted
     let local_arg =
         Arg::new("local")
@@ -99,6 +161,7 @@ fn main() {
             .required(true)
             .help("Path to the location on Nextcloud where your files will be uploaded too.");
 
+    #[allow(clippy::manual_range_contains)]
     let threads_arg =
         Arg::new("threads")
             .short('t')
@@ -113,6 +176,115 @@ fn main() {
             .default_value("3")
             .help("Lets you control the number of threads used to upload the files. The value must be between 1 and 6.");
     
+    let chunk_threshold_arg =
+        Arg::new("chunk_threshold")
+            .long("chunk-threshold")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .default_value("100")
+            .help("Files larger than this size in MB are uploaded in chunks.");
+
+    let chunk_size_arg =
+        Arg::new("chunk_size")
+            .long("chunk-size")
+            .value_parser(clap::value_parser!(u64).range(5..=5000))
+            .default_value("50")
+            .help("Size of a single chunk in MB when uploading large files. The value must be between 5 and 5000.");
+
+    let on_conflict_arg =
+        Arg::new("on_conflict")
+            .long("on-conflict")
+            .value_parser(["skip", "overwrite", "rename", "newer"])
+            .default_value("skip")
+            .help("Lets you control what happens with files which already exist on Nextcloud. Files with the same size and modification date are only uploaded again with 'overwrite'. Options are: skip, overwrite, rename and newer.");
+
+    let include_arg =
+        Arg::new("include")
+            .long("include")
+            .action(ArgAction::Append)
+            .value_parser(clap::value_parser!(String))
+            .help("Only uploads files whose name or path below --local matches the glob pattern, e.g. \"*.jpg\". Can be used multiple times.");
+
+    let exclude_arg =
+        Arg::new("exclude")
+            .long("exclude")
+            .action(ArgAction::Append)
+            .value_parser(clap::value_parser!(String))
+            .help("Skips files and folders whose name or path below --local matches the glob pattern, e.g. \"raw/**\". Can be used multiple times.");
+
+    let no_default_excludes_arg =
+        Arg::new("no_default_excludes")
+            .long("no-default-excludes")
+            .action(ArgAction::SetTrue)
+            .help("Uploads junk files like .DS_Store, Thumbs.db or .trashed-* which are skipped by default.");
+
+    let dry_run_arg =
+        Arg::new("dry_run")
+            .long("dry-run")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("table")
+            .value_parser(ValueParser::new(|s: &str| s.parse::<PlanFormat>().map_err(|e| e.to_string())))
+            .help("Prints where each file would be uploaded and which folders would be created without changing anything on Nextcloud. Use --dry-run=json for JSON instead of a table.");
+
+    let save_plan_arg =
+        Arg::new("save_plan")
+            .long("save-plan")
+            .value_parser(clap::value_parser!(PathBuf))
+            .help("Writes the upload plan to a JSON file instead of uploading. The plan can be reviewed and edited and is uploaded later with the apply command.");
+
+    let skip_hidden_arg =
+        Arg::new("skip_hidden")
+            .long("skip-hidden")
+            .action(ArgAction::SetTrue)
+            .help("Skips files and folders whose name starts with a dot.");
+
+    let follow_symlinks_arg =
+        Arg::new("follow_symlinks")
+            .long("follow-symlinks")
+            .action(ArgAction::SetTrue)
+            .help("Uploads the files and folders symlinks in --local point to. Symlinks are skipped by default, symlinks to already scanned folders are always skipped.");
+
+    let max_depth_arg =
+        Arg::new("max_depth")
+            .long("max-depth")
+            .value_parser(clap::value_parser!(usize))
+            .help("Maximum number of folder levels below --local which are scanned. 0 only uploads the files directly in --local.");
+
+    let on_collision_arg =
+        Arg::new("on_collision")
+            .long("on-collision")
+            .value_parser(["suffix", "hash", "skip"])
+            .default_value("suffix")
+            .help("Lets you control what happens if several local files with the same name would be uploaded to the same folder on Nextcloud. Options are: suffix (name_1.jpg), hash (name_<hash>.jpg) and skip.");
+
+    let date_sources_arg =
+        Arg::new("date_sources")
+            .long("date-sources")
+            .value_parser(ValueParser::new(|s: &str| date_source::parse_date_sources(s).map_err(|e| e.to_string())))
+            .default_value(date_source::DEFAULT_DATE_SOURCES)
+            .help("Comma separated list of the sources the date of a file is read from, in the order they are tried. Sources are: exif, quicktime, exiftool, filename, xmp, folder and mtime.");
+
+    let timezone_arg =
+        Arg::new("timezone")
+            .long("timezone")
+            .value_parser(ValueParser::new(|s: &str| s.parse::<Timezone>().map_err(|e| e.to_string())))
+            .default_value("local")
+            .help("Time zone of dates which were stored without one, like most camera dates and dates in file names. Options are: local, UTC, an offset like +02:00 or a name like Europe/Berlin.");
+
+    let checksum_arg =
+        Arg::new("checksum")
+            .long("checksum")
+            .value_parser(["sha256", "sha1", "md5"])
+            .default_value("sha256")
+            .help("Algorithm of the checksum which is sent with every file, so Nextcloud can store it and the upload can be verified later. Options are: sha256, sha1 and md5.");
+
+    let max_attempts_arg =
+        Arg::new("max_attempts")
+            .long("max-attempts")
+            .value_parser(clap::value_parser!(u32).range(1..=20))
+            .default_value("5")
+            .help("Maximum number of attempts to transfer a file before it is given up. Failed attempts are repeated with an increasing delay. The value must be between 1 and 20.");
+
     let file_arg = 
         Arg::new("file")
             .short('f')
@@ -122,17 +294,19 @@ fn main() {
                 let path = Path::new(&value);
                 if path.is_file() {
                     if let Some(ext) = path.extension() {
-                        if ext == "txt" {
+                        // plain lists of paths or failed uploads files written by nextsyncengine
+                        if ext == "txt" || ext == "jsonl" {
                             return Ok(value)
                         }
-                        return Err(format!("The provided path {} does not point to a .txt file", value))
+                        return Err(format!("The provided path {} does not point to a .txt or .jsonl file", value))
                     }
                     return Err(format!("Failed to extract the file extension from  {}", value))
                 }
                 Err(format!("The provided path {} does not point to a file", value))
             }))
-            .help("Path to the text file generated by nextsyncengine or any other text file with the same format.");
+            .help("Path to a text file with one local path per line or a failed uploads file (.jsonl) written by nextsyncengine.");
 
+    #[allow(clippy::needless_borrows_for_generic_args)]
     let local_required_group = 
         ArgGroup::new("local_required_group")
             .args(&["local", "file"])
@@ -145,6 +319,30 @@ fn main() {
         .about("Have a look at the README.md at https://github.com/SealJonny/nextsyncengine")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Answers every question with yes, e.g. creates a missing remote folder and tries failed uploads again once."),
+        )
+        .arg(
+            Arg::new("no_input")
+                .long("no-input")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("yes")
+                .help("Never waits for an answer and answers every question with no, e.g. for cron jobs. This is the default if stdin is not a terminal."),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true)
+                .help("Lets you control the output on stdout. 'json' writes one JSON event per line instead of status messages and progress bars and implies --no-input. Options are: text and json."),
+        )
         .propagate_version(true)
         .subcommand(
     Command::new("upload:sorted")
@@ -156,73 +354,269 @@ fn main() {
                     Arg::new("depth")
                         .short('d')
                         .long("depth")
-                        .value_parser(clap::value_parser!(String))
+                        .value_parser(["year", "month", "day"])
                         .default_value("month")
+                        .conflicts_with("layout")
                         .help("Lets you control the depth of the remote folder structure. Options are: year, month and day."),
                 )
+                .arg(
+                    Arg::new("unknown_date_folder")
+                        .long("unknown-date-folder")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value(DEFAULT_UNKNOWN_DATE_FOLDER)
+                        .help("Folder below --remote for files whose date could not be determined by any date source."),
+                )
+                .arg(
+                    Arg::new("layout")
+                        .long("layout")
+                        .value_parser(ValueParser::new(|s: &str| s.parse::<Layout>().map_err(|e| e.to_string())))
+                        .help("Template of the remote folder structure, e.g. \"{year}/{year}-{month} {month_name}/{camera_model}\". Replaces --depth, see the README for all placeholders."),
+                )
                 .arg(threads_arg.clone())
+                .arg(chunk_threshold_arg.clone())
+                .arg(chunk_size_arg.clone())
+                .arg(on_conflict_arg.clone())
+                .arg(on_collision_arg.clone())
+                .arg(include_arg.clone())
+                .arg(exclude_arg.clone())
+                .arg(no_default_excludes_arg.clone())
+                .arg(skip_hidden_arg.clone())
+                .arg(follow_symlinks_arg.clone())
+                .arg(max_depth_arg.clone())
+                .arg(date_sources_arg.clone())
+                .arg(timezone_arg.clone())
+                .arg(checksum_arg.clone())
+                .arg(max_attempts_arg.clone())
+                .arg(dry_run_arg.clone())
+                .arg(save_plan_arg.clone())
                 .group(local_required_group.clone())
         )
         .subcommand(
     Command::new("upload:unsorted")
-                .about("Allows you to upload files from a local folder and its sub folders to Nextcloud while getting rid of the original folder structure.")
+                .about("Allows you to upload files from a local folder and its sub folders to Nextcloud while getting rid of the original folder structure, unless --preserve-structure is used.")
                 .arg(local_arg.clone())
                 .arg(file_arg.clone())
                 .arg(remote_arg.clone())
+                .arg(
+                    Arg::new("preserve_structure")
+                        .long("preserve-structure")
+                        .action(ArgAction::SetTrue)
+                        .requires("local")
+                        .help("Recreates the folder structure of --local below --remote instead of uploading all files into a single folder."),
+                )
                 .arg(threads_arg.clone())
+                .arg(chunk_threshold_arg.clone())
+                .arg(chunk_size_arg.clone())
+                .arg(on_conflict_arg.clone())
+                .arg(on_collision_arg.clone())
+                .arg(include_arg.clone())
+                .arg(exclude_arg.clone())
+                .arg(no_default_excludes_arg.clone())
+                .arg(skip_hidden_arg.clone())
+                .arg(follow_symlinks_arg.clone())
+                .arg(max_depth_arg.clone())
+                .arg(date_sources_arg.clone())
+                .arg(timezone_arg.clone())
+                .arg(checksum_arg.clone())
+                .arg(max_attempts_arg.clone())
+                .arg(dry_run_arg.clone())
+                .arg(save_plan_arg.clone())
                 .group(local_required_group.clone())
         )
+        .subcommand(
+    Command::new("resume")
+                .about("Continues the last upload session exactly where it stopped, e.g. after a crash or an interruption.")
+                .arg(threads_arg.clone())
+                .arg(chunk_threshold_arg.clone())
+                .arg(chunk_size_arg.clone())
+                .arg(checksum_arg.clone())
+                .arg(max_attempts_arg.clone())
+        )
+        .subcommand(
+    Command::new("apply")
+                .about("Uploads the files of a plan written by --save-plan, e.g. after reviewing it or on another machine.")
+                .arg(
+                    Arg::new("plan")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Path to the plan file written by --save-plan."),
+                )
+                .arg(threads_arg.clone())
+                .arg(chunk_threshold_arg.clone())
+                .arg(chunk_size_arg.clone())
+                .arg(on_conflict_arg.clone())
+                .arg(checksum_arg.clone())
+                .arg(max_attempts_arg.clone())
+        )
+        .subcommand(
+    Command::new("verify")
+                .about("Compares the checksums of local files with the checksums Nextcloud stored for the uploaded files and reports any mismatches.")
+                .arg(local_arg.clone())
+                .arg(file_arg.clone())
+                .arg(remote_arg.clone())
+                .arg(include_arg.clone())
+                .arg(exclude_arg.clone())
+                .arg(no_default_excludes_arg.clone())
+                .arg(skip_hidden_arg.clone())
+                .arg(follow_symlinks_arg.clone())
+                .arg(max_depth_arg.clone())
+                .group(local_required_group.clone())
+        )
+        .subcommand(
+    Command::new("sync")
+                .about("Keeps a local folder and a folder on Nextcloud in step by applying new, changed, deleted and moved files of each side to the other side.")
+                .arg(local_arg.clone().required(true).help("Path to the local folder which is synchronized with Nextcloud."))
+                .arg(remote_arg.clone().help("Path to the folder on Nextcloud which is synchronized with the local folder."))
+                .arg(
+                    Arg::new("on_conflict")
+                        .long("on-conflict")
+                        .value_parser(["newer", "local", "remote", "keep-both", "skip"])
+                        .default_value("newer")
+                        .help("Lets you control which side wins if a file was changed on both sides since the last sync. Options are: newer, local, remote, keep-both and skip."),
+                )
+        )
+        .subcommand(
+    Command::new("download")
+                .about("Downloads a folder on Nextcloud including its sub folders to a local folder. Interrupted downloads are continued when running the command again.")
+                .arg(remote_arg.clone().help("Path to the folder on Nextcloud which will be downloaded."))
+                .arg(local_arg.clone().required(true).help("Path to the local folder where the files will be downloaded to."))
+                .arg(threads_arg.clone().help("Lets you control the number of threads used to download the files. The value must be between 1 and 6."))
+                .arg(max_attempts_arg.clone())
+        )
         .get_matches();
+
+    // the command line is parsed first, so every following error ends with the summary of the chosen output format
+    let command = matches.subcommand_name().unwrap_or_default();
+    let output_format = matches.get_one::<String>("output").expect("--output was not set").parse::<OutputFormat>().expect("--output is invalid");
+    output::set_format(output_format);
+
+    // get the current working directory. The logger is not running yet, so the errors are printed directly
+    let working_dir: PathBuf;
+    match env::current_dir() {
+        Ok(val) => working_dir = val,
+        Err(e) => {
+            output::println(format!("Could not determine the current working directory: {}", e).red());
+            finish(command, Err(e.into()))
+        }
+    }
     
-    
+    // get parent folder of executable
+    let mut exe_path: PathBuf;
+    match env::current_exe() {
+        Ok(val) => exe_path = val,
+        Err(e) => {
+            output::println(format!("Could not determine the location of nextsyncengine: {}", e).red());
+            finish(command, Err(e.into()))
+        }
+    }
+    exe_path.pop();
+
+    let config_folder = exe_path.join("_nextsyncengine_");
+    let mut logger = init_logger(&config_folder);
+
+    // the JSON output owns stdout, so warnings are written to stderr instead
+    if output::is_json() {
+        let _ = logger.adapt_duplication_to_stdout(Duplicate::None);
+        let _ = logger.adapt_duplication_to_stderr(Duplicate::Warn);
+    }
+
+    let path = config_folder.join(".env");
+    if let Err(e) = dotenv::from_path(&path) {
+        finish(command, Err(format!("Failed to read the .env file {:?}: {}", path, e).into()))
+    }
+
+    // Helper function to retrieve environment variables
+    fn get_env_var(var_name: &str) -> String {
+        env::var(var_name).unwrap_or_else(|e| {
+            error!("Error while reading '{}': {}", var_name, e);
+            String::new()
+        })
+    }
+
+    let server_url = get_env_var("SERVER_URL");
+    let username = get_env_var("NC_USERNAME");
+    let password = get_env_var("PASSWORD");
+    // exiftool is optional and only used for files the built-in metadata reader does not support
+    let exiftool = env::var("EXIFTOOL").ok();
+
+    let mut client = NextcloudClient::new(server_url, username.clone(), password);
+    let mut extractor = Extractor::new(exiftool);
+    if let Err(e) = extractor.get_supported_formats() {
+        warn!("exiftool is not available, only the built-in metadata reader is used: {}", e);
+        extractor = Extractor::new(None);
+    }
+
+    // questions are answered automatically with --yes, --no-input, the JSON output or without a terminal. CREATE_ROOT and
+    // RETRY_FAILED in the .env answer them in advance
+    let prompt = PromptMode::detect(matches.get_flag("yes"), matches.get_flag("no_input") || output::is_json());
+    let create_root = match env::var("CREATE_ROOT") {
+        Ok(value) => match value.parse::<CreateRoot>() {
+            Ok(create_root) => create_root,
+            Err(e) => finish(command, Err(e.into()))
+        },
+        Err(_) => CreateRoot::Ask
+    };
+    let retry_failed = match env::var("RETRY_FAILED") {
+        Ok(value) => match value.trim().parse::<u32>() {
+            Ok(retry_failed) => Some(retry_failed),
+            Err(e) => finish(command, Err(format!("Error while reading 'RETRY_FAILED': {}", e).into()))
+        },
+        Err(_) => None
+    };
+
     // checking if nextcloud server is online and not in maintenance mode and terminating execution if it is offline.
-    print!("{}", "Checking if Nextcloud server is online ... ".green());
+    output::print("Checking if Nextcloud server is online ... ".green());
     match client.is_online() {
         Ok(val) => {
             if !val {
-                println!("{}", "\nNextcloud server is offline or in maintenance mode!".red());
-                return
+                output::println("");
+                finish(command, Err("Nextcloud server is offline or in maintenance mode!".into()))
             }
-            println!("{}", "done".green())
-        }
-        Err(e) => {
-            error!("{}", e);
-            return
+            output::println("done".green())
         }
+        Err(e) => finish(command, Err(e))
     }
     
     // check if the credentials in the .env are valid
     match client.authenticate() {
-        Ok(true) => println!("{}", format!("You are logged in as {}.", &username).green()),
-        Ok(false) => {
-            println!("{}", "Your Nextcloud credentials are wrong. Check your .env!".red());
-            return
-        }
-        Err(e) =>  {
-            error!("{}", e);
-            return
-        }
+        Ok(true) => output::println(format!("You are logged in as {}.", &username).green()),
+        Ok(false) => finish(command, Err("Your Nextcloud credentials are wrong. Check your .env!".into())),
+        Err(e) => finish(command, Err(e))
     }
 
-    // check which command was used by the user
-    match matches.subcommand() {
+    // check which command was used by the user. Each command returns the number of files which failed
+    let result = match matches.subcommand() {
         Some(("upload:sorted", upload_matches)) => {
             // extract the options for upload:sorted
             let local_path = upload_matches.get_one::<String>("local");
             let file_path = upload_matches.get_one::<String>("file");
             let remote_path = upload_matches.get_one::<String>("remote").expect("--remote is required").trim().to_string();
-            let depth = upload_matches.get_one::<String>("depth").expect("--depth was not set").trim().to_string();
-            let num_threads = upload_matches.get_one::<usize>("threads").expect("--threads was not set");
+            let mut layout = match upload_matches.get_one::<Layout>("layout") {
+                Some(layout) => layout.clone(),
+                None => Layout::from_depth(upload_matches.get_one::<String>("depth").expect("--depth was not set")).expect("--depth is invalid")
+            };
+            let unknown_date_folder = upload_matches.get_one::<String>("unknown_date_folder").expect("--unknown-date-folder was not set");
+            if let Err(e) = layout.set_unknown_date_folder(unknown_date_folder) {
+                finish(command, Err(e.into()))
+            }
+            let options = match get_upload_options(upload_matches, &mut client, &config_folder, prompt, create_root, retry_failed) {
+                Ok(options) => options,
+                Err(e) => finish(command, Err(e))
+            };
+            let date_sources = upload_matches.get_one::<Vec<DateSource>>("date_sources").expect("--date-sources was not set");
+            extractor.set_date_sources(date_sources.clone());
+            let timezone = upload_matches.get_one::<Timezone>("timezone").expect("--timezone was not set");
+            extractor.set_timezone(*timezone);
 
             // determine if user chose local arg or file arg
             let mut path_upload = String::new();
-            let from_folder = helpers::get_path_folder_or_file(&mut path_upload, local_path, file_path, &working_dir);
+            let from_folder = match helpers::get_path_folder_or_file(&mut path_upload, local_path, file_path, &working_dir) {
+                Ok(from_folder) => from_folder,
+                Err(e) => finish(command, Err(e))
+            };
 
             // start the sorted upload of the files from 'path_upload' to 'remote_path'
-            match upload_sorted(path_upload, from_folder, remote_path, depth, *num_threads, client, extractor) {
-                Err(e) => error!("{}", e),
-                _ => {}
-            }
+            upload_sorted(path_upload, from_folder, remote_path, layout, client, extractor, &options)
         }
 
         Some(("upload:unsorted", upload_matches)) => {
@@ -230,23 +624,91 @@ fn main() {
             let local_path = upload_matches.get_one::<String>("local");
             let file_path = upload_matches.get_one::<String>("file");
             let remote_path = upload_matches.get_one::<String>("remote").expect("--remote is required").trim().to_string();
-            let num_threads = upload_matches.get_one::<usize>("threads").expect("--threads was not set");
+            let options = match get_upload_options(upload_matches, &mut client, &config_folder, prompt, create_root, retry_failed) {
+                Ok(options) => options,
+                Err(e) => finish(command, Err(e))
+            };
+            let date_sources = upload_matches.get_one::<Vec<DateSource>>("date_sources").expect("--date-sources was not set");
+            extractor.set_date_sources(date_sources.clone());
+            let timezone = upload_matches.get_one::<Timezone>("timezone").expect("--timezone was not set");
+            extractor.set_timezone(*timezone);
+            let preserve_structure = upload_matches.get_flag("preserve_structure");
 
             // determine if user chose local arg or file arg
             let mut path_upload = String::new();
-            let from_folder = helpers::get_path_folder_or_file(&mut path_upload, local_path, file_path, &working_dir);
+            let from_folder = match helpers::get_path_folder_or_file(&mut path_upload, local_path, file_path, &working_dir) {
+                Ok(from_folder) => from_folder,
+                Err(e) => finish(command, Err(e))
+            };
             
             // start the unsorted upload of the files from 'path_upload' to 'remote_path'
-            match upload_unsorted(path_upload, from_folder, remote_path, *num_threads, client, extractor) {
-                Err(e) => error!("{}", e),
-                _ => {}
-            }
+            upload_unsorted(path_upload, from_folder, remote_path, preserve_structure, client, extractor, &options)
         }
-        _ => {
-            error!("The command line options could not be parsed!");
-            return
+
+        Some(("resume", resume_matches)) => {
+            // extract the options for resume
+            let options = match get_upload_options(resume_matches, &mut client, &config_folder, prompt, create_root, retry_failed) {
+                Ok(options) => options,
+                Err(e) => finish(command, Err(e))
+            };
+
+            // continue the last upload session where it stopped
+            resume_upload(client, &options)
         }
-    }
 
-    log::logger().flush();
+        Some(("apply", apply_matches)) => {
+            // extract the options for apply
+            let plan_path = apply_matches.get_one::<PathBuf>("plan").expect("the plan is required");
+            let options = match get_upload_options(apply_matches, &mut client, &config_folder, prompt, create_root, retry_failed) {
+                Ok(options) => options,
+                Err(e) => finish(command, Err(e))
+            };
+
+            // upload the files of the plan, the names of colliding files were already chosen while planning
+            apply_plan(plan_path, client, &options)
+        }
+
+        Some(("verify", verify_matches)) => {
+            // extract the options for verify
+            let local_path = verify_matches.get_one::<String>("local");
+            let file_path = verify_matches.get_one::<String>("file");
+            let remote_path = verify_matches.get_one::<String>("remote").expect("--remote is required").trim().to_string();
+            let scan_filter = match get_scan_filter(verify_matches) {
+                Ok(scan_filter) => scan_filter,
+                Err(e) => finish(command, Err(Box::new(e)))
+            };
+
+            // determine if user chose local arg or file arg
+            let mut path_local = String::new();
+            let from_folder = match helpers::get_path_folder_or_file(&mut path_local, local_path, file_path, &working_dir) {
+                Ok(from_folder) => from_folder,
+                Err(e) => finish(command, Err(e))
+            };
+
+            // compare the local files with the checksums stored on nextcloud
+            verify(path_local, from_folder, remote_path, &client, &scan_filter)
+        }
+        Some(("sync", sync_matches)) => {
+            // extract the options for sync
+            let local_path = sync_matches.get_one::<String>("local").expect("--local is required").trim().to_string();
+            let remote_path = sync_matches.get_one::<String>("remote").expect("--remote is required").trim().to_string();
+            let on_conflict = sync_matches.get_one::<String>("on_conflict").expect("--on-conflict was not set").parse::<SyncConflictPolicy>().expect("--on-conflict is invalid");
+
+            // synchronize the local folder and the remote folder in both directions
+            let options = SyncOptions { on_conflict, state_folder: config_folder.join("sync"), prompt, create_root };
+            sync(local_path, remote_path, client, &options)
+        }
+        Some(("download", download_matches)) => {
+            // extract the options for download
+            let remote_path = download_matches.get_one::<String>("remote").expect("--remote is required").trim().to_string();
+            let local_path = download_matches.get_one::<String>("local").expect("--local is required").trim().to_string();
+            let num_threads = download_matches.get_one::<usize>("threads").expect("--threads was not set");
+            let max_attempts = download_matches.get_one::<u32>("max_attempts").expect("--max-attempts was not set");
+
+            // mirror the remote folder to the local folder
+            download(remote_path, local_path, client, *num_threads, *max_attempts)
+        }
+        _ => Err("The command line options could not be parsed!".into())
+    };
+    finish(command, result)
 }
\ No newline at end of file
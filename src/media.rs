@@ -1,37 +1,56 @@
-use std::process::Command;
+use std::collections::HashMap;
 use std::path::Path;
 use std::{io, vec};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
 use std::error::Error;
+use log::warn;
 
+use crate::date_source::{self, DateSource, Timezone};
+use crate::exiftool::{self, Exiftool};
+use crate::metadata::{self, Metadata, MetadataDate};
+
+// dates of the built-in metadata reader from the most to the least preferred
+const EXIF_DATES: [&str; 3] = ["DateTimeOriginal", "CreateDate", "ModifyDate"];
+const QUICKTIME_DATES: [&str; 3] = ["CreationDate", "CreateDate", "ModifyDate"];
+// dates requested from exiftool from the most to the least preferred and the tags of their time zones
+const EXIFTOOL_DATES: [(&str, &str); 3] = [("DateTimeOriginal", "OffsetTimeOriginal"), ("CreateDate", "OffsetTimeDigitized"), ("ModifyDate", "OffsetTime")];
+
+// extracts the dates and tags of files using the built-in metadata reader and exiftool as optional fallback
 pub struct Extractor {
-    exiftool: String,
-    supported_formats: Vec<String>
+    // exiftool running in batch mode or None if only the built-in reader is used
+    exiftool: Option<Exiftool>,
+    supported_formats: Vec<String>,
+    // sources of the date of a file in the order they are asked
+    date_sources: Vec<DateSource>,
+    // time zone of dates which were stored without one
+    timezone: Timezone
 }
 
 impl Extractor {
 
-    pub fn new(exiftool: String) -> Self {
+    pub fn new(exiftool: Option<String>) -> Self {
         Self {
-            exiftool: exiftool,
-            supported_formats: vec![]
+            exiftool: exiftool.filter(|exiftool| !exiftool.trim().is_empty()).map(Exiftool::new),
+            supported_formats: vec![],
+            date_sources: date_source::parse_date_sources(date_source::DEFAULT_DATE_SOURCES).unwrap(),
+            timezone: Timezone::Local
         }
     }
 
+    pub fn set_timezone(&mut self, timezone: Timezone) {
+        self.timezone = timezone;
+    }
+
+    pub fn set_date_sources(&mut self, date_sources: Vec<DateSource>) {
+        self.date_sources = date_sources;
+    }
+
     pub fn get_supported_formats(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        #[cfg(unix)]
-        let cmd = format!("{} -listwf | sed '1d'", &self.exiftool);
-
-        #[cfg(windows)]
-        let cmd = format!("{} -listwf | Select-Object -Skip 1", &self.exiftool);
-
-        let result = self.execute_shell_command(cmd)?;
-        let result = result.replace(" ", "\n");
-        let mut result: Vec<String> = result
-                .split_ascii_whitespace()
-                .map(|val| val.to_lowercase())
-                .collect();
-        self.supported_formats.append(&mut result);
+        let Some(exiftool) = &self.exiftool else {
+            return Ok(())
+        };
+        let mut formats = exiftool.list_writable_formats()?;
+        self.supported_formats.append(&mut formats);
         Ok(())
     }
 
@@ -44,8 +63,9 @@ impl Extractor {
         false
     }
 
-    // returns the modification date of a file as an unix timestamp. Returns an error if path does not point to a file
-    pub fn extract_date_time(&self, path: &Path) -> Result<i64, Box<dyn std::error::Error>> {
+    // returns the date of a file in the time zone it was recorded in and the source it was read from. The sources are
+    // asked in the configured order until one knows the date. Returns an error if path does not point to a file
+    pub fn extract_date_time(&self, path: &Path) -> Result<(DateTime<FixedOffset>, DateSource), Box<dyn std::error::Error>> {
         // checking if path points to a file and if not returnig an error
         if !path.is_file() {
             return Err(Box::new(
@@ -54,14 +74,63 @@ impl Extractor {
                 format!("Path: {} is not a file!", path.display()))));
         }
 
-        // checking if file is supported by exiftool if not using os to get mtime
-        if self.is_supported_by_exif(path) {
-            return self.extract_date_time_exif(path)
-        } else {
-            return self.extract_date_time_os(path)
+        // the built-in reader covers the common photo and video formats without starting a process per file
+        let metadata = match metadata::read_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Failed to read the metadata of {:?}: {}", path, e);
+                None
+            }
+        };
+
+        // a capture date which exists but can not be read, e.g. the placeholder of a camera without clock, means that the
+        // modification date is most likely not the capture date either, so the date of such a file is unknown
+        let mut invalid_date = metadata.as_ref()
+            .and_then(|metadata| EXIF_DATES.iter().chain(QUICKTIME_DATES.iter()).find(|name| metadata.has_invalid_date(name)))
+            .map(|name| format!("'{}'", name));
+
+        for source in &self.date_sources {
+            let date = match source {
+                DateSource::Exif => get_first_date(metadata.as_ref().filter(|metadata| !metadata.is_quicktime()), &EXIF_DATES),
+                DateSource::QuickTime => get_first_date(metadata.as_ref().filter(|metadata| metadata.is_quicktime()), &QUICKTIME_DATES),
+                DateSource::Exiftool if self.is_supported_by_exif(path) => match self.extract_date_time_exif(path, metadata.as_ref().is_some_and(|metadata| metadata.is_quicktime())) {
+                    Ok(date) => date,
+                    Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::InvalidData) => {
+                        invalid_date.get_or_insert(e.to_string());
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Failed to read the date of {:?} with exiftool: {}", path, e);
+                        None
+                    }
+                },
+                DateSource::Exiftool => None,
+                DateSource::Filename => date_source::read_filename_date(path),
+                DateSource::Xmp => match date_source::read_xmp_date(path) {
+                    Ok(date) => date,
+                    Err(e) => {
+                        warn!("Failed to read the sidecar of {:?}: {}", path, e);
+                        None
+                    }
+                },
+                DateSource::Folder => date_source::read_folder_date(path),
+                DateSource::Mtime => {
+                    if let Some(invalid_date) = invalid_date {
+                        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("The capture date {} of {:?} can not be read, so its modification date is not used", invalid_date, path))))
+                    }
+                    let mtime = self.extract_date_time_os(path)?;
+                    let date = self.timezone.at(mtime)
+                        .ok_or_else(|| io::Error::other(format!("Failed to convert the modification date {} of {:?}", mtime, path)))?;
+                    return Ok((date, *source))
+                }
+            };
+            if let Some(date) = date.and_then(|date| date.resolve(&self.timezone)) {
+                return Ok((date, *source))
+            }
         }
+        Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("None of the date sources knows the date of {:?}", path))))
     }
-    
+
     // extracts the modification date using the os
     fn extract_date_time_os(&self, path: &Path) -> Result<i64, Box<dyn std::error::Error>> {
         // handling potential error and returning mtime if present
@@ -72,80 +141,114 @@ impl Extractor {
 
     }
 
-    // extracts the modification date using the exiftool binary
-    fn extract_date_time_exif(&self, path: &Path) -> Result<i64, Box<dyn std::error::Error>> {
-        // converting the extracted date time string into a unix timestamp
-        let path_str: String;
-        if let Some(tmp) = path.to_str() {
-            path_str = tmp.to_string();
-        } else {
-            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "Failed to extract exif metadata due to a conversion error")))
+    // extracts the capture date using the exiftool binary. Returns None if the file has none of the dates
+    // 'quicktime' is true for QuickTime and MP4 files, whose CreateDate and ModifyDate are points in time in UTC
+    fn extract_date_time_exif(&self, path: &Path, quicktime: bool) -> Result<Option<MetadataDate>, Box<dyn std::error::Error>> {
+        let path_str = exiftool::path_arg(path)?;
+        let exiftool = self.exiftool.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "exiftool is not configured"))?;
+
+        // -s2 prints each tag as 'Name: Value', the date of the file system is left to the mtime source. QuickTimeUTC
+        // prints the UTC dates of QuickTime files with a time zone instead of passing them off as local time
+        let mut args: Vec<String> = vec!["-m".to_string(), "-s2".to_string(), "-api".to_string(), "QuickTimeUTC".to_string()];
+        for (date, offset) in EXIFTOOL_DATES {
+            args.push(format!("-{}", date));
+            args.push(format!("-{}", offset));
+        }
+        args.push(path_str);
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+
+        // exiftool prints nothing if the file has none of the tags
+        let result = match exiftool.execute(&args) {
+            Ok(result) => result,
+            Err(_) => return Ok(None)
+        };
+        let values: HashMap<&str, &str> = result.lines()
+            .filter_map(|line| line.split_once(": "))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+
+        // placeholders like '0000:00:00 00:00:00' can not be parsed and the next date is used
+        for (name, offset) in EXIFTOOL_DATES {
+            let Some(mut date) = values.get(name).and_then(|value| parse_exiftool_date(value)) else {
+                continue
+            };
+            if let Some(offset) = values.get(offset).and_then(|offset| offset.parse::<FixedOffset>().ok()) {
+                date.set_offset(offset);
+            }
+            // the time zone exiftool added to the date of a QuickTime file is the one of this computer
+            if quicktime && name != "DateTimeOriginal" {
+                date.set_utc();
+            }
+            return Ok(Some(date))
         }
 
-        #[cfg(unix)]
-        let cmd = format!("{} -m -s3 -d '%Y:%m:%d %H:%M:%S' -DateTime -ModifyDate -FileModifyDate \"{}\"", &self.exiftool, path_str);
-
-        #[cfg(windows)]
-        let cmd = format!("{} -m -s3 -d '%Y:%m:%d %H:%M:%S' -DateTime -ModifyDate -FileModifyDate \"{}\"", &self.exiftool, path_str);
-
-        // extract the date time from the file using exiftool
-        let result = self.execute_shell_command(cmd)?;
-        let result = result.replace("\r\n", "\n");
-
-        // only use the first found time by exiftool
-        let mut times = result
-        .split("\n") 
-        .map(|val| val.to_string())
-        .collect::<Vec<String>>();
-        let mut result = times.swap_remove(0);
+        // the file has dates, but none of them can be read
+        if let Some((date, _)) = EXIFTOOL_DATES.iter().find(|(date, _)| values.contains_key(date)) {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("'{}'", date))))
+        }
+        Ok(None)
+    }
 
-        // use the next extracted date time if the current date time is useless
-        while result == "0000:00:00 00:00:00" && !times.is_empty() {
-            result = times.swap_remove(0);
+    // extracts the values of the given exif tags, e.g. Model or LensModel. Tags the file does not have are missing in the returned map
+    pub fn extract_tags(&self, path: &Path, tags: &[String]) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let mut values: HashMap<String, String> = HashMap::new();
+        if tags.is_empty() {
+            return Ok(values)
         }
 
-        // attempt to convert result to a datetime object
-        let format = "%Y:%m:%d %H:%M:%S";
-        match NaiveDateTime::parse_from_str(&result, format) {
-            Ok(mtime) => Ok(mtime.and_utc().timestamp()),
-            Err(_e) => Err(Box::new(io::Error::new(io::ErrorKind::Other, format!("Failed to convert {} to a date time, {}", result, path_str))))
+        // read the tags with the built-in reader first
+        match metadata::read_metadata(path) {
+            Ok(Some(metadata)) => {
+                for tag in tags {
+                    if let Some(value) = metadata.get_tag(tag) {
+                        values.insert(tag.clone(), value.to_string());
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read the metadata of {:?}: {}", path, e)
         }
-    }
 
-    // execute the given cmd in the systems shell and return stdout
-    fn execute_shell_command(&self, cmd: String) -> Result<String, Box<dyn std::error::Error>> {
-        // execute 'cmd' in the systems shell
-        #[cfg(unix)]
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(&cmd)
-            .output()
-            .expect(format!("Failed to execute command {}", cmd).as_str());
-            
-        #[cfg(windows)]
-        let output = Command::new("powershell")
-            .arg("-Command")
-            .arg(&cmd)
-            .output()
-            .expect(format!("Failed to execute command {}", cmd).as_str());
-
-        // convert the ouput to a string and remove trailing whitespaces, line breackers or indents
-        let stdout = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).trim_end().to_string();
-
-        // return stdout if shell terminated successfully, otherwise return stderr
-        if !stdout.is_empty() && output.status.success() {
-            Ok(stdout)
-        } else {
-            Err(Box::new(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to extract metadata with exiftool: Exit Code {}, CMD: {}, {}", output.status.code().unwrap(), stderr, cmd)
-                    )
-                )
-            )
+        // only ask exiftool for tags the built-in reader does not know
+        let missing: Vec<&String> = tags.iter().filter(|tag| !values.contains_key(*tag)).collect();
+        let Some(exiftool) = self.exiftool.as_ref().filter(|_| !missing.is_empty() && self.is_supported_by_exif(path)) else {
+            return Ok(values)
+        };
+        let path_str = exiftool::path_arg(path)?;
+
+        // -s2 prints each tag as 'Name: Value'
+        let tag_args: Vec<String> = missing.iter().map(|tag| format!("-{}", tag)).collect();
+        let mut args: Vec<&str> = vec!["-m", "-s2"];
+        args.extend(tag_args.iter().map(|arg| arg.as_str()));
+        args.push(&path_str);
+
+        // exiftool prints nothing if the file has none of the tags, which is reported as error
+        let result = match exiftool.execute(&args) {
+            Ok(result) => result,
+            Err(_) => return Ok(values)
+        };
+        for line in result.lines() {
+            if let Some((name, value)) = line.split_once(": ") {
+                values.insert(name.trim().to_string(), value.trim().to_string());
+            }
         }
+        Ok(values)
     }
+}
 
+// returns the first of the given dates the metadata contains
+fn get_first_date(metadata: Option<&Metadata>, names: &[&str]) -> Option<MetadataDate> {
+    let metadata = metadata?;
+    names.iter().find_map(|name| metadata.get_date(name)).copied()
+}
+
+// parses a date printed by exiftool like '2024:03:15 12:00:00', which may have sub seconds and a time zone
+fn parse_exiftool_date(value: &str) -> Option<MetadataDate> {
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y:%m:%d %H:%M:%S%.f%:z") {
+        return Some(MetadataDate::new(date.naive_local(), Some(*date.offset())))
+    }
+    NaiveDateTime::parse_from_str(value, "%Y:%m:%d %H:%M:%S%.f").ok().map(|date_time| MetadataDate::new(date_time, None))
 }
 
 // custom Metadata struct for storing the mtime and size of a file
@@ -154,6 +257,7 @@ pub struct CustomMetadata {
     size: u64
 }
 
+#[allow(clippy::needless_return, clippy::redundant_field_names)]
 impl CustomMetadata {
     pub fn new(mtime: i64, size: u64) -> CustomMetadata {
         return CustomMetadata {
@@ -199,29 +303,146 @@ pub fn get_metadata(path: &str) -> Result<CustomMetadata, Box<dyn Error>> {
     Ok(CustomMetadata::new(mtime, size))
 }
 
-// #[cfg(test)]
-//  mod tests {
-//      use core::panic;
-
-//      use super::*;
-
-//      #[test]
-//      fn test_get_supported_formats() {
-//          let mut extractor = Extractor::new("c:\\nextsyncengine\\exiftool.exe".to_string());
-//          match extractor.get_supported_formats() {
-//              Ok(_val) => {
-//                  println!("{:?}", extractor.supported_formats);
-//                  assert!(true)
-//             }
-//              Err(e) => panic!("{}", e)
-//          }
-//      }
-
-//      #[test]
-//      fn test_is_supported_exif() {
-//          let mut extractor = Extractor::new("c:\\nextsyncengine\\exiftool.exe".to_string());
-//          let _ = extractor.get_supported_formats();
-//          assert!(extractor.is_supported_by_exif(Path::new("hallo.jpg")))
-
-//      }
-// }
\ No newline at end of file
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    // fake exiftool which lists two writable formats and returns the last argument of each command as camera model
+    const FAKE_EXIFTOOL: &str = "#!/bin/sh
+if [ \"$1\" = \"-listwf\" ]; then echo 'Writable file extensions:'; echo '  JPG MOV'; exit 0; fi
+while IFS= read -r line; do
+  if [ \"$line\" = \"-execute\" ]; then echo \"Model: $last\"; echo '{ready}'; else last=\"$line\"; fi
+done
+";
+
+    #[test]
+    fn test_extract_tags_hostile_filenames() {
+        let temp_dir = tempdir().unwrap();
+        let script = temp_dir.path().join("exiftool");
+        fs::write(&script, FAKE_EXIFTOOL).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut extractor = Extractor::new(Some(script.to_str().unwrap().to_string()));
+        extractor.get_supported_formats().unwrap();
+        assert_eq!(vec!["jpg".to_string(), "mov".to_string()], extractor.supported_formats);
+
+        // assert that quotes, command substitutions and backticks reach exiftool unchanged and are never executed
+        let tags = vec!["Model".to_string()];
+        for name in ["a\"b.jpg", "$(touch pwned).jpg", "`touch pwned`.jpg", "-delete_original.jpg"] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, "test file").unwrap();
+            let values = extractor.extract_tags(&path, &tags).unwrap();
+            assert_eq!(Some(path.to_str().unwrap()), values.get("Model").map(|value| value.as_str()));
+        }
+        assert!(!temp_dir.path().join("pwned").exists());
+        assert!(!Path::new("pwned").exists());
+    }
+
+    #[test]
+    fn test_extract_date_time_chain() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("IMG-20230514-WA0003.jpg");
+        fs::write(&path, "test file").unwrap();
+
+        // assert that a file without metadata is dated by its name before the file system is asked and that the
+        // date without time zone is interpreted in the configured time zone
+        let mut extractor = Extractor::new(None);
+        extractor.set_timezone("+02:00".parse().unwrap());
+        let (date, source) = extractor.extract_date_time(&path).unwrap();
+        assert_eq!((DateSource::Filename, "2023-05-14T00:00:00+02:00"), (source, date.to_rfc3339().as_str()));
+
+        // assert that the configured order is used and a chain without result is reported as error
+        extractor.set_date_sources(vec![DateSource::Mtime, DateSource::Filename]);
+        assert_eq!(DateSource::Mtime, extractor.extract_date_time(&path).unwrap().1);
+        extractor.set_date_sources(vec![DateSource::Exif, DateSource::Folder]);
+        assert!(extractor.extract_date_time(&path).is_err());
+    }
+
+    #[test]
+    fn test_extract_date_time_placeholder() {
+        // little endian tiff with only the tag DateTime, which holds the placeholder of a camera without clock
+        let mut tiff: Vec<u8> = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&0x0132u16.to_le_bytes());
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend_from_slice(&20u32.to_le_bytes());
+        tiff.extend_from_slice(&26u32.to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(b"0000:00:00 00:00:00\0");
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("DSC_0001.dng");
+        fs::write(&path, tiff).unwrap();
+
+        // assert that the modification date is not used for a file whose capture date can not be read
+        let extractor = Extractor::new(None);
+        let e = extractor.extract_date_time(&path).unwrap_err();
+        assert!(e.to_string().contains("'ModifyDate'"), "{}", e);
+
+        // assert that a date in the file name is still used
+        let path_named = temp_dir.path().join("IMG_20230514_153012.dng");
+        fs::rename(&path, &path_named).unwrap();
+        assert_eq!(DateSource::Filename, extractor.extract_date_time(&path_named).unwrap().1);
+    }
+
+    #[test]
+    fn test_extract_date_time_exif_quicktime() {
+        // prints the date like exiftool only if the dates of QuickTime files are requested as UTC
+        let script = "#!/bin/sh
+if [ \"$1\" = \"-listwf\" ]; then echo 'Writable file extensions:'; echo '  MOV MP4'; exit 0; fi
+utc=no
+while IFS= read -r line; do
+  if [ \"$line\" = \"QuickTimeUTC\" ]; then utc=yes; fi
+  if [ \"$line\" = \"-execute\" ]; then
+    if [ $utc = yes ]; then echo 'CreateDate: 2024:03:16 00:30:00+01:00'; fi
+    echo '{ready}'; utc=no
+  fi
+done
+";
+        let temp_dir = tempdir().unwrap();
+        let exiftool = temp_dir.path().join("exiftool");
+        fs::write(&exiftool, script).unwrap();
+        fs::set_permissions(&exiftool, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // video whose movie header has no dates, so only exiftool knows the date
+        let mut video = [0, 0, 0, 16].to_vec();
+        video.extend_from_slice(b"ftypqt  \0\0\0\0");
+        video.extend_from_slice(&[0, 0, 0, 28]);
+        video.extend_from_slice(b"moov");
+        video.extend_from_slice(&[0, 0, 0, 20]);
+        video.extend_from_slice(b"mvhd");
+        video.extend_from_slice(&[0; 12]);
+        let path = temp_dir.path().join("VID_0001.mov");
+        fs::write(&path, video).unwrap();
+
+        // assert that the time zone of the computer running exiftool is replaced by the configured one
+        let mut extractor = Extractor::new(Some(exiftool.to_str().unwrap().to_string()));
+        extractor.get_supported_formats().unwrap();
+        extractor.set_date_sources(vec![DateSource::QuickTime, DateSource::Exiftool]);
+        extractor.set_timezone("-05:00".parse().unwrap());
+        let (date, source) = extractor.extract_date_time(&path).unwrap();
+        assert_eq!((DateSource::Exiftool, "2024-03-15T18:30:00-05:00"), (source, date.to_rfc3339().as_str()));
+    }
+
+    #[test]
+    fn test_parse_exiftool_date() {
+        // assert that sub seconds and time zones are accepted and placeholders are rejected
+        let utc: Timezone = "UTC".parse().unwrap();
+        let date = parse_exiftool_date("2024:03:16 00:30:00.25+09:00").unwrap().resolve(&utc).unwrap();
+        assert_eq!("2024-03-16T00:30:00.250+09:00", date.to_rfc3339());
+        let date = parse_exiftool_date("2024:03:16 00:30:00").unwrap().resolve(&utc).unwrap();
+        assert_eq!("2024-03-16T00:30:00+00:00", date.to_rfc3339());
+        assert!(parse_exiftool_date("0000:00:00 00:00:00").is_none());
+    }
+
+    #[test]
+    fn test_get_supported_formats_missing_binary() {
+        // assert that a missing exiftool is reported as error instead of a panic
+        let mut extractor = Extractor::new(Some("/nonexistent/exiftool".to_string()));
+        assert!(extractor.get_supported_formats().is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::date_source::Timezone;
+
+// seconds between the QuickTime epoch (1904-01-01) and the unix epoch
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+// atoms which can start a QuickTime or MP4 file
+const QUICKTIME_ATOMS: [&[u8; 4]; 7] = [b"ftyp", b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot"];
+// upper limit for the size of a metadata value, which protects against corrupted files
+const MAX_VALUE_SIZE: u64 = 64 * 1024;
+
+// a date read from the metadata of a file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MetadataDate {
+    date_time: NaiveDateTime,
+    // offset to UTC if the file stores the time zone
+    offset: Option<FixedOffset>,
+    // true if 'date_time' is a point in time in UTC and the time zone it was recorded in is unknown, like the dates of
+    // a QuickTime movie header
+    utc: bool
+}
+
+impl MetadataDate {
+    pub fn new(date_time: NaiveDateTime, offset: Option<FixedOffset>) -> MetadataDate {
+        MetadataDate {
+            date_time,
+            offset,
+            utc: false
+        }
+    }
+
+    pub fn new_utc(date_time: NaiveDateTime) -> MetadataDate {
+        MetadataDate {
+            date_time,
+            offset: None,
+            utc: true
+        }
+    }
+
+    pub fn set_offset(&mut self, offset: FixedOffset) {
+        self.offset = Some(offset);
+    }
+
+    // turns the date into a point in time in UTC, which drops its offset because it is not the one of the recording
+    pub fn set_utc(&mut self) {
+        if let Some(offset) = self.offset.take() {
+            self.date_time -= Duration::seconds(offset.local_minus_utc() as i64);
+        }
+        self.utc = true;
+    }
+
+    // returns the date in the time zone it was recorded in. A date without time zone and a point in time in UTC are
+    // shown in 'timezone'
+    pub fn resolve(&self, timezone: &Timezone) -> Option<DateTime<FixedOffset>> {
+        if self.utc {
+            return timezone.at(self.date_time.and_utc().timestamp())
+        }
+        match self.offset {
+            Some(offset) => offset.from_local_datetime(&self.date_time).single(),
+            None => timezone.resolve(&self.date_time)
+        }
+    }
+}
+
+// metadata of a photo or video, using the tag names of exiftool, e.g. ModifyDate, DateTimeOriginal, Make or Model
+#[derive(Default, Debug)]
+pub struct Metadata {
+    dates: HashMap<String, MetadataDate>,
+    // names of the dates which exist but can not be read, e.g. placeholders like '0000:00:00 00:00:00'
+    invalid_dates: Vec<String>,
+    tags: HashMap<String, String>,
+    // true if the metadata was read from a QuickTime or MP4 container instead of EXIF data
+    quicktime: bool
+}
+
+impl Metadata {
+    pub fn is_quicktime(&self) -> bool {
+        self.quicktime
+    }
+
+    pub fn get_date(&self, name: &str) -> Option<&MetadataDate> {
+        self.dates.get(name)
+    }
+
+    pub fn has_invalid_date(&self, name: &str) -> bool {
+        self.invalid_dates.iter().any(|invalid| invalid == name)
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name).map(|value| value.as_str())
+    }
+}
+
+// reads the metadata of JPEG, TIFF, the TIFF based RAW formats (CR2, NEF, ARW, DNG, ...), HEIF/HEIC, PNG, WebP and
+// QuickTime/MP4 files without an external program. Returns None if the format is not supported or the file has no metadata
+pub fn read_metadata(path: &Path) -> Result<Option<Metadata>, Box<dyn Error>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+
+    let error = match exif::Reader::new().continue_on_error(true).read_from_container(&mut reader) {
+        Ok(exif) => return Ok(Some(read_exif(&exif))),
+        Err(e) => e
+    };
+    match error.distill_partial_result(|_| {}) {
+        // use the readable part of slightly corrupted metadata
+        Ok(exif) => Ok(Some(read_exif(&exif))),
+        // videos are not supported by the exif reader
+        Err(exif::Error::InvalidFormat(_)) | Err(exif::Error::NotFound(_)) if is_quicktime(&mut reader)? => {
+            Ok(Some(read_quicktime(&mut reader)?))
+        }
+        Err(exif::Error::InvalidFormat(_)) | Err(exif::Error::NotFound(_)) => Ok(None),
+        Err(e) => Err(Box::new(e))
+    }
+}
+
+// collects the dates and the text tags of the main image
+fn read_exif(exif: &exif::Exif) -> Metadata {
+    let mut metadata = Metadata::default();
+    for field in exif.fields().filter(|field| field.ifd_num == exif::In::PRIMARY) {
+        let exif::Value::Ascii(values) = &field.value else {
+            continue
+        };
+        let Some(value) = values.first() else {
+            continue
+        };
+
+        let date_name = match field.tag {
+            exif::Tag::DateTime => Some("ModifyDate"),
+            exif::Tag::DateTimeOriginal => Some("DateTimeOriginal"),
+            exif::Tag::DateTimeDigitized => Some("CreateDate"),
+            _ => None
+        };
+        match date_name {
+            Some(name) => match parse_exif_date(value) {
+                Some(date_time) => { metadata.dates.insert(name.to_string(), MetadataDate::new(date_time, None)); }
+                None => metadata.invalid_dates.push(name.to_string())
+            }
+            None => {
+                let value = String::from_utf8_lossy(value).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string();
+                if !value.is_empty() {
+                    metadata.tags.insert(field.tag.to_string(), value);
+                }
+            }
+        }
+    }
+
+    // the time zones of the dates are stored in separate tags since EXIF 2.31
+    for (name, offset_tag) in [("ModifyDate", "OffsetTime"), ("DateTimeOriginal", "OffsetTimeOriginal"), ("CreateDate", "OffsetTimeDigitized")] {
+        let offset = metadata.tags.get(offset_tag).and_then(|offset| offset.parse::<FixedOffset>().ok());
+        if let (Some(date), Some(offset)) = (metadata.dates.get_mut(name), offset) {
+            date.set_offset(offset);
+        }
+    }
+    metadata
+}
+
+// parses an exif date like '2024:03:15 12:00:00'. Placeholders like '0000:00:00 00:00:00' are ignored
+fn parse_exif_date(value: &[u8]) -> Option<NaiveDateTime> {
+    let date = exif::DateTime::from_ascii(value).ok()?;
+    NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)?
+        .and_hms_opt(date.hour as u32, date.minute as u32, date.second as u32)
+}
+
+// an atom of a QuickTime or MP4 file. 'start' is the position of the content after the header
+struct Atom {
+    kind: [u8; 4],
+    start: u64,
+    end: u64
+}
+
+fn is_quicktime<R: Read + Seek>(reader: &mut R) -> Result<bool, io::Error> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = [0u8; 8];
+    if reader.read_exact(&mut header).is_err() {
+        return Ok(false)
+    }
+    Ok(QUICKTIME_ATOMS.iter().any(|kind| header[4..8] == kind[..]))
+}
+
+// lists the atoms between 'start' and 'end'
+fn read_atoms<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<Vec<Atom>, io::Error> {
+    let mut atoms: Vec<Atom> = Vec::new();
+    let mut position = start;
+    while position + 8 <= end {
+        reader.seek(SeekFrom::Start(position))?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        // a size of 1 is followed by a 64 bit size and a size of 0 extends the atom to the end of its parent
+        let (content_start, atom_end) = match size {
+            0 => (position + 8, end),
+            1 => {
+                let mut large_size = [0u8; 8];
+                reader.read_exact(&mut large_size)?;
+                (position + 16, position.saturating_add(u64::from_be_bytes(large_size)))
+            }
+            _ => (position + 8, position + size)
+        };
+        if atom_end < content_start || atom_end > end {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid atom size"))
+        }
+        atoms.push(Atom { kind, start: content_start, end: atom_end });
+        position = atom_end;
+    }
+    Ok(atoms)
+}
+
+fn read_bytes<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> Result<Vec<u8>, io::Error> {
+    if len > MAX_VALUE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Metadata value is too large"))
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+// reads the dates of the movie header and the tags of the QuickTime metadata
+fn read_quicktime<R: Read + Seek>(reader: &mut R) -> Result<Metadata, io::Error> {
+    let mut metadata = Metadata { quicktime: true, ..Metadata::default() };
+    let len = reader.seek(SeekFrom::End(0))?;
+    let Some(moov) = read_atoms(reader, 0, len)?.into_iter().find(|atom| &atom.kind == b"moov") else {
+        return Ok(metadata)
+    };
+
+    for atom in read_atoms(reader, moov.start, moov.end)? {
+        match &atom.kind {
+            b"mvhd" => read_movie_header(reader, &atom, &mut metadata)?,
+            b"meta" => read_quicktime_meta(reader, &atom, &mut metadata)?,
+            b"udta" => {
+                for child in read_atoms(reader, atom.start, atom.end)? {
+                    if &child.kind == b"meta" {
+                        read_quicktime_meta(reader, &child, &mut metadata)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+// reads the creation and modification date of the movie header, which are stored in UTC without the time zone of the
+// recording, so they are shown in the configured time zone
+fn read_movie_header<R: Read + Seek>(reader: &mut R, mvhd: &Atom, metadata: &mut Metadata) -> Result<(), io::Error> {
+    let header = read_bytes(reader, mvhd.start, (mvhd.end - mvhd.start).min(20))?;
+    let (create, modify) = match header.first() {
+        Some(1) if header.len() >= 20 => (
+            u64::from_be_bytes(header[4..12].try_into().unwrap()) as i64,
+            u64::from_be_bytes(header[12..20].try_into().unwrap()) as i64
+        ),
+        Some(0) if header.len() >= 12 => (
+            u32::from_be_bytes(header[4..8].try_into().unwrap()) as i64,
+            u32::from_be_bytes(header[8..12].try_into().unwrap()) as i64
+        ),
+        _ => return Ok(())
+    };
+
+    for (name, seconds) in [("CreateDate", create), ("ModifyDate", modify)] {
+        // a date of 0 means that the date was never set
+        if seconds == 0 {
+            continue
+        }
+        if let Some(date_time) = DateTime::from_timestamp(seconds - QUICKTIME_EPOCH_OFFSET, 0) {
+            metadata.dates.insert(name.to_string(), MetadataDate::new_utc(date_time.naive_utc()));
+        }
+    }
+    Ok(())
+}
+
+// reads the entries of a QuickTime metadata atom, which maps the keys of the 'keys' atom to the values of the 'ilst' atom
+fn read_quicktime_meta<R: Read + Seek>(reader: &mut R, meta: &Atom, metadata: &mut Metadata) -> Result<(), io::Error> {
+    // the meta atom of MP4 files starts with a version and flags, the one of QuickTime files does not
+    let mut start = meta.start;
+    if read_bytes(reader, start, 4.min(meta.end - start))? == [0, 0, 0, 0] {
+        start += 4;
+    }
+    let atoms = read_atoms(reader, start, meta.end)?;
+
+    let mut keys: Vec<String> = Vec::new();
+    if let Some(keys_atom) = atoms.iter().find(|atom| &atom.kind == b"keys") {
+        // skip version, flags and the number of entries
+        for key in read_atoms(reader, keys_atom.start + 8, keys_atom.end)? {
+            keys.push(String::from_utf8_lossy(&read_bytes(reader, key.start, key.end - key.start)?).to_string());
+        }
+    }
+
+    let Some(ilst) = atoms.iter().find(|atom| &atom.kind == b"ilst") else {
+        return Ok(())
+    };
+    for item in read_atoms(reader, ilst.start, ilst.end)? {
+        // the type of an item is the index of its key starting at 1
+        let index = u32::from_be_bytes(item.kind) as usize;
+        let Some(key) = index.checked_sub(1).and_then(|index| keys.get(index)) else {
+            continue
+        };
+        let Some(data) = read_atoms(reader, item.start, item.end)?.into_iter().find(|atom| &atom.kind == b"data") else {
+            continue
+        };
+        // skip the type and the locale of the value
+        if data.end - data.start < 8 {
+            continue
+        }
+        let value = String::from_utf8_lossy(&read_bytes(reader, data.start + 8, data.end - data.start - 8)?).trim().to_string();
+
+        match key.as_str() {
+            "com.apple.quicktime.creationdate" => match parse_quicktime_date(&value) {
+                Some(date) => { metadata.dates.insert("CreationDate".to_string(), date); }
+                None => metadata.invalid_dates.push("CreationDate".to_string())
+            }
+            "com.apple.quicktime.make" => { metadata.tags.insert("Make".to_string(), value); }
+            "com.apple.quicktime.model" => { metadata.tags.insert("Model".to_string(), value); }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// parses a QuickTime date like '2024-03-15T13:00:00+0100'
+fn parse_quicktime_date(value: &str) -> Option<MetadataDate> {
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z") {
+        return Some(MetadataDate::new(date.naive_local(), Some(*date.offset())))
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok().map(|date_time| MetadataDate::new(date_time, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date_source::DateSource;
+    use crate::media::Extractor;
+    use tempfile::tempdir;
+
+    // 2024-03-15 12:00:00 UTC
+    const TIMESTAMP: i64 = 1710504000;
+
+    fn timestamp(date: &MetadataDate) -> i64 {
+        date.resolve(&"UTC".parse().unwrap()).unwrap().timestamp()
+    }
+
+    // creates an atom with the given type and content
+    fn atom(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut atom = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(content);
+        atom
+    }
+
+    #[test]
+    fn test_read_metadata_tiff() {
+        // little endian tiff with the tags Make, DateTime and a pointer to the Exif IFD in the first IFD, followed by
+        // the values and the Exif IFD with the time zone of DateTime
+        let mut tiff: Vec<u8> = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        tiff.extend_from_slice(&3u16.to_le_bytes());
+        for (tag, kind, count, offset) in [(0x010fu16, 2u16, 6u32, 50u32), (0x0132, 2, 20, 56), (0x8769, 4, 1, 76)] {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&kind.to_le_bytes());
+            tiff.extend_from_slice(&count.to_le_bytes());
+            tiff.extend_from_slice(&offset.to_le_bytes());
+        }
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(b"Canon\0");
+        tiff.extend_from_slice(b"2024:03:15 13:00:00\0");
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        for value in [0x9010u16, 2] {
+            tiff.extend_from_slice(&value.to_le_bytes());
+        }
+        tiff.extend_from_slice(&7u32.to_le_bytes());
+        tiff.extend_from_slice(&94u32.to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(b"+01:00\0");
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.dng");
+        fs::write(&path, tiff).unwrap();
+
+        // assert that the date is read with its time zone and the camera make is read
+        let metadata = read_metadata(&path).unwrap().unwrap();
+        assert_eq!(TIMESTAMP, timestamp(metadata.get_date("ModifyDate").unwrap()));
+        assert_eq!(Some("Canon"), metadata.get_tag("Make"));
+        assert!(!metadata.has_invalid_date("ModifyDate"));
+    }
+
+    #[test]
+    fn test_read_metadata_placeholder_date() {
+        // little endian tiff with only the tag DateTime, which holds the placeholder of a camera without clock
+        let mut tiff: Vec<u8> = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&0x0132u16.to_le_bytes());
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend_from_slice(&20u32.to_le_bytes());
+        tiff.extend_from_slice(&26u32.to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(b"0000:00:00 00:00:00\0");
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.dng");
+        fs::write(&path, tiff).unwrap();
+
+        // assert that the placeholder is not used as date but remembered as invalid date
+        let metadata = read_metadata(&path).unwrap().unwrap();
+        assert!(metadata.get_date("ModifyDate").is_none());
+        assert!(metadata.has_invalid_date("ModifyDate"));
+    }
+
+    #[test]
+    fn test_read_metadata_quicktime() {
+        // movie header with the creation date and QuickTime metadata with a creation date in a different time zone
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend_from_slice(&((TIMESTAMP + QUICKTIME_EPOCH_OFFSET) as u32).to_be_bytes());
+        mvhd.extend_from_slice(&0u32.to_be_bytes());
+        let key = b"com.apple.quicktime.creationdate";
+        let mut keys = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        keys.extend_from_slice(&atom(b"mdta", key));
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(b"2024-03-15T13:00:00+0100");
+        let ilst = atom(&1u32.to_be_bytes(), &atom(b"data", &data));
+        let meta = [atom(b"keys", &keys), atom(b"ilst", &ilst)].concat();
+        let moov = [atom(b"mvhd", &mvhd), atom(b"meta", &meta)].concat();
+        let video = [atom(b"ftyp", b"qt  \0\0\0\0"), atom(b"mdat", &[0; 16]), atom(b"moov", &moov)].concat();
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a.mov");
+        fs::write(&path, video).unwrap();
+
+        // assert that both dates describe the same point in time and the modification date is missing
+        let metadata = read_metadata(&path).unwrap().unwrap();
+        assert_eq!(TIMESTAMP, timestamp(metadata.get_date("CreateDate").unwrap()));
+        let creation_date = metadata.get_date("CreationDate").unwrap();
+        assert_eq!(TIMESTAMP, timestamp(creation_date));
+        let local_time = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap().and_hms_opt(13, 0, 0).unwrap();
+        assert_eq!(&MetadataDate::new(local_time, FixedOffset::east_opt(3600)), creation_date);
+        assert!(metadata.get_date("ModifyDate").is_none());
+
+        // assert that files without metadata are not supported
+        fs::write(&path, "text").unwrap();
+        assert!(read_metadata(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_movie_header_midnight() {
+        // movie header of a video recorded at 2024-03-15 23:30:00 UTC
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend_from_slice(&((TIMESTAMP + 41400 + QUICKTIME_EPOCH_OFFSET) as u32).to_be_bytes());
+        mvhd.extend_from_slice(&0u32.to_be_bytes());
+        let video = [atom(b"ftyp", b"isom\0\0\0\0"), atom(b"moov", &atom(b"mvhd", &mvhd))].concat();
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("VID_0001.mp4");
+        fs::write(&path, video).unwrap();
+
+        // assert that the UTC date is shown in the configured time zone, which already reached the next day
+        let mut extractor = Extractor::new(None);
+        extractor.set_timezone("+02:00".parse().unwrap());
+        let (date, source) = extractor.extract_date_time(&path).unwrap();
+        assert_eq!((DateSource::QuickTime, "2024-03-16T01:30:00+02:00"), (source, date.to_rfc3339().as_str()));
+        extractor.set_timezone("-05:00".parse().unwrap());
+        assert_eq!("2024-03-15T18:30:00-05:00", extractor.extract_date_time(&path).unwrap().0.to_rfc3339());
+    }
+}
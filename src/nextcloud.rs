@@ -1,24 +1,135 @@
-use reqwest::blocking::Client;
+use reqwest::blocking::{Body, Client};
+use reqwest::StatusCode;
+use std::fmt;
 use std::time::Duration;
+use std::sync::{Arc, Mutex};
 use std::fs::File as StdFile;
 use std::vec;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashMap;
 use std::error::Error;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::str::FromStr;
 use xml::reader::{EventReader, XmlEvent};
-use log::error;
 
-use crate::filesystem::File;
+use crate::filesystem::{File, RemoteFile};
+use crate::checksum::{self, ChecksumAlgorithm, Hasher};
 use crate::helpers;
 
+// files larger than this are uploaded in chunks by default (100 MB)
+const DEFAULT_CHUNK_THRESHOLD: u64 = 100_000_000;
+// default size of a single chunk (50 MB), Nextcloud requires chunks between 5 MB and 5 GB
+const DEFAULT_CHUNK_SIZE: u64 = 50_000_000;
+// nextcloud accepts at most 10000 chunks per file and chunks of up to 5 GB
+const MAX_CHUNKS: u64 = 10_000;
+const MAX_CHUNK_SIZE: u64 = 5_000_000_000;
+
+// callback which receives the number of bytes sent since its last call
+pub type ProgressCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+// wraps a reader and reports the number of bytes read from it to a progress callback
+struct ProgressReader<R: Read> {
+    inner: R,
+    progress: Option<ProgressCallback>,
+    // computes the checksum of the content while it is streamed
+    hasher: Option<Arc<Mutex<Hasher>>>
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(hasher) = &self.hasher {
+            hasher.lock().unwrap().update(&buf[..read]);
+        }
+        if let Some(progress) = &self.progress {
+            progress(read as u64);
+        }
+        Ok(read)
+    }
+}
+
+// error status returned by the nextcloud server, including how long the server asked the client to wait before trying again
+#[derive(Debug)]
+pub struct HttpError {
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    source: reqwest::Error
+}
+
+impl HttpError {
+    fn new(source: reqwest::Error, response: &reqwest::blocking::Response) -> HttpError {
+        let retry_after = response.headers().get(reqwest::header::RETRY_AFTER)
+            .and_then(|val| val.to_str().ok())
+            .and_then(parse_retry_after);
+        HttpError {
+            status: response.status(),
+            retry_after,
+            source
+        }
+    }
+
+    pub fn get_status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn get_retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Error for HttpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+// parses the value of the 'Retry-After' header, which is either a number of seconds or a http date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds))
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64;
+    Some(Duration::from_secs(seconds))
+}
+
+// returns the ETag header of a response, which identifies the version of a remote file
+fn get_etag(response: &reqwest::blocking::Response) -> Option<String> {
+    response.headers().get("ETag").and_then(|etag| etag.to_str().ok()).map(|etag| etag.to_string())
+}
+
+// returns the http status of an error returned by the client, which is None for errors without a response like timeouts
+pub fn get_error_status(e: &(dyn Error + 'static)) -> Option<StatusCode> {
+    if let Some(http_err) = e.downcast_ref::<HttpError>() {
+        return Some(http_err.get_status())
+    }
+    e.downcast_ref::<reqwest::Error>().and_then(|e| e.status())
+}
+
+// a single resource of the multistatus response to a PROPFIND request
+struct DavResource {
+    href: String,
+    is_collection: bool,
+    props: HashMap<String, String>
+}
+
 #[derive(Clone)]
 pub struct NextcloudClient {
     url_server: String,
     url_dav: String,
+    url_uploads: String,
     username: String,
     password: String,
+    chunk_threshold: u64,
+    chunk_size: u64,
+    checksum_algorithm: ChecksumAlgorithm,
     client: Client
 }
 
@@ -27,12 +138,20 @@ impl NextcloudClient {
     pub fn new(url_server: String, username: String, password: String) -> NextcloudClient {
         let mut url_dav = url_server.clone();
         url_dav.push_str(format!("/remote.php/dav/files/{}", username).as_str());
+
+        // the chunking v2 api stores the chunks in a separate upload folder of the user
+        let mut url_uploads = url_server.clone();
+        url_uploads.push_str(format!("/remote.php/dav/uploads/{}", username).as_str());
         
-        return NextcloudClient{
-            url_server: url_server,
-            url_dav: url_dav,
-            username: username,
-            password: password,
+        NextcloudClient{
+            url_server,
+            url_dav,
+            url_uploads,
+            username,
+            password,
+            chunk_threshold: DEFAULT_CHUNK_THRESHOLD,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            checksum_algorithm: ChecksumAlgorithm::Sha256,
             client: Client::builder()
                 .timeout(Duration::from_secs(2700))
                 .build()
@@ -69,30 +188,56 @@ impl NextcloudClient {
         Ok(false)
     }
     
-    // uploads a file to the specified location on a nextcloud server
-    pub fn upload_file(&self, file: &File) -> Result<(), Box<dyn Error>> {
-        // parse the file content into a vector needed to send the content via http request
-        let local_path = file.get_local_path();
-        let mtime = file.get_mtime();
-        let remote_parent = file.get_remote_parent();
+    // sets the file size above which files are uploaded in chunks and the size of a single chunk
+    pub fn set_chunking(&mut self, chunk_threshold: u64, chunk_size: u64) {
+        self.chunk_threshold = chunk_threshold;
+        self.chunk_size = chunk_size;
+    }
 
-        let file_content = Self::read_file_to_vec(local_path)?;
+    // returns the size of the chunks of a file, which is raised if the file would need more than MAX_CHUNKS chunks
+    fn get_chunk_size(&self, total_size: u64) -> Result<u64, io::Error> {
+        let chunk_size = self.chunk_size.max(total_size.div_ceil(MAX_CHUNKS));
+        if chunk_size > MAX_CHUNK_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The file is too large to be uploaded in at most {} chunks of up to {} bytes", MAX_CHUNKS, MAX_CHUNK_SIZE)))
+        }
+        Ok(chunk_size)
+    }
 
-        // extract the file name {from local_path and build the final url
-        let url: String;
-        if let Some(file_name) = local_path.file_name().and_then(|name| name.to_str()) {
-            let remote_parent = helpers::path_to_str(remote_parent)?;
-            url = self.build_url(vec![remote_parent.as_str(), file_name])
+    // sets the algorithm of the checksum which is sent with every uploaded file
+    pub fn set_checksum_algorithm(&mut self, checksum_algorithm: ChecksumAlgorithm) {
+        self.checksum_algorithm = checksum_algorithm;
+    }
 
-        } else {
-            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "Extracting the file name from local path failed!")));
+    // uploads a file to the specified location on a nextcloud server.
+    // 'progress' is called with the number of bytes sent while the file content is streamed to the server
+    pub fn upload_file(&self, file: &File, progress: Option<ProgressCallback>) -> Result<(), Box<dyn Error>> {
+        // large files are uploaded in chunks so a failure does not restart the whole upload
+        if file.get_size() > self.chunk_threshold {
+            return self.upload_file_chunked(file, progress)
         }
 
+        // the checksum is sent as header in front of the content, so the file is hashed before its first upload attempt.
+        // Nextcloud stores it and returns it as 'oc:checksums' which allows to verify the upload later
+        let checksum = match file.get_checksum(self.checksum_algorithm.get_name()) {
+            Some(checksum) => checksum.to_string(),
+            None => {
+                let checksum = checksum::format_checksum(self.checksum_algorithm, &checksum::hash_file(file.get_resolved_path(), self.checksum_algorithm)?);
+                file.set_checksum(checksum.clone());
+                checksum
+            }
+        };
+
+        // stream the file content from disk instead of reading the whole file into memory
+        let mtime = file.get_mtime();
+        let body = Self::open_file_body(file.get_resolved_path(), 0, file.get_size(), progress, None)?;
+        let url = self.build_file_url(file)?;
+
         // send file to server using a http PUT request. The header 'X-OC-MTime' specifies the modification date which will be shown on the nextcloud UI
         let response = self.client.put(url.as_str())
             .header("X-OC-MTime", format!("{}", mtime))
+            .header("OC-Checksum", checksum.as_str())
             .basic_auth(&self.username, Some(&self.password))
-            .body(file_content)
+            .body(body)
             .send()?;
 
         // checking reponse for errors
@@ -100,22 +245,159 @@ impl NextcloudClient {
 
     }
     
-    // lists a folder
-    pub fn ls(&self, path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-        // Todo: Optimize the error handling!
-        
-        let prop = r#"<?xml version='1.0'?> 
+    // uploads a file using the chunking v2 api of nextcloud:
+    // creates an upload folder, uploads the numbered chunks into it and finally assembles them at the destination.
+    // Chunks which are already on the server from an interrupted attempt are not uploaded again.
+    // The checksum is computed while the chunks are streamed and sent with the final MOVE
+    fn upload_file_chunked(&self, file: &File, progress: Option<ProgressCallback>) -> Result<(), Box<dyn Error>> {
+        let local_path = file.get_resolved_path();
+        let total_size = file.get_size();
+        let chunk_size = self.get_chunk_size(total_size)?;
+        let destination = reqwest::Url::parse(&self.build_file_url(file)?)?.to_string();
+        let url_upload_folder = format!("{}/{}", self.url_uploads, self.get_upload_id(file));
+
+        // continue an interrupted upload or create the upload folder which will temporarily hold the chunks
+        let uploaded_chunks = match self.ls_chunks(&url_upload_folder)? {
+            Some(chunks) if !chunks_match(&chunks, chunk_size, total_size) => {
+                // the chunks were uploaded with another chunk size, e.g. before --chunk-size was changed, and can not be continued
+                let response = self.client.delete(url_upload_folder.as_str())
+                    .basic_auth(&self.username, Some(&self.password))
+                    .send()?;
+                self.evaluate_response_for_error(&response)?;
+                None
+            }
+            chunks => chunks
+        };
+        if uploaded_chunks.is_none() {
+            let mkcol = reqwest::Method::from_str("MKCOL")?;
+            let response = self.client.request(mkcol, url_upload_folder.as_str())
+                .header("Destination", destination.as_str())
+                .basic_auth(&self.username, Some(&self.password))
+                .send()?;
+            self.evaluate_response_for_error(&response)?;
+        }
+        let uploaded_chunks = uploaded_chunks.unwrap_or_default();
+
+        // the chunks are uploaded in order, so one hasher sees the whole content. It is not needed if a previous attempt
+        // already streamed all chunks
+        let cached_checksum = file.get_checksum(self.checksum_algorithm.get_name());
+        let hasher = match cached_checksum {
+            Some(_) => None,
+            None => Some(Arc::new(Mutex::new(Hasher::new(self.checksum_algorithm))))
+        };
+
+        // upload the chunks, the names of the chunks must be numbers between 1 and 10000 sorted in the upload order
+        let num_chunks = total_size.div_ceil(chunk_size);
+        let mut resumable = true;
+        for index in 0..num_chunks {
+            let offset = index * chunk_size;
+            let length = chunk_size.min(total_size - offset);
+            let chunk_name = format!("{:05}", index + 1);
+
+            // skip the leading chunks which were completely uploaded by a previous attempt
+            if resumable && uploaded_chunks.get(&chunk_name) == Some(&length) {
+                // the content of the chunk is still part of the checksum, but reading it locally is cheaper than uploading it
+                if let Some(hasher) = &hasher {
+                    hasher.lock().unwrap().update_from_file(local_path, offset, length)?;
+                }
+                if let Some(progress) = &progress {
+                    progress(length);
+                }
+                continue
+            }
+            resumable = false;
+
+            let chunk = Self::open_file_body(local_path, offset, length, progress.clone(), hasher.clone())?;
+            let response = self.client.put(format!("{}/{}", url_upload_folder, chunk_name))
+                .header("Destination", destination.as_str())
+                .header("OC-Total-Length", format!("{}", total_size))
+                .basic_auth(&self.username, Some(&self.password))
+                .body(chunk)
+                .send()?;
+
+            // the upload folder is kept on errors, so the next attempt can continue with this chunk
+            self.evaluate_response_for_error(&response)?;
+        }
+
+        let checksum = match hasher {
+            Some(hasher) => {
+                // the body of the last chunk may still hold a reference to the hasher, so its state is taken out
+                let hasher = std::mem::replace(&mut *hasher.lock().unwrap(), Hasher::new(self.checksum_algorithm));
+                let checksum = checksum::format_checksum(self.checksum_algorithm, &hasher.finalize());
+                file.set_checksum(checksum.clone());
+                checksum
+            }
+            None => cached_checksum.unwrap_or_default().to_string()
+        };
+
+        // assemble the chunks by moving the virtual file '.file' to the destination
+        let move_method = reqwest::Method::from_str("MOVE")?;
+        let response = self.client.request(move_method, format!("{}/.file", url_upload_folder))
+            .header("Destination", destination.as_str())
+            .header("OC-Total-Length", format!("{}", total_size))
+            .header("X-OC-MTime", format!("{}", file.get_mtime()))
+            .header("OC-Checksum", checksum.as_str())
+            .basic_auth(&self.username, Some(&self.password))
+            .send()?;
+
+        self.evaluate_response_for_error(&response)
+    }
+
+    // lists the chunks in an upload folder with their sizes. Returns None if the upload folder does not exist
+    fn ls_chunks(&self, url_upload_folder: &str) -> Result<Option<HashMap<String, u64>>, Box<dyn Error>> {
+        let prop = r#"<?xml version='1.0'?>
                     <d:propfind xmlns:d="DAV:" >
+                        <d:prop>
+                            <d:getcontentlength />
+                        </d:prop>
+                    </d:propfind>
+        "#;
+
+        let propfind = reqwest::Method::from_str("PROPFIND")?;
+        let response = self.client.request(propfind, url_upload_folder)
+            .header("Content-Type", "application/xml")
+            .header("Depth", "1")
+            .basic_auth(&self.username, Some(&self.password))
+            .body(prop)
+            .send()?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None)
+        }
+        self.evaluate_response_for_error(&response)?;
+
+        // map the names of the chunks to their sizes
+        let mut chunks: HashMap<String, u64> = HashMap::new();
+        for resource in Self::extract_resources_xml(&response.text()?)? {
+            if resource.is_collection {
+                continue
+            }
+            let name = resource.href.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string();
+            if let Some(size) = resource.props.get("getcontentlength").and_then(|val| val.parse::<u64>().ok()) {
+                chunks.insert(name, size);
+            }
+        }
+        Ok(Some(chunks))
+    }
+
+    // lists the files and folders of a folder including their size, modification date, etag, file id and checksums
+    pub fn ls_files(&self, path: &Path) -> Result<Vec<RemoteFile>, Box<dyn Error>> {
+        let prop = r#"<?xml version='1.0'?>
+                    <d:propfind xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
                         <d:prop>
                             <d:displayname />
                             <d:resourcetype />
+                            <d:getcontentlength />
+                            <d:getlastmodified />
+                            <d:getetag />
+                            <oc:fileid />
+                            <oc:checksums />
                         </d:prop>
                     </d:propfind>
         "#;
 
-        let url: String;
         let path = helpers::path_to_str(path)?;
-        url = self.build_url(vec![path.as_str()]);
+        let url: String = self.build_url(vec![path.as_str()]);
 
         let propfind = reqwest::Method::from_str("PROPFIND")?;
 
@@ -127,24 +409,42 @@ impl NextcloudClient {
             .send()?;
 
         // checking the status code for erros
-        if let Err(e) = self.evaluate_response_for_error(&response) {
-            return Err(e)
+        self.evaluate_response_for_error(&response)?;
+
+        // convert the resources into remote files and remove the listed folder itself from the result
+        let mut files: Vec<RemoteFile> = Vec::new();
+        for resource in Self::extract_resources_xml(&response.text()?)?.into_iter().skip(1) {
+            // prefer the display name and fall back to the last segment of the href
+            let name = match resource.props.get("displayname") {
+                Some(name) => name.to_string(),
+                None => helpers::percent_decode(resource.href.trim_end_matches('/').rsplit('/').next().unwrap_or_default())
+            };
+            let size = resource.props.get("getcontentlength").and_then(|val| val.parse::<u64>().ok()).unwrap_or(0);
+            let mtime = resource.props.get("getlastmodified")
+                .and_then(|val| chrono::DateTime::parse_from_rfc2822(val).ok())
+                .map(|val| val.timestamp())
+                .unwrap_or(0);
+            let mut remote_file = RemoteFile::new(name, resource.is_collection, size, mtime);
+            if let Some(etag) = resource.props.get("getetag") {
+                remote_file.set_etag(etag.trim_matches('"').to_string());
+            }
+            if let Some(file_id) = resource.props.get("fileid") {
+                remote_file.set_file_id(file_id.to_string());
+            }
+            if let Some(checksums) = resource.props.get("checksum") {
+                remote_file.set_checksums(checksum::parse_checksums(checksums));
+            }
+            files.push(remote_file);
         }
-        
-        // remove the root folder from the result
-        let response = response.text()?;
-        let mut folders = self.extract_folder_xml(&response)?;
-        folders.remove(0);
-        Ok(folders)
+        Ok(files)
     }
 
     // queries the nextcloud sever if a folder at 'path' exists and returns the result
     pub fn exists_folder(&self, path: &Path) -> Result<bool, Box<dyn Error>> {
 
         // build the final url appending path to url_server
-        let url: String;
         let path = helpers::path_to_str(path)?;
-        url = self.build_url(vec![path.as_str()]);
+        let url: String = self.build_url(vec![path.as_str()]);
 
         // query the server if this folder exists and returnig the erros directly to the caller of this method
         let propfind = reqwest::Method::from_str("PROPFIND")?;
@@ -161,21 +461,16 @@ impl NextcloudClient {
             return Ok(true)
         }
 
-        // checking the responses for errors, if none are found the programm will be terminated
-        // because the request returned an unexpected status code
-        if let Err(e) = self.evaluate_response_for_error(&response) {
-            return Err(e)
-        }
-        error!("exists_folder returned a unhandled response code!");
-        panic!()
+        // checking the responses for errors, a response without error but with another status code is unexpected as well
+        self.evaluate_response_for_error(&response)?;
+        Err(Box::new(io::Error::other(format!("Unexpected status {} while checking if {:?} exists", response.status(), path))))
     }
 
     // creates a folder on the nextcloud server at the location 'path'
     pub fn create_folder(&self, path: &Path) -> Result<(), Box<dyn Error>> {
         // build url containing the dav url and the location of the new folder
-        let url: String;
         let path = helpers::path_to_str(path)?;
-        url = self.build_url(vec![path.as_str()]);
+        let url: String = self.build_url(vec![path.as_str()]);
         
         // creating the http method
         let mkcol = reqwest::Method::from_str("MKCOL")?;
@@ -188,20 +483,136 @@ impl NextcloudClient {
         self.evaluate_response_for_error(&response)
     }
 
+    // downloads the file at 'remote_path' to 'local_path' and sets its modification date to 'mtime'.
+    // The content is streamed into a temporary file next to the destination, so an existing file is only replaced by a complete download.
+    // An interrupted download is continued at the end of the temporary file using a range request. The ETag of the remote file is
+    // recorded next to the temporary file and sent with If-Range, so bytes of a remote file which changed since are never appended
+    pub fn download_file(&self, remote_path: &Path, local_path: &Path, mtime: i64, progress: Option<ProgressCallback>) -> Result<(), Box<dyn Error>> {
+        let path = helpers::path_to_str(remote_path)?;
+        let url: String = self.build_url(vec![path.as_str()]);
+
+        let file_name = local_path.file_name().and_then(|name| name.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Extracting the file name from local path failed!"))?;
+        let tmp_path = local_path.with_file_name(format!(".{}.part", file_name));
+        let etag_path = local_path.with_file_name(format!(".{}.part.etag", file_name));
+
+        // a temporary file is only continued if the version of the remote file it belongs to is known
+        let etag = std::fs::read_to_string(&etag_path).ok().map(|etag| etag.trim().to_string()).filter(|etag| !etag.is_empty());
+        let offset = match &etag {
+            Some(_) => std::fs::metadata(&tmp_path).map(|metadata| metadata.len()).unwrap_or(0),
+            None => 0
+        };
+
+        let mut request = self.client.get(url.as_str())
+            .basic_auth(&self.username, Some(&self.password));
+        if let (Some(etag), true) = (&etag, offset > 0) {
+            request = request.header("Range", format!("bytes={}-", offset))
+                .header("If-Range", etag.as_str());
+        }
+        let mut response = request.send()?;
+
+        // the server sends the requested range only if the remote file is unchanged. A range of another version or a range
+        // which is not part of the current remote file means the download starts from scratch
+        let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT && etag.is_some() && get_etag(&response).is_none_or(|current| Some(current) == etag);
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE || (response.status() == reqwest::StatusCode::PARTIAL_CONTENT && !resumed) {
+            response = self.client.get(url.as_str())
+                .basic_auth(&self.username, Some(&self.password))
+                .send()?;
+        }
+        self.evaluate_response_for_error(&response)?;
+
+        // the download is only continued if the server answers with the requested range, otherwise the whole file is sent again
+        let mut tmp_file = if resumed {
+            if let Some(progress) = &progress {
+                progress(offset);
+            }
+            std::fs::OpenOptions::new().append(true).open(&tmp_path)?
+        } else {
+            let tmp_file = StdFile::create(&tmp_path)?;
+            match get_etag(&response) {
+                Some(etag) => std::fs::write(&etag_path, etag)?,
+                None => {
+                    let _ = std::fs::remove_file(&etag_path);
+                }
+            }
+            tmp_file
+        };
+        let mut reader = ProgressReader {
+            inner: response,
+            progress,
+            hasher: None
+        };
+        io::copy(&mut reader, &mut tmp_file)?;
+
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(mtime.max(0) as u64);
+        tmp_file.set_modified(mtime)?;
+        drop(tmp_file);
+        std::fs::rename(&tmp_path, local_path)?;
+        let _ = std::fs::remove_file(&etag_path);
+        Ok(())
+    }
+
+    // deletes the file or folder at 'path' on the nextcloud server
+    pub fn delete(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let path = helpers::path_to_str(path)?;
+        let url: String = self.build_url(vec![path.as_str()]);
+
+        let response = self.client.delete(url)
+            .basic_auth(&self.username, Some(&self.password))
+            .send()?;
+
+        self.evaluate_response_for_error(&response)
+    }
+
+    // moves the file or folder at 'from' to 'to' on the nextcloud server without overwriting an existing item
+    pub fn move_item(&self, from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+        let from = helpers::path_to_str(from)?;
+        let to = helpers::path_to_str(to)?;
+        let url: String = self.build_url(vec![from.as_str()]);
+        let destination = reqwest::Url::parse(&self.build_url(vec![to.as_str()]))?.to_string();
+
+        let move_method = reqwest::Method::from_str("MOVE")?;
+        let response = self.client.request(move_method, url)
+            .header("Destination", destination.as_str())
+            .header("Overwrite", "F")
+            .basic_auth(&self.username, Some(&self.password))
+            .send()?;
+
+        self.evaluate_response_for_error(&response)
+    }
+
     // evaluates the given response and determines if it has a error
     fn evaluate_response_for_error(&self, response: &reqwest::blocking::Response) -> Result<(), Box<dyn Error>> {
         if let Err(e) = response.error_for_status_ref() {
-            return Err(Box::new(e))
+            return Err(Box::new(HttpError::new(e, response)))
         }
         Ok(())
     }
 
+    // builds the url of the file at its remote location
+    fn build_file_url(&self, file: &File) -> Result<String, Box<dyn Error>> {
+        // use the remote name of the file and build the final url
+        if let Some(file_name) = file.get_remote_name() {
+            let remote_parent = helpers::path_to_str(file.get_remote_parent())?;
+            Ok(self.build_url(vec![remote_parent.as_str(), file_name.as_str()]))
+        } else {
+            Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "Extracting the file name from local path failed!")))
+        }
+    }
+
+    // returns the name of the upload folder of a chunked upload. The name only depends on the file, so an interrupted
+    // upload of the same file is continued in the same upload folder, even if the chunk size was changed in between
+    fn get_upload_id(&self, file: &File) -> String {
+        let key = format!("{}|{}|{}|{}", file.get_local_path().display(), file.get_remote_parent().display(), file.get_size(), file.get_mtime());
+        format!("nextsyncengine-{:016x}", helpers::hash_fnv1a(key.as_bytes()))
+    }
+
     // builds the url from the attribute 'url_server' and the given extensions
     fn build_url(&self, extensions: Vec<&str>) -> String {
         let mut current_url = self.url_dav.clone();
         for ext in extensions {
             if ext.starts_with("/") {
-                current_url.push_str(&ext);
+                current_url.push_str(ext);
             } else {
                 current_url.push_str(format!("/{}", &ext).as_str());
             }
@@ -210,47 +621,44 @@ impl NextcloudClient {
 
     }
 
-    // extracts the folder from xml data
-    fn extract_folder_xml(&self, xml_data: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // extracts the resources and their properties from the multistatus response of a PROPFIND request
+    fn extract_resources_xml(xml_data: &str) -> Result<Vec<DavResource>, Box<dyn Error>> {
         let parser = EventReader::from_str(xml_data);
-        let mut inside_displayname = false;
-        let mut current_displayname: Option<String> = None;
-        let mut folders: Vec<PathBuf> = Vec::new();
+        let mut resources: Vec<DavResource> = Vec::new();
+        let mut current: Option<DavResource> = None;
+        let mut current_element = String::new();
 
-        // loop through the xml tags
         for e in parser {
             match e {
-                // check if current iteration is a start of an element
-                Ok(XmlEvent::StartElement { name, ..})   => {
-                    // if the local_name is displayname set inside_displayname to true for the next
-                    // iteration to exract the data inside this tag
-                    // if the local_name is 'collection' the current item with the name 'displayname' is a folder
-                    // and will be pushed into folders
-                    if name.local_name == "displayname" {
-                        inside_displayname = true;
-
+                Ok(XmlEvent::StartElement { name, .. }) => {
+                    if name.local_name == "response" {
+                        current = Some(DavResource { href: String::new(), is_collection: false, props: HashMap::new() });
                     } else if name.local_name == "collection" {
-                        if current_displayname.is_some() {
-                            folders.push(Path::new(current_displayname.unwrap().as_str()).to_path_buf());
-                            current_displayname = None;
+                        if let Some(resource) = current.as_mut() {
+                            resource.is_collection = true;
                         }
                     }
+                    current_element = name.local_name;
                 }
 
-                // check if the current iteration is the data between the start and end of an element
+                // store the data of an element either as href or as property of the current resource
                 Ok(XmlEvent::Characters(data)) => {
-                    if inside_displayname && !data.is_empty() {
-                        current_displayname = Some(data);
+                    if let Some(resource) = current.as_mut() {
+                        if current_element == "href" {
+                            resource.href = data;
+                        } else {
+                            resource.props.insert(current_element.clone(), data);
+                        }
                     }
                 }
 
-                // check if the current iteration is the end of an element
-                Ok(XmlEvent::EndElement { name, .. }) => {
-                    if name.local_name == "displayname" {
-                        inside_displayname = false;
-                    } else if name.local_name == "collection" {
-                        current_displayname = None
+                Ok(XmlEvent::EndElement { name }) => {
+                    if name.local_name == "response" {
+                        if let Some(resource) = current.take() {
+                            resources.push(resource);
+                        }
                     }
+                    current_element.clear();
                 }
 
                 Err(e) => {
@@ -259,28 +667,43 @@ impl NextcloudClient {
                 _ => {}
             }
         }
-        Ok(folders)
+        Ok(resources)
     }
 
-
-    // reads a file to a vector and returns the vector
-    fn read_file_to_vec(local_path: &Path) -> Result<Vec<u8>, io::Error> {
+    // opens a file and returns a request body streaming 'length' bytes of it starting at 'offset'.
+    // The streamed bytes are added to 'hasher' if it is set
+    fn open_file_body(local_path: &Path, offset: u64, length: u64, progress: Option<ProgressCallback>, hasher: Option<Arc<Mutex<Hasher>>>) -> Result<Body, io::Error> {
         let mut file = StdFile::open(local_path)?;
-        let mut file_contents = Vec::with_capacity(file.metadata()?.len() as usize);
-        file.read_to_end(&mut file_contents)?;
-        Ok(file_contents)
+        file.seek(SeekFrom::Start(offset))?;
+        let reader = ProgressReader {
+            inner: file.take(length),
+            progress,
+            hasher
+        };
+        Ok(Body::sized(reader, length))
     }
 }
 
 
+// returns true if every chunk in an upload folder has the number and size it would get with 'chunk_size', so the upload can be continued
+fn chunks_match(chunks: &HashMap<String, u64>, chunk_size: u64, total_size: u64) -> bool {
+    let num_chunks = total_size.div_ceil(chunk_size);
+    chunks.iter().all(|(name, size)| match name.parse::<u64>() {
+        Ok(number) if number >= 1 && number <= num_chunks => *size == chunk_size.min(total_size - (number - 1) * chunk_size),
+        _ => false
+    })
+}
 
 // Unit Tests
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
+    use mockito::Matcher;
     use core::panic;
     use std::io::Write;
+    use std::path::PathBuf;
 
     #[test]
     fn test_upload_file_success() {
@@ -289,6 +712,7 @@ mod tests {
         let server_url = mock.url();
         mock
             .mock("PUT", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .match_header("OC-Checksum", "SHA256:649b8b471e7d7bc175eec758a7006ac693c434c8297c07db15286788c837154a")
             .with_status(201)
             .with_header("Content-Type", "application/xml")
             .create();
@@ -307,9 +731,9 @@ mod tests {
         let nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
     
         // attempt to upload the file
-        let result = nextcloud_client.upload_file(&fs_file);
+        let result = nextcloud_client.upload_file(&fs_file, None);
     
-        // assert that the upload was successful
+        // assert that the upload was successful and the checksum of the file was sent
         assert!(result.is_ok());
     }
     
@@ -338,12 +762,430 @@ mod tests {
         let nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
     
         // attempt to upload the file
-        let result = nextcloud_client.upload_file(&fs_file);
+        let result = nextcloud_client.upload_file(&fs_file, None);
     
         // assert that the upload failed with a 404 error
         assert!(result.is_err());
     }
-    
+
+    #[test]
+    fn test_upload_file_progress() {
+        // create a mock for the PUT request which expects the complete file content
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let upload = mock
+            .mock("PUT", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .match_header("Content-Length", "21")
+            .match_body("This is a test file.\n")
+            .with_status(201)
+            .create();
+
+        // create a temporary directory and file to simulate a file upload
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let mut temp_file = StdFile::create(&file_path).unwrap();
+        writeln!(temp_file, "This is a test file.").unwrap();
+
+        // create a mock Nextcloud file struct
+        let mut fs_file = File::new(&file_path, 123456789);
+        fs_file.set_remote_parent(PathBuf::from("/remote_parent"));
+
+        // count the bytes reported by the progress callback
+        let sent_size = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let sent_size_clone = Arc::clone(&sent_size);
+        let progress: ProgressCallback = Arc::new(move |bytes| {
+            sent_size_clone.fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        // attempt to upload the file and assert that the streamed body was reported completely
+        let nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        if let Err(e) = nextcloud_client.upload_file(&fs_file, Some(progress)) {
+            panic!("{}", e)
+        }
+        upload.assert();
+        assert_eq!(21, sent_size.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_upload_file_chunked_success() {
+        // create mocks for the requests of the chunking v2 api to simulate the Nextcloud server
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let destination = format!("{}/remote.php/dav/files/testuser/remote_parent/test_file.txt", server_url);
+        mock
+            .mock("PROPFIND", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .with_status(404)
+            .create();
+        let mkcol = mock
+            .mock("MKCOL", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .match_header("Destination", destination.as_str())
+            .with_status(201)
+            .create();
+        let chunks = mock
+            .mock("PUT", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}/0000[1-3]$".to_string()))
+            .match_header("Destination", destination.as_str())
+            .match_header("OC-Total-Length", "21")
+            .with_status(201)
+            .expect(3)
+            .create();
+        let assemble = mock
+            .mock("MOVE", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}/\.file$".to_string()))
+            .match_header("Destination", destination.as_str())
+            .match_header("X-OC-MTime", "123456789")
+            .match_header("OC-Checksum", "SHA256:649b8b471e7d7bc175eec758a7006ac693c434c8297c07db15286788c837154a")
+            .with_status(201)
+            .create();
+
+        // create a temporary directory and a file with 21 bytes to simulate a file upload
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let mut temp_file = StdFile::create(&file_path).unwrap();
+        writeln!(temp_file, "This is a test file.").unwrap();
+
+        // create a mock Nextcloud file struct
+        let mut fs_file = File::new(&file_path, 123456789);
+        fs_file.set_remote_parent(PathBuf::from("/remote_parent"));
+
+        // initialize the Nextcloud client so the file is split into three chunks
+        let mut nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        nextcloud_client.set_chunking(10, 8);
+
+        // attempt to upload the file and assert that every step of the chunked upload was executed
+        if let Err(e) = nextcloud_client.upload_file(&fs_file, None) {
+            panic!("{}", e)
+        }
+        mkcol.assert();
+        chunks.assert();
+        assemble.assert();
+    }
+
+    #[test]
+    fn test_upload_file_chunked_resume() {
+        // simulate an interrupted upload whose first chunk is already stored in the upload folder
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let body = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+                <d:response>
+                    <d:href>/remote.php/dav/uploads/testuser/nextsyncengine-0123456789abcdef/</d:href>
+                    <d:propstat>
+                        <d:prop>
+                            <d:resourcetype><d:collection/></d:resourcetype>
+                        </d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                </d:response>
+                <d:response>
+                    <d:href>/remote.php/dav/uploads/testuser/nextsyncengine-0123456789abcdef/00001</d:href>
+                    <d:propstat>
+                        <d:prop>
+                            <d:getcontentlength>8</d:getcontentlength>
+                            <d:resourcetype/>
+                        </d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                </d:response>
+            </d:multistatus>
+        "#;
+        mock
+            .mock("PROPFIND", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .with_status(207)
+            .with_body(body)
+            .create();
+        let mkcol = mock
+            .mock("MKCOL", Matcher::Any)
+            .with_status(201)
+            .expect(0)
+            .create();
+        let chunks = mock
+            .mock("PUT", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}/0000[2-3]$".to_string()))
+            .with_status(201)
+            .expect(2)
+            .create();
+        // the checksum covers the chunk which was not uploaded again as well
+        let assemble = mock
+            .mock("MOVE", Matcher::Regex(r"/\.file$".to_string()))
+            .match_header("OC-Checksum", "SHA256:649b8b471e7d7bc175eec758a7006ac693c434c8297c07db15286788c837154a")
+            .with_status(201)
+            .create();
+
+        // create a temporary directory and a file with 21 bytes to simulate a file upload
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let mut temp_file = StdFile::create(&file_path).unwrap();
+        writeln!(temp_file, "This is a test file.").unwrap();
+
+        // create a mock Nextcloud file struct
+        let mut fs_file = File::new(&file_path, 123456789);
+        fs_file.set_remote_parent(PathBuf::from("/remote_parent"));
+
+        // initialize the Nextcloud client so the file is split into three chunks
+        let mut nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        nextcloud_client.set_chunking(10, 8);
+
+        // assert that only the missing chunks were uploaded into the existing upload folder
+        if let Err(e) = nextcloud_client.upload_file(&fs_file, None) {
+            panic!("{}", e)
+        }
+        mkcol.assert();
+        chunks.assert();
+        assemble.assert();
+
+        // assert that the checksum is kept for another attempt
+        assert_eq!(Some("SHA256:649b8b471e7d7bc175eec758a7006ac693c434c8297c07db15286788c837154a"), fs_file.get_checksum("SHA256"));
+    }
+
+    #[test]
+    fn test_upload_file_chunked_stale_chunk_size() {
+        // simulate an interrupted upload whose chunks were uploaded with a chunk size of 8 bytes
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let body = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+                <d:response>
+                    <d:href>/remote.php/dav/uploads/testuser/nextsyncengine-0123456789abcdef/00001</d:href>
+                    <d:propstat>
+                        <d:prop>
+                            <d:getcontentlength>8</d:getcontentlength>
+                            <d:resourcetype/>
+                        </d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                </d:response>
+            </d:multistatus>
+        "#;
+        mock
+            .mock("PROPFIND", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .with_status(207)
+            .with_body(body)
+            .create();
+        let cleanup = mock
+            .mock("DELETE", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .with_status(204)
+            .create();
+        let mkcol = mock
+            .mock("MKCOL", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}$".to_string()))
+            .with_status(201)
+            .create();
+        let chunks = mock
+            .mock("PUT", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/nextsyncengine-[0-9a-f]{16}/0000[1-3]$".to_string()))
+            .with_status(201)
+            .expect(3)
+            .create();
+        mock
+            .mock("MOVE", Matcher::Regex(r"/\.file$".to_string()))
+            .with_status(201)
+            .create();
+
+        // create a temporary directory and a file with 21 bytes to simulate a file upload
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let mut temp_file = StdFile::create(&file_path).unwrap();
+        writeln!(temp_file, "This is a test file.").unwrap();
+
+        // create a mock Nextcloud file struct
+        let mut fs_file = File::new(&file_path, 123456789);
+        fs_file.set_remote_parent(PathBuf::from("/remote_parent"));
+
+        // initialize the Nextcloud client with a chunk size of 10 bytes
+        let mut nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        nextcloud_client.set_chunking(10, 10);
+
+        // assert that the stale upload folder was replaced and all chunks were uploaded again
+        if let Err(e) = nextcloud_client.upload_file(&fs_file, None) {
+            panic!("{}", e)
+        }
+        cleanup.assert();
+        mkcol.assert();
+        chunks.assert();
+    }
+
+    #[test]
+    fn test_upload_file_chunked_error() {
+        // create mocks for the requests of the chunking v2 api and let the upload of a chunk fail
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        mock
+            .mock("PROPFIND", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/".to_string()))
+            .with_status(404)
+            .create();
+        mock
+            .mock("MKCOL", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/".to_string()))
+            .with_status(201)
+            .create();
+        mock
+            .mock("PUT", Matcher::Regex(r"^/remote.php/dav/uploads/testuser/".to_string()))
+            .with_status(507)
+            .create();
+        let cleanup = mock
+            .mock("DELETE", Matcher::Any)
+            .with_status(204)
+            .expect(0)
+            .create();
+
+        // create a temporary directory and file to simulate a file upload
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let mut temp_file = StdFile::create(&file_path).unwrap();
+        writeln!(temp_file, "This is a test file.").unwrap();
+
+        // create a mock Nextcloud file struct
+        let mut fs_file = File::new(&file_path, 123456789);
+        fs_file.set_remote_parent(PathBuf::from("/remote_parent"));
+
+        // initialize the Nextcloud client so the file is uploaded in chunks
+        let mut nextcloud_client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        nextcloud_client.set_chunking(10, 8);
+
+        // assert that the upload failed and the upload folder was kept for the next attempt
+        assert!(nextcloud_client.upload_file(&fs_file, None).is_err());
+        cleanup.assert();
+    }
+
+    #[test]
+    fn test_get_chunk_size() {
+        let mut client = NextcloudClient::new("http://localhost".to_string(), "testuser".to_string(), "password".to_string());
+        client.set_chunking(100_000_000, 50_000_000);
+
+        // assert that the chunk size is only raised for files which would need more than 10000 chunks
+        assert_eq!(50_000_000, client.get_chunk_size(1_000_000_000).unwrap());
+        assert_eq!(60_000_000, client.get_chunk_size(600_000_000_000).unwrap());
+
+        // assert that files which do not fit into 10000 chunks of the largest size are rejected
+        assert!(client.get_chunk_size(MAX_CHUNKS * MAX_CHUNK_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn test_download_file_success() {
+        // create a mock for the GET request to simulate the Nextcloud server
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        mock
+            .mock("GET", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .with_status(200)
+            .with_body("This is a test file.")
+            .create();
+
+        // attempt to download the file into a temporary directory
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        let result = client.download_file(Path::new("/remote_parent/test_file.txt"), &file_path, 1700000000, None);
+
+        // assert that the content and the modification date were written and no temporary file is left behind
+        assert!(result.is_ok());
+        assert_eq!("This is a test file.", std::fs::read_to_string(&file_path).unwrap());
+        let mtime = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        assert_eq!(1700000000, mtime.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs());
+        assert_eq!(1, std::fs::read_dir(temp_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn test_download_file_resume() {
+        // create a mock which only sends the missing end of the file
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let range_mock = mock
+            .mock("GET", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .match_header("Range", "bytes=10-")
+            .match_header("If-Range", "\"v1\"")
+            .with_status(206)
+            .with_header("ETag", "\"v1\"")
+            .with_body("test file.")
+            .create();
+
+        // simulate an interrupted download which left the first 10 bytes and the ETag of the remote file behind
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        std::fs::write(temp_dir.path().join(".test_file.txt.part"), "This is a ").unwrap();
+        std::fs::write(temp_dir.path().join(".test_file.txt.part.etag"), "\"v1\"").unwrap();
+
+        // attempt to continue the download and count the reported bytes
+        let reported = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let reported_clone = Arc::clone(&reported);
+        let progress: ProgressCallback = Arc::new(move |bytes| {
+            reported_clone.fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
+        });
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        let result = client.download_file(Path::new("/remote_parent/test_file.txt"), &file_path, 1700000000, Some(progress));
+
+        // assert that only the missing bytes were requested and the whole file was reported as progress
+        assert!(result.is_ok());
+        range_mock.assert();
+        assert_eq!("This is a test file.", std::fs::read_to_string(&file_path).unwrap());
+        assert_eq!(20, reported.load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_download_file_remote_changed() {
+        // create a mock of a remote file which was replaced after the first attempt was interrupted
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let first_mock = mock
+            .mock("GET", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .with_status(200)
+            .with_header("ETag", "\"v1\"")
+            .with_body("This is a test file.")
+            .create();
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        client.download_file(Path::new("/remote_parent/test_file.txt"), &file_path, 1700000000, None).unwrap();
+        first_mock.assert();
+        first_mock.remove();
+
+        // simulate the interruption by truncating the downloaded file to a partial download of the first version
+        std::fs::write(temp_dir.path().join(".test_file.txt.part"), "This is a ").unwrap();
+        std::fs::write(temp_dir.path().join(".test_file.txt.part.etag"), "\"v1\"").unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        // the server ignores the range because the ETag sent with If-Range does not match the new version anymore
+        let changed_mock = mock
+            .mock("GET", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .match_header("If-Range", "\"v1\"")
+            .with_status(200)
+            .with_header("ETag", "\"v2\"")
+            .with_body("A completely different file.")
+            .create();
+        client.download_file(Path::new("/remote_parent/test_file.txt"), &file_path, 1700000000, None).unwrap();
+
+        // assert that the new version replaced the partial download of the old version instead of being appended to it
+        changed_mock.assert();
+        assert_eq!("A completely different file.", std::fs::read_to_string(&file_path).unwrap());
+        assert!(!temp_dir.path().join(".test_file.txt.part.etag").exists());
+
+        // assert that a partial download without a recorded ETag is not continued
+        std::fs::write(temp_dir.path().join(".test_file.txt.part"), "stale").unwrap();
+        let full_mock = mock
+            .mock("GET", "/remote.php/dav/files/testuser/remote_parent/test_file.txt")
+            .match_header("Range", mockito::Matcher::Missing)
+            .with_status(200)
+            .with_body("A completely different file.")
+            .create();
+        client.download_file(Path::new("/remote_parent/test_file.txt"), &file_path, 1700000000, None).unwrap();
+        full_mock.assert();
+        assert_eq!("A completely different file.", std::fs::read_to_string(&file_path).unwrap());
+    }
+
+    #[test]
+    fn test_move_item_success() {
+        // create a mock for the MOVE request which must not overwrite an existing file
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let destination = format!("{}/remote.php/dav/files/testuser/Photos/2024/a.jpg", server_url);
+        let move_mock = mock
+            .mock("MOVE", "/remote.php/dav/files/testuser/Photos/a.jpg")
+            .match_header("Destination", destination.as_str())
+            .match_header("Overwrite", "F")
+            .with_status(201)
+            .create();
+
+        // attempt to move the file on Nextcloud and assert that the request was sent
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        assert!(client.move_item(Path::new("/Photos/a.jpg"), Path::new("/Photos/2024/a.jpg")).is_ok());
+        move_mock.assert();
+    }
+
     #[test]
     fn test_is_online_true() {
         // create a mock for the GET request to simulate the Nextcloud server
@@ -569,6 +1411,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_exists_folder_unexpected_status() {
+        // create a mock which answers the PROPFIND request with a status nextcloud never sends for it
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        mock
+            .mock("PROPFIND", "/remote.php/dav/files/testuser/Test")
+            .with_status(200)
+            .create();
+
+        // assert that the unexpected status is returned as error instead of terminating the process
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        assert!(client.exists_folder(Path::new("/Test")).is_err());
+    }
+
     #[test]
     fn test_ls_success() {
         // create a mock for the GET request to simulate the Nextcloud server
@@ -624,6 +1481,11 @@ mod tests {
                         <d:prop>
                             <d:displayname>Neue Textdatei.md</d:displayname>
                             <d:resourcetype/>
+                            <d:getcontentlength>4</d:getcontentlength>
+                            <d:getlastmodified>Tue, 14 Nov 2023 22:13:20 GMT</d:getlastmodified>
+                            <oc:checksums>
+                                <oc:checksum>SHA1:a94a8fe5ccb19ba61c4c0873d391e987982fbbd3 MD5:098f6bcd4621d373cade4e832627b4f6</oc:checksum>
+                            </oc:checksums>
                         </d:prop>
                         <d:status>HTTP/1.1 200 OK</d:status>
                     </d:propstat>
@@ -637,20 +1499,26 @@ mod tests {
             .with_body(body)
             .create();
 
-        // create vec of files to test the returned value against it
-        let test_files = vec![
-            PathBuf::from("test_folder_1"), 
-            PathBuf::from("test_folder_2"),
-            ];
+        // create vec of folders to test the returned value against it
+        let test_folders = vec!["test_folder_1", "test_folder_2"];
 
         // create a Nextcloud client
         let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
         
         // attempt to list the items of a folder on Nextcloud
-        match client.ls(Path::new("/Test")) {
-            // assert that the listing returned the same paths like the predefined paths in 'test_files'
+        match client.ls_files(Path::new("/Test")) {
+            // assert that the listing returned the same folders like the predefined folders in 'test_folders'
             Ok(files) => {
-                assert_eq!(files, test_files)
+                let folders: Vec<&str> = files.iter().filter(|file| file.is_folder()).map(|file| file.get_name()).collect();
+                assert_eq!(folders, test_folders);
+
+                // assert that the size, modification date and checksums of the file were parsed
+                let file = files.iter().find(|file| !file.is_folder()).unwrap();
+                assert_eq!("Neue Textdatei.md", file.get_name());
+                assert_eq!(4, file.get_size());
+                assert_eq!(1700000000, file.get_mtime());
+                assert_eq!(Some("a94a8fe5ccb19ba61c4c0873d391e987982fbbd3"), file.get_checksum("SHA1"));
+                assert_eq!(None, file.get_checksum("SHA256"));
             }
             Err(e) => panic!("{}", e)
         }
@@ -682,7 +1550,7 @@ mod tests {
         let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
         
         // attempt to list the items of a folder on Nextcloud
-        match client.ls(Path::new("/Test1")) {
+        match client.ls_files(Path::new("/Test1")) {
             Ok(_files) => panic!(),
             // assert that the folder could not be found
             Err(_e) => assert!(true)
@@ -0,0 +1,153 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::Serialize;
+use log::error;
+
+use crate::upload::plan::PlanAction;
+
+// format of the output on stdout, set by --output
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    // colored status messages and progress bars for humans
+    Text,
+    // one JSON event per line for scripts, status messages and progress bars are not printed
+    Json
+}
+
+impl FromStr for OutputFormat {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown output format '{}'", s)))
+        }
+    }
+}
+
+// the format is chosen once at startup and read by every thread, so it is not passed through all commands
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+// an event of the JSON output, written as one line with its name in the field 'event'. Fields are only added and never
+// renamed or removed, so scripts reading the events keep working
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    // the local files are scanned
+    ScanStarted {
+        path: &'a Path
+    },
+    // what the upload does with a local file, emitted before anything is uploaded
+    FilePlanned {
+        local_path: &'a Path,
+        remote_path: PathBuf,
+        action: PlanAction,
+        size: u64,
+        // date the file is sorted by in RFC 3339 format, None if no date source knew it
+        date: Option<&'a str>,
+        date_source: Option<&'a str>
+    },
+    // a folder was created on nextcloud
+    FolderCreated {
+        remote_path: &'a Path
+    },
+    // the number of bytes sent to nextcloud so far
+    UploadProgress {
+        uploaded_size: u64,
+        total_size: u64
+    },
+    // a file was uploaded completely
+    UploadDone {
+        local_path: &'a Path,
+        remote_path: PathBuf,
+        size: u64,
+        attempts: u32
+    },
+    // a file could not be uploaded in this run, also after trying again
+    UploadFailed {
+        local_path: &'a Path,
+        remote_path: PathBuf,
+        error: &'a str,
+        // status of the last http response, None if the request failed before the server answered
+        http_status: Option<u16>,
+        attempts: u32
+    },
+    // the last event of a run
+    Summary {
+        command: &'a str,
+        // success, partial or fatal
+        status: &'a str,
+        exit_code: i32,
+        // number of files which could not be uploaded, downloaded, verified or synchronized
+        failed: usize,
+        // the error which stopped the run
+        error: Option<String>
+    }
+}
+
+// writes the event as one line to stdout if the JSON output is enabled
+pub fn emit(event: &Event) {
+    if !is_json() {
+        return
+    }
+    match serde_json::to_string(event) {
+        Ok(line) => {
+            // the lock keeps the lines of parallel uploads apart
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+        Err(e) => error!("Failed to write the event {:?}: {}", event, e)
+    }
+}
+
+// prints a status message for humans, which is left out of the JSON output
+pub fn print(message: impl Display) {
+    if !is_json() {
+        print!("{}", message);
+    }
+}
+
+pub fn println(message: impl Display) {
+    if !is_json() {
+        println!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_fields() {
+        assert_eq!(OutputFormat::Json, " JSON".parse::<OutputFormat>().unwrap());
+        assert!("yaml".parse::<OutputFormat>().is_err());
+
+        // assert that the name of an event and its fields are written in snake case
+        let event = Event::FilePlanned {
+            local_path: Path::new("/home/user/a.jpg"),
+            remote_path: PathBuf::from("/Photos/2024/a.jpg"),
+            action: PlanAction::SkipExisting,
+            size: 3,
+            date: None,
+            date_source: Some("exif")
+        };
+        let line = serde_json::to_string(&event).unwrap();
+        assert_eq!(r#"{"event":"file_planned","local_path":"/home/user/a.jpg","remote_path":"/Photos/2024/a.jpg","action":"skip_existing","size":3,"date":null,"date_source":"exif"}"#, line);
+
+        let event = Event::UploadProgress { uploaded_size: 5, total_size: 10 };
+        assert_eq!(r#"{"event":"upload_progress","uploaded_size":5,"total_size":10}"#, serde_json::to_string(&event).unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use colored::*;
+
+use crate::output;
+
+// decides how questions to the user are answered
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptMode {
+    // ask the user on the terminal
+    Ask,
+    // answer every question with yes, set by --yes
+    Yes,
+    // answer every question with no, set by --no-input or if stdin is not a terminal, e.g. under cron or systemd
+    NoInput
+}
+
+impl PromptMode {
+    // returns the prompt mode of the flags. Without a terminal nobody could answer, so the questions are not asked
+    pub fn detect(yes: bool, no_input: bool) -> PromptMode {
+        if yes {
+            PromptMode::Yes
+        } else if no_input || !io::stdin().is_terminal() {
+            PromptMode::NoInput
+        } else {
+            PromptMode::Ask
+        }
+    }
+
+    // asks a yes or no question and returns true if it was answered with yes
+    pub fn confirm(&self, question: &str) -> bool {
+        output::print(format!("{}\nYes(y) or No(n) ", question).yellow());
+        match self {
+            PromptMode::Ask => {
+                let _ = io::stdout().flush();
+                let mut answer = String::new();
+                let _ = io::stdin().read_line(&mut answer);
+                answer.trim().to_lowercase() == "y" || answer.trim().to_lowercase() == "yes"
+            }
+            PromptMode::Yes => {
+                output::println("y (--yes)".yellow());
+                true
+            }
+            PromptMode::NoInput => {
+                output::println("n (no input)".yellow());
+                false
+            }
+        }
+    }
+}
+
+// decides if a missing root folder on nextcloud is created, set by CREATE_ROOT in the .env
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CreateRoot {
+    // depends on the prompt mode
+    Ask,
+    // create it without asking
+    Always,
+    // never create it and stop
+    Never
+}
+
+impl CreateRoot {
+    // returns true if the missing root folder should be created
+    pub fn should_create(&self, prompt: PromptMode, question: &str) -> bool {
+        match self {
+            CreateRoot::Ask => prompt.confirm(question),
+            CreateRoot::Always => true,
+            CreateRoot::Never => false
+        }
+    }
+}
+
+impl FromStr for CreateRoot {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ask" => Ok(CreateRoot::Ask),
+            "always" => Ok(CreateRoot::Always),
+            "never" => Ok(CreateRoot::Never),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown value '{}' for CREATE_ROOT, options are: ask, always and never", s)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_root() {
+        assert_eq!(CreateRoot::Always, " Always ".parse::<CreateRoot>().unwrap());
+        assert_eq!(CreateRoot::Never, "never".parse::<CreateRoot>().unwrap());
+        assert!("yes".parse::<CreateRoot>().is_err());
+
+        // assert that the configured value wins over the prompt mode and that questions are answered without a terminal
+        assert!(CreateRoot::Always.should_create(PromptMode::NoInput, "Create it?"));
+        assert!(!CreateRoot::Never.should_create(PromptMode::Yes, "Create it?"));
+        assert!(CreateRoot::Ask.should_create(PromptMode::Yes, "Create it?"));
+        assert!(!CreateRoot::Ask.should_create(PromptMode::NoInput, "Create it?"));
+        assert_eq!(PromptMode::Yes, PromptMode::detect(true, false));
+        assert_eq!(PromptMode::NoInput, PromptMode::detect(false, true));
+    }
+}
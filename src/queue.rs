@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// queue of pending work which is shared by all worker threads. Each worker pulls the next item as soon as it is idle,
+// so a few large files never keep a single thread busy while the other threads have nothing left to do
+pub struct WorkQueue<T> {
+    items: Mutex<VecDeque<T>>
+}
+
+impl<T> WorkQueue<T> {
+    // creates a queue which hands out the largest items first, so the small items fill the gaps at the end of the batch
+    pub fn largest_first(mut items: Vec<T>, size: impl Fn(&T) -> u64) -> WorkQueue<T> {
+        items.sort_by_key(|item| std::cmp::Reverse(size(item)));
+        WorkQueue {
+            items: Mutex::new(VecDeque::from(items))
+        }
+    }
+
+    // returns the next item or None if the queue is empty
+    pub fn pop(&self) -> Option<T> {
+        self.items.lock().unwrap().pop_front()
+    }
+
+    // removes and returns all remaining items, e.g. to stop all workers after a fatal error
+    pub fn drain(&self) -> Vec<T> {
+        self.items.lock().unwrap().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_largest_first() {
+        let queue = WorkQueue::largest_first(vec![3, 10, 1, 7], |item| *item as u64);
+
+        // assert that the items are handed out from the largest to the smallest
+        assert_eq!(Some(10), queue.pop());
+        assert_eq!(Some(7), queue.pop());
+
+        // assert that draining returns the remaining items and empties the queue
+        assert_eq!(vec![3, 1], queue.drain());
+        assert_eq!(None, queue.pop());
+    }
+
+    #[test]
+    fn test_workers_share_queue() {
+        let queue = Arc::new(WorkQueue::largest_first((1..=100).collect::<Vec<u64>>(), |item| *item));
+
+        // let four workers pull items until the queue is empty
+        let mut threads = vec![];
+        for _ in 0..4 {
+            let queue = Arc::clone(&queue);
+            threads.push(std::thread::spawn(move || {
+                let mut sum: u64 = 0;
+                while let Some(item) = queue.pop() {
+                    sum += item;
+                }
+                sum
+            }));
+        }
+
+        // assert that every item was processed exactly once
+        let total: u64 = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+        assert_eq!(5050, total);
+        assert_eq!(None, queue.pop());
+    }
+}
@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use colored::*;
+use log::warn;
+use rand::Rng;
+use reqwest::StatusCode;
+
+use crate::nextcloud::{self, HttpError};
+use crate::output;
+
+// delay before the second attempt, which doubles with every further attempt
+const BASE_DELAY: Duration = Duration::from_secs(2);
+// upper limit of the delay between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(120);
+// interval in which the server is checked while it is in maintenance mode
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+// number of times a single transfer may pause for the maintenance mode. A transfer which is still answered with 503 while the
+// server is online, e.g. because its storage is not available, would otherwise pause forever
+const MAX_MAINTENANCE_TRIPS: u32 = 5;
+
+// what to do after a request failed
+#[derive(PartialEq, Debug)]
+pub enum Decision {
+    // try again after a delay, optionally requested by the server
+    Retry(Option<Duration>),
+    // the server is in maintenance mode, so all workers pause until it is back
+    Maintenance(Option<Duration>),
+    // trying again would fail in the same way, so the file is given up
+    GiveUp,
+    // every following request would fail too, e.g. because the credentials are wrong or the storage is full
+    Fatal
+}
+
+// decides how an error of a transfer is handled
+pub fn classify(e: &(dyn Error + 'static)) -> Decision {
+    let retry_after = e.downcast_ref::<HttpError>().and_then(|e| e.get_retry_after());
+    match nextcloud::get_error_status(e) {
+        Some(StatusCode::SERVICE_UNAVAILABLE) => Decision::Maintenance(retry_after),
+        Some(StatusCode::TOO_MANY_REQUESTS) => Decision::Retry(retry_after),
+        Some(
+            StatusCode::INTERNAL_SERVER_ERROR |
+            StatusCode::BAD_GATEWAY |
+            StatusCode::GATEWAY_TIMEOUT |
+            StatusCode::REQUEST_TIMEOUT |
+            StatusCode::LOCKED) => Decision::Retry(retry_after),
+        Some(
+            StatusCode::BAD_REQUEST |
+            StatusCode::UNAUTHORIZED |
+            StatusCode::FORBIDDEN |
+            StatusCode::INSUFFICIENT_STORAGE) => Decision::Fatal,
+        Some(_) => Decision::GiveUp,
+        // errors without a response like timeouts or lost connections are usually temporary
+        None if e.downcast_ref::<reqwest::Error>().is_some() => Decision::Retry(None),
+        // local errors like a missing file will not go away by trying again
+        None => Decision::GiveUp
+    }
+}
+
+// pauses all workers while the server is in maintenance mode. The first worker which notices the maintenance mode
+// checks the server regularly and resumes all workers once it is back online
+struct CircuitBreaker {
+    open: Mutex<bool>,
+    closed: Condvar,
+    probe_interval: Duration
+}
+
+impl CircuitBreaker {
+    fn new() -> CircuitBreaker {
+        CircuitBreaker {
+            open: Mutex::new(false),
+            closed: Condvar::new(),
+            probe_interval: PROBE_INTERVAL
+        }
+    }
+
+    // blocks while the circuit breaker is open
+    fn wait(&self) {
+        let mut open = self.open.lock().unwrap();
+        while *open {
+            open = self.closed.wait(open).unwrap();
+        }
+    }
+
+    // opens the circuit breaker and blocks until 'is_online' reports that the server is back
+    fn trip(&self, retry_after: Option<Duration>, is_online: impl Fn() -> bool) {
+        {
+            let mut open = self.open.lock().unwrap();
+            if *open {
+                // another worker already checks the server
+                drop(open);
+                return self.wait()
+            }
+            *open = true;
+        }
+
+        warn!("Nextcloud is in maintenance mode or unavailable, pausing all transfers");
+        output::println(format!("\n{}", "Nextcloud is in maintenance mode or unavailable. Pausing until it is back online ...".yellow()));
+        let mut delay = retry_after.unwrap_or(self.probe_interval);
+        loop {
+            thread::sleep(delay);
+            if is_online() {
+                break
+            }
+            delay = self.probe_interval;
+        }
+        output::println("Nextcloud is back online, resuming.".green());
+
+        *self.open.lock().unwrap() = false;
+        self.closed.notify_all();
+    }
+}
+
+// how often and how long to wait before a failed transfer is tried again. The policy is shared by all workers of a
+// batch, so the circuit breaker pauses all of them at once
+pub struct RetryPolicy {
+    max_attempts: u32,
+    breaker: CircuitBreaker
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            breaker: CircuitBreaker::new()
+        }
+    }
+
+    // returns the delay after the failed attempt 'attempt' (starting at 1). A delay requested by the server is preferred,
+    // otherwise the delay grows exponentially with a random jitter, so the workers do not retry all at the same time
+    pub fn get_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after
+        }
+        let delay = BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(MAX_DELAY);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    // runs 'operation' until it succeeds, an error is not worth retrying or the maximum number of attempts is reached.
+    // Waiting for the end of a maintenance mode does not count as attempt, but is limited to MAX_MAINTENANCE_TRIPS
+    pub fn run<T>(&self, is_online: impl Fn() -> bool, mut operation: impl FnMut() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        let mut attempt: u32 = 0;
+        let mut maintenance_trips: u32 = 0;
+        loop {
+            self.breaker.wait();
+            let e = match operation() {
+                Ok(val) => return Ok(val),
+                Err(e) => e
+            };
+
+            match classify(e.as_ref()) {
+                Decision::Maintenance(retry_after) => {
+                    maintenance_trips += 1;
+                    if maintenance_trips > MAX_MAINTENANCE_TRIPS {
+                        return Err(e)
+                    }
+                    self.breaker.trip(retry_after, &is_online)
+                }
+                Decision::Retry(retry_after) => {
+                    attempt += 1;
+                    if attempt >= self.max_attempts {
+                        return Err(e)
+                    }
+                    let delay = self.get_delay(attempt, retry_after);
+                    warn!("Attempt {} of {} failed, trying again in {:.1}s: {}", attempt, self.max_attempts, delay.as_secs_f64(), e);
+                    thread::sleep(delay);
+                }
+                Decision::GiveUp | Decision::Fatal => return Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io;
+    use std::path::Path;
+    use crate::nextcloud::NextcloudClient;
+
+    #[test]
+    fn test_get_delay() {
+        let policy = RetryPolicy::new(5);
+
+        // assert that the delay grows exponentially with a jitter of up to 50 percent
+        for attempt in 1..=4 {
+            let expected = BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = policy.get_delay(attempt, None);
+            assert!(delay <= expected && delay >= expected / 2, "{:?} is not within the jitter of {:?}", delay, expected);
+        }
+
+        // assert that the delay is capped and a delay requested by the server is used as it is
+        assert!(policy.get_delay(30, None) <= MAX_DELAY);
+        assert_eq!(Duration::from_secs(7), policy.get_delay(1, Some(Duration::from_secs(7))));
+    }
+
+    #[test]
+    fn test_run_gives_up() {
+        let policy = RetryPolicy::new(3);
+        let attempts = Cell::new(0);
+
+        // assert that local errors are not retried
+        let result: Result<(), Box<dyn Error>> = policy.run(|| true, || {
+            attempts.set(attempts.get() + 1);
+            Err(Box::new(io::Error::new(io::ErrorKind::NotFound, "missing")))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts.get());
+    }
+
+    #[test]
+    fn test_run_retries_rate_limited_request() {
+        // create a mock which rate limits every request and asks to retry immediately
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let delete_mock = mock
+            .mock("DELETE", "/remote.php/dav/files/testuser/Photos/a.jpg")
+            .with_status(429)
+            .with_header("Retry-After", "0")
+            .expect(3)
+            .create();
+
+        // assert that the request is sent until the maximum number of attempts is reached
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        let policy = RetryPolicy::new(3);
+        let result = policy.run(|| true, || client.delete(Path::new("/Photos/a.jpg")));
+        assert_eq!(Some(StatusCode::TOO_MANY_REQUESTS), nextcloud::get_error_status(result.unwrap_err().as_ref()));
+        delete_mock.assert();
+    }
+
+    #[test]
+    fn test_run_limits_maintenance_trips() {
+        // create a mock of a file whose storage is unavailable while the server itself is online
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let delete_mock = mock
+            .mock("DELETE", "/remote.php/dav/files/testuser/Photos/a.jpg")
+            .with_status(503)
+            .with_header("Retry-After", "0")
+            .expect(MAX_MAINTENANCE_TRIPS as usize + 1)
+            .create();
+
+        // assert that the transfer is given up instead of pausing forever
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        let policy = RetryPolicy::new(3);
+        let result = policy.run(|| true, || client.delete(Path::new("/Photos/a.jpg")));
+        assert_eq!(Some(StatusCode::SERVICE_UNAVAILABLE), nextcloud::get_error_status(result.unwrap_err().as_ref()));
+        delete_mock.assert();
+    }
+
+    #[test]
+    fn test_circuit_breaker_resumes() {
+        let breaker = CircuitBreaker { probe_interval: Duration::from_millis(1), ..CircuitBreaker::new() };
+        let probes = Cell::new(0);
+
+        // the server is back online at the second check
+        breaker.trip(Some(Duration::from_millis(1)), || {
+            probes.set(probes.get() + 1);
+            probes.get() == 2
+        });
+
+        // assert that the breaker is closed again and does not block
+        assert_eq!(2, probes.get());
+        breaker.wait();
+    }
+}
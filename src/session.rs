@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use log::warn;
+
+use crate::filesystem::File;
+
+// state of a single file inside an upload session
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadState {
+    Pending,
+    Uploading,
+    Done,
+    Failed
+}
+
+// a single line of the session journal. The journal is append only, so a crash can never corrupt already written records
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "record", rename_all = "lowercase")]
+enum Record {
+    Started {
+        session_id: String,
+        created: i64
+    },
+    Planned {
+        local_path: PathBuf,
+        remote_parent: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remote_name: Option<String>,
+        mtime: i64,
+        size: u64
+    },
+    State {
+        local_path: PathBuf,
+        state: UploadState
+    }
+}
+
+// planned upload of a file and its current state
+#[derive(Clone, Debug)]
+pub struct SessionEntry {
+    local_path: PathBuf,
+    remote_parent: PathBuf,
+    remote_name: Option<String>,
+    mtime: i64,
+    state: UploadState
+}
+
+impl SessionEntry {
+    pub fn get_state(&self) -> UploadState {
+        self.state
+    }
+
+    // converts the entry back into a file which can be uploaded
+    pub fn to_file(&self) -> File {
+        let mut file = File::new(&self.local_path, self.mtime);
+        file.set_remote_parent(self.remote_parent.clone());
+        if let Some(remote_name) = &self.remote_name {
+            file.set_remote_name(remote_name.clone());
+        }
+        file
+    }
+}
+
+// persistent journal of an upload run which allows to continue the run after a crash or an interruption
+pub struct Session {
+    path: PathBuf,
+    session_id: String,
+    entries: Mutex<Vec<SessionEntry>>,
+    index: HashMap<PathBuf, usize>,
+    journal: Mutex<fs::File>
+}
+
+impl Session {
+    // creates a new session journal at 'path' containing all planned files. An existing journal is replaced
+    pub fn create(path: &Path, files: &[File]) -> Result<Session, Box<dyn Error>> {
+        let created = chrono::Utc::now().timestamp();
+        let session_id = format!("{:x}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or(created));
+
+        // write the journal to a temporary file first, so an existing journal is only replaced by a complete one
+        let tmp_path = path.with_extension("tmp");
+        let mut journal = fs::File::create(&tmp_path)?;
+        let mut content = String::new();
+        content.push_str(&serde_json::to_string(&Record::Started { session_id: session_id.clone(), created })?);
+        content.push('\n');
+
+        let mut entries: Vec<SessionEntry> = Vec::with_capacity(files.len());
+        let mut index: HashMap<PathBuf, usize> = HashMap::with_capacity(files.len());
+        for file in files {
+            // the remote name is only recorded if it differs from the local file name
+            let local_name = file.get_local_path().file_name().and_then(|name| name.to_str()).map(|name| name.to_string());
+            let remote_name = file.get_remote_name().filter(|name| Some(name) != local_name.as_ref());
+            let record = Record::Planned {
+                local_path: file.get_local_path().to_path_buf(),
+                remote_parent: file.get_remote_parent().to_path_buf(),
+                remote_name: remote_name.clone(),
+                mtime: file.get_mtime(),
+                size: file.get_size()
+            };
+            content.push_str(&serde_json::to_string(&record)?);
+            content.push('\n');
+
+            index.insert(file.get_local_path().to_path_buf(), entries.len());
+            entries.push(SessionEntry {
+                local_path: file.get_local_path().to_path_buf(),
+                remote_parent: file.get_remote_parent().to_path_buf(),
+                remote_name,
+                mtime: file.get_mtime(),
+                state: UploadState::Pending
+            });
+        }
+        journal.write_all(content.as_bytes())?;
+        journal.sync_all()?;
+        drop(journal);
+        fs::rename(&tmp_path, path)?;
+
+        let journal = fs::OpenOptions::new().append(true).open(path)?;
+        Ok(Session {
+            path: path.to_path_buf(),
+            session_id,
+            entries: Mutex::new(entries),
+            index,
+            journal: Mutex::new(journal)
+        })
+    }
+
+    // loads the session journal at 'path' and replays its records to restore the state of every file
+    pub fn load(path: &Path) -> Result<Session, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let mut session_id = String::new();
+        let mut entries: Vec<SessionEntry> = Vec::new();
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+
+        for (line_number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue
+            }
+
+            // the last record might be incomplete if the process was killed while writing it
+            let record: Record = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("Skipping unreadable record in line {} of {:?}: {}", line_number + 1, path, e);
+                    continue
+                }
+            };
+
+            match record {
+                Record::Started { session_id: id, .. } => session_id = id,
+                Record::Planned { local_path, remote_parent, remote_name, mtime, .. } => {
+                    index.insert(local_path.clone(), entries.len());
+                    entries.push(SessionEntry { local_path, remote_parent, remote_name, mtime, state: UploadState::Pending });
+                }
+                Record::State { local_path, state } => {
+                    if let Some(&i) = index.get(&local_path) {
+                        entries[i].state = state;
+                    }
+                }
+            }
+        }
+
+        // terminate an incomplete last record, so new records start on their own line
+        let mut journal = fs::OpenOptions::new().append(true).open(path)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            journal.write_all(b"\n")?;
+        }
+        Ok(Session {
+            path: path.to_path_buf(),
+            session_id,
+            entries: Mutex::new(entries),
+            index,
+            journal: Mutex::new(journal)
+        })
+    }
+
+    pub fn get_session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn get_entries(&self) -> Vec<SessionEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    // returns all files which were not uploaded successfully, including files whose upload was interrupted
+    pub fn get_unfinished_files(&self) -> Vec<File> {
+        self.entries.lock().unwrap().iter()
+            .filter(|entry| entry.state != UploadState::Done)
+            .map(|entry| entry.to_file())
+            .collect()
+    }
+
+    // appends the new state of a file to the journal
+    pub fn set_state(&self, file: &File, state: UploadState) -> Result<(), io::Error> {
+        match self.index.get(file.get_local_path()) {
+            Some(&i) => self.entries.lock().unwrap()[i].state = state,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} is not part of the session", file.get_local_path())))
+        }
+
+        let record = Record::State { local_path: file.get_local_path().to_path_buf(), state };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        let mut journal = self.journal.lock().unwrap();
+        journal.write_all(line.as_bytes())?;
+        journal.flush()
+    }
+
+    // removes the journal from disk after the session finished
+    pub fn remove(self) -> Result<(), io::Error> {
+        drop(self.journal);
+        fs::remove_file(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_session_replay_states() {
+        // create a few local files which will be part of the session
+        let temp_dir = tempdir().unwrap();
+        let mut files: Vec<File> = vec![];
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            let local_path = temp_dir.path().join(name);
+            fs::write(&local_path, name).unwrap();
+            let mut file = File::new(&local_path, 1700000000);
+            file.set_remote_parent(PathBuf::from("/Photos/2023"));
+            files.push(file);
+        }
+
+        // create a session and change the states of two files
+        let journal_path = temp_dir.path().join("session.jsonl");
+        let session = Session::create(&journal_path, &files).unwrap();
+        session.set_state(&files[0], UploadState::Uploading).unwrap();
+        session.set_state(&files[0], UploadState::Done).unwrap();
+        session.set_state(&files[1], UploadState::Uploading).unwrap();
+        drop(session);
+
+        // load the session again and assert that the states were restored
+        let session = Session::load(&journal_path).unwrap();
+        let states: Vec<UploadState> = session.get_entries().iter().map(|entry| entry.get_state()).collect();
+        assert_eq!(vec![UploadState::Done, UploadState::Uploading, UploadState::Pending], states);
+
+        // assert that the interrupted and the pending file are continued with their remote target
+        let unfinished = session.get_unfinished_files();
+        assert_eq!(2, unfinished.len());
+        assert_eq!(files[1].get_local_path(), unfinished[0].get_local_path());
+        assert_eq!(Path::new("/Photos/2023"), unfinished[0].get_remote_parent());
+    }
+
+    #[test]
+    fn test_session_load_incomplete_record() {
+        // create a session with a single file
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("a.jpg");
+        fs::write(&local_path, "a").unwrap();
+        let files = vec![File::new(&local_path, 1700000000)];
+        let journal_path = temp_dir.path().join("session.jsonl");
+        let session = Session::create(&journal_path, &files).unwrap();
+        session.set_state(&files[0], UploadState::Done).unwrap();
+        drop(session);
+
+        // simulate a crash while the last record was written
+        let mut journal = fs::OpenOptions::new().append(true).open(&journal_path).unwrap();
+        journal.write_all(b"{\"record\":\"state\",\"local_pa").unwrap();
+
+        // assert that the complete records are still used
+        let session = Session::load(&journal_path).unwrap();
+        assert!(session.get_unfinished_files().is_empty());
+
+        // assert that records written after the incomplete record can be read again
+        session.set_state(&files[0], UploadState::Failed).unwrap();
+        drop(session);
+        let session = Session::load(&journal_path).unwrap();
+        assert_eq!(1, session.get_unfinished_files().len());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use colored::*;
+use log::error;
+
+use crate::filesystem::File;
+use crate::helpers;
+use crate::nextcloud::NextcloudClient;
+use crate::prompt::{CreateRoot, PromptMode};
+use crate::upload::common;
+use crate::sync::plan::{plan_sync, SyncAction, SyncConflictPolicy};
+use crate::sync::scan::{self, LocalEntry, RemoteTree};
+use crate::sync::state::{SyncState, SyncedFile};
+use crate::output::{self, Event};
+
+// options of the sync command
+pub struct SyncOptions {
+    // decides which side wins if a file was changed on both sides
+    pub on_conflict: SyncConflictPolicy,
+    // folder containing the states of all synchronized pairs of folders
+    pub state_folder: PathBuf,
+    // answers the question whether a missing remote folder is created
+    pub prompt: PromptMode,
+    // handling of a missing remote folder
+    pub create_root: CreateRoot
+}
+
+// applies the actions to the local and the remote folder
+struct Syncer<'a> {
+    local_root: &'a Path,
+    remote_root: &'a Path,
+    client: &'a NextcloudClient,
+    local: &'a BTreeMap<String, LocalEntry>,
+    remote: &'a RemoteTree,
+    // remote folders which exist or were created during this sync
+    remote_folders: HashSet<String>
+}
+
+impl Syncer<'_> {
+    // creates the parent folders of a relative path on nextcloud if they do not exist yet
+    fn create_remote_parents(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let parent = scan::get_relative_parent(path);
+        if parent.is_empty() || self.remote_folders.contains(parent) {
+            return Ok(())
+        }
+        self.create_remote_parents(parent)?;
+        let remote_path = self.remote_root.join(parent);
+        self.client.create_folder(&remote_path)?;
+        output::emit(&Event::FolderCreated { remote_path: &remote_path });
+        self.remote_folders.insert(parent.to_string());
+        Ok(())
+    }
+
+    // creates the parent folders of a relative path in the local folder
+    fn create_local_parents(&self, path: &str) -> Result<(), io::Error> {
+        match self.local_root.join(path).parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(())
+        }
+    }
+
+    // returns the modification date of a local file as it was scanned before the sync
+    fn get_local_mtime(&self, path: &str) -> Result<i64, io::Error> {
+        match self.local.get(path) {
+            Some(entry) => Ok(entry.get_file().get_mtime()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not part of the local folder", path)))
+        }
+    }
+
+    fn upload(&mut self, path: &str, mtime: i64) -> Result<(), Box<dyn Error>> {
+        self.create_remote_parents(path)?;
+
+        // the file model of the upload commands is reused, so the modification date is kept on nextcloud
+        let mut file = File::new(&self.local_root.join(path), mtime);
+        file.set_remote_parent(self.remote_root.join(scan::get_relative_parent(path)));
+        self.client.upload_file(&file, None)
+    }
+
+    fn download(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let remote = self.remote.files.get(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} is not part of the remote folder", path)))?;
+        self.create_local_parents(path)?;
+        self.client.download_file(&self.remote_root.join(path), &self.local_root.join(path), remote.get_mtime(), None)
+    }
+
+    fn apply(&mut self, action: &SyncAction) -> Result<(), Box<dyn Error>> {
+        match action {
+            SyncAction::Upload(path) => {
+                let mtime = self.get_local_mtime(path)?;
+                self.upload(path, mtime)
+            }
+            SyncAction::Download(path) => self.download(path),
+            SyncAction::DeleteRemote(path) => self.client.delete(&self.remote_root.join(path)),
+            SyncAction::DeleteLocal(path) => Ok(fs::remove_file(self.local_root.join(path))?),
+            SyncAction::MoveRemote { from, to } => {
+                self.create_remote_parents(to)?;
+                self.client.move_item(&self.remote_root.join(from), &self.remote_root.join(to))
+            }
+            SyncAction::MoveLocal { from, to } => {
+                self.create_local_parents(to)?;
+                Ok(fs::rename(self.local_root.join(from), self.local_root.join(to))?)
+            }
+            SyncAction::KeepBoth { path, renamed } => {
+                // renaming keeps the modification date of the local file
+                let mtime = self.get_local_mtime(path)?;
+                fs::rename(self.local_root.join(path), self.local_root.join(renamed))?;
+                self.upload(renamed, mtime)?;
+                self.download(path)
+            }
+            SyncAction::Conflict(path) => Err(Box::new(io::Error::other(format!("{} was changed on both sides and is skipped", path))))
+        }
+    }
+}
+
+// returns the paths of a sync action whose synchronized state is unknown if the action failed
+fn get_action_paths(action: &SyncAction) -> Vec<&String> {
+    match action {
+        SyncAction::Upload(path) | SyncAction::Download(path) | SyncAction::DeleteRemote(path) | SyncAction::DeleteLocal(path) | SyncAction::Conflict(path) => vec![path],
+        SyncAction::MoveRemote { from, to } | SyncAction::MoveLocal { from, to } => vec![from, to],
+        SyncAction::KeepBoth { path, renamed } => vec![path, renamed]
+    }
+}
+
+// builds the new state from both sides after the sync. Files with failed actions keep their previous state,
+// so their changes are detected again during the next sync
+fn build_state(local: &BTreeMap<String, LocalEntry>, remote: &RemoteTree, previous: &BTreeMap<String, SyncedFile>, unresolved: &HashSet<String>) -> BTreeMap<String, SyncedFile> {
+    let mut files: BTreeMap<String, SyncedFile> = BTreeMap::new();
+    for path in unresolved {
+        if let Some(synced) = previous.get(path) {
+            files.insert(path.clone(), synced.clone());
+        }
+    }
+    for (path, local_entry) in local {
+        if unresolved.contains(path) {
+            continue
+        }
+        if let Some(remote_file) = remote.files.get(path) {
+            if remote_file.get_size() == local_entry.get_file().get_size() {
+                files.insert(path.clone(), SyncedFile::new(local_entry, remote_file));
+            }
+        }
+    }
+    files
+}
+
+// prints the number of planned actions of each kind
+fn print_plan(actions: &[SyncAction]) {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for action in actions {
+        let kind = match action {
+            SyncAction::Upload(_) => "upload",
+            SyncAction::Download(_) => "download",
+            SyncAction::DeleteRemote(_) => "delete on Nextcloud",
+            SyncAction::DeleteLocal(_) => "delete locally",
+            SyncAction::MoveRemote { .. } => "move on Nextcloud",
+            SyncAction::MoveLocal { .. } => "move locally",
+            SyncAction::KeepBoth { .. } => "keep both versions",
+            SyncAction::Conflict(_) => "conflict"
+        };
+        *counts.entry(kind).or_default() += 1;
+    }
+    for (kind, count) in counts {
+        output::println(format!("  {}: {} file(s)", kind, count).green());
+    }
+}
+
+// synchronizes a local folder and a folder on nextcloud in both directions. Returns the number of files which could not be
+// synchronized
+pub fn sync(local_path: String, remote_path: String, client: NextcloudClient, options: &SyncOptions) -> Result<usize, Box<dyn Error>> {
+    let local_root = fs::canonicalize(&local_path)?;
+    let remote_root = PathBuf::from(&remote_path);
+
+    // check if the root folder exists and if not create it depending on CREATE_ROOT or the answer of the user
+    match common::exists_root_folder(&remote_root, &client, options.create_root, options.prompt) {
+        Ok(true) => {}
+        Ok(false) => return Ok(0),
+        Err(e) => return Err(e)
+    }
+
+    let state_path = SyncState::get_state_path(&options.state_folder, &local_root, &remote_root);
+    let mut state = SyncState::load(&state_path, &local_root, &remote_root)?;
+
+    output::print("Scanning local and remote folder ... ".green());
+    let local = scan::scan_local(&local_root)?;
+    let remote = scan::scan_remote(&remote_root, &client, Some(&local_root))?;
+    output::println("done".green());
+
+    // an empty side usually means an unmounted drive or a wrong path, which must never delete the other side
+    if !state.get_files().is_empty() && (local.is_empty() || remote.files.is_empty()) {
+        return Err(Box::new(io::Error::other("One side of the sync is empty although files were synchronized before. Aborting to prevent deleting all files on the other side!")))
+    }
+
+    let actions = plan_sync(&local, &remote.files, state.get_files(), options.on_conflict);
+    if actions.is_empty() {
+        output::println("Everything is up to date.".green());
+    } else {
+        output::println(format!("Synchronizing {} file(s):", actions.len()).green());
+        print_plan(&actions);
+    }
+
+    let mut syncer = Syncer {
+        local_root: &local_root,
+        remote_root: &remote_root,
+        client: &client,
+        local: &local,
+        remote: &remote,
+        remote_folders: remote.folders.clone()
+    };
+    let mut unresolved: HashSet<String> = HashSet::new();
+    for (i, action) in actions.iter().enumerate() {
+        if let Err(e) = syncer.apply(action) {
+            error!("{:?}: {}", action, e);
+            unresolved.extend(get_action_paths(action).into_iter().cloned());
+        }
+        helpers::progress_bar(i as u64 + 1, actions.len() as u64, "Syncing:", "");
+    }
+    if !actions.is_empty() {
+        output::println("");
+    }
+
+    // record the state of both sides after the sync as base for the next sync
+    let local = scan::scan_local(&local_root)?;
+    let remote = scan::scan_remote(&remote_root, &client, Some(&local_root))?;
+    state.set_files(build_state(&local, &remote, state.get_files(), &unresolved));
+    state.save()?;
+
+    if !unresolved.is_empty() {
+        output::println(format!("{} file(s) could not be synchronized, see process.log for details.", unresolved.len()).yellow());
+    }
+    Ok(unresolved.len())
+}
@@ -0,0 +1,12 @@
+// The sync module keeps a local folder and a Nextcloud folder in step in both directions.
+
+// scanning of the local and the remote folder
+pub mod scan;
+
+// persistent state of the last sync
+pub mod state;
+
+// detection of changes and planning of the actions
+pub mod plan;
+
+pub mod engine;
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::filesystem::RemoteFile;
+use crate::sync::scan::LocalEntry;
+use crate::sync::state::SyncedFile;
+
+// decides which side wins if a file was changed on both sides since the last sync
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SyncConflictPolicy {
+    // the file with the newer modification date wins
+    Newer,
+    // the local file always wins
+    Local,
+    // the remote file always wins
+    Remote,
+    // the local file is renamed, so both versions are kept on both sides
+    KeepBoth,
+    // neither side is touched and the conflict is reported
+    Skip
+}
+
+impl FromStr for SyncConflictPolicy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "newer" => Ok(SyncConflictPolicy::Newer),
+            "local" => Ok(SyncConflictPolicy::Local),
+            "remote" => Ok(SyncConflictPolicy::Remote),
+            "keep-both" => Ok(SyncConflictPolicy::KeepBoth),
+            "skip" => Ok(SyncConflictPolicy::Skip),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown sync conflict policy '{}'", s)))
+        }
+    }
+}
+
+// a single step which brings the local and the remote folder in step. Paths are relative to the roots
+#[derive(PartialEq, Debug)]
+pub enum SyncAction {
+    Upload(String),
+    Download(String),
+    DeleteRemote(String),
+    DeleteLocal(String),
+    MoveRemote { from: String, to: String },
+    MoveLocal { from: String, to: String },
+    // renames the local file to 'renamed' and uploads it, then downloads the remote file to 'path'
+    KeepBoth { path: String, renamed: String },
+    // a conflict which is left untouched
+    Conflict(String)
+}
+
+// change of a file on one side since the last sync
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Change {
+    Unchanged,
+    New,
+    Modified,
+    Deleted,
+    // the file neither exists now nor at the last sync
+    Absent
+}
+
+fn get_local_change(local: Option<&LocalEntry>, synced: Option<&SyncedFile>) -> Change {
+    match (local, synced) {
+        (Some(local), Some(synced)) => {
+            if local.get_file().get_size() != synced.get_size() || local.get_file().get_mtime() != synced.get_mtime() {
+                return Change::Modified
+            }
+            Change::Unchanged
+        }
+        (Some(_), None) => Change::New,
+        (None, Some(_)) => Change::Deleted,
+        (None, None) => Change::Absent
+    }
+}
+
+fn get_remote_change(remote: Option<&RemoteFile>, synced: Option<&SyncedFile>) -> Change {
+    match (remote, synced) {
+        (Some(remote), Some(synced)) => {
+            // the etag changes with every change of the content. Without an etag the size and modification date are compared
+            let modified = if !remote.get_etag().is_empty() && !synced.get_etag().is_empty() {
+                remote.get_etag() != synced.get_etag()
+            } else {
+                remote.get_size() != synced.get_size() || remote.get_mtime() != synced.get_mtime()
+            };
+            if modified {
+                return Change::Modified
+            }
+            Change::Unchanged
+        }
+        (Some(_), None) => Change::New,
+        (None, Some(_)) => Change::Deleted,
+        (None, None) => Change::Absent
+    }
+}
+
+// returns the name of the renamed local copy of a conflicting file, e.g. 'a (conflicted copy 2024-05-01 134502).jpg'
+pub fn get_conflict_name(path: &str, mtime: i64) -> String {
+    let date = chrono::DateTime::from_timestamp(mtime, 0).map(|date| date.format("%Y-%m-%d %H%M%S").to_string()).unwrap_or_default();
+    let file_name = Path::new(path);
+    let stem = file_name.file_stem().and_then(|val| val.to_str()).unwrap_or(path);
+    let name = match file_name.extension().and_then(|val| val.to_str()) {
+        Some(ext) => format!("{} (conflicted copy {}).{}", stem, date, ext),
+        None => format!("{} (conflicted copy {})", stem, date)
+    };
+    match path.rsplit_once('/') {
+        Some((parent, _)) => format!("{}/{}", parent, name),
+        None => name
+    }
+}
+
+// resolves a file which was changed on both sides according to the conflict policy
+fn resolve_conflict(path: &str, local: &LocalEntry, remote: &RemoteFile, policy: SyncConflictPolicy) -> Option<SyncAction> {
+    // both sides changed to the same content, so there is nothing to do
+    if local.get_file().get_size() == remote.get_size() && local.get_file().get_mtime() == remote.get_mtime() {
+        return None
+    }
+
+    let action = match policy {
+        SyncConflictPolicy::Newer if local.get_file().get_mtime() > remote.get_mtime() => SyncAction::Upload(path.to_string()),
+        SyncConflictPolicy::Newer => SyncAction::Download(path.to_string()),
+        SyncConflictPolicy::Local => SyncAction::Upload(path.to_string()),
+        SyncConflictPolicy::Remote => SyncAction::Download(path.to_string()),
+        SyncConflictPolicy::KeepBoth => SyncAction::KeepBoth { path: path.to_string(), renamed: get_conflict_name(path, local.get_file().get_mtime()) },
+        SyncConflictPolicy::Skip => SyncAction::Conflict(path.to_string())
+    };
+    Some(action)
+}
+
+// compares both sides with the state of the last sync and returns the actions which bring them in step
+pub fn plan_sync(local: &BTreeMap<String, LocalEntry>, remote: &BTreeMap<String, RemoteFile>, synced: &BTreeMap<String, SyncedFile>, policy: SyncConflictPolicy) -> Vec<SyncAction> {
+    let mut actions: Vec<SyncAction> = Vec::new();
+    let mut handled: HashSet<String> = HashSet::new();
+
+    // a file which vanished on one side and appeared under a new path with the same inode or file id was moved.
+    // Moves are only replayed if the other side still has the unchanged file at the old path
+    for (path, synced_file) in synced {
+        if local.contains_key(path) && remote.contains_key(path) {
+            continue
+        }
+
+        if !local.contains_key(path) && get_remote_change(remote.get(path), Some(synced_file)) == Change::Unchanged {
+            let moved_to = local.iter().find(|(new_path, entry)| {
+                synced_file.get_inode().is_some()
+                    && entry.get_inode() == synced_file.get_inode()
+                    && entry.get_file().get_size() == synced_file.get_size()
+                    && !synced.contains_key(*new_path)
+                    && !remote.contains_key(*new_path)
+                    && !handled.contains(*new_path)
+            });
+            if let Some((new_path, _)) = moved_to {
+                actions.push(SyncAction::MoveRemote { from: path.clone(), to: new_path.clone() });
+                handled.insert(path.clone());
+                handled.insert(new_path.clone());
+                continue
+            }
+        }
+
+        if !remote.contains_key(path) && get_local_change(local.get(path), Some(synced_file)) == Change::Unchanged {
+            let moved_to = remote.iter().find(|(new_path, remote_file)| {
+                !synced_file.get_file_id().is_empty()
+                    && remote_file.get_file_id() == synced_file.get_file_id()
+                    && !synced.contains_key(*new_path)
+                    && !local.contains_key(*new_path)
+                    && !handled.contains(*new_path)
+            });
+            if let Some((new_path, _)) = moved_to {
+                actions.push(SyncAction::MoveLocal { from: path.clone(), to: new_path.clone() });
+                handled.insert(path.clone());
+                handled.insert(new_path.clone());
+            }
+        }
+    }
+
+    // compare every other file which exists on any side or existed at the last sync
+    let paths: BTreeSet<&String> = local.keys().chain(remote.keys()).chain(synced.keys()).collect();
+    for path in paths {
+        if handled.contains(path) {
+            continue
+        }
+
+        let local_change = get_local_change(local.get(path), synced.get(path));
+        let remote_change = get_remote_change(remote.get(path), synced.get(path));
+        let action = match (local_change, remote_change) {
+            (Change::New, Change::Absent) | (Change::Modified, Change::Unchanged) | (Change::Modified, Change::Deleted) => Some(SyncAction::Upload(path.clone())),
+            (Change::Absent, Change::New) | (Change::Unchanged, Change::Modified) | (Change::Deleted, Change::Modified) => Some(SyncAction::Download(path.clone())),
+            (Change::Deleted, Change::Unchanged) => Some(SyncAction::DeleteRemote(path.clone())),
+            (Change::Unchanged, Change::Deleted) => Some(SyncAction::DeleteLocal(path.clone())),
+            (Change::New, Change::New) | (Change::Modified, Change::Modified) => {
+                resolve_conflict(path, &local[path], &remote[path], policy)
+            }
+            _ => None
+        };
+        if let Some(action) = action {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::File;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    // creates a local file with the given content, modification date and inode
+    fn create_local(dir: &TempDir, name: &str, content: &str, mtime: i64, inode: u64) -> LocalEntry {
+        let local_path = dir.path().join(name);
+        fs::write(&local_path, content).unwrap();
+        LocalEntry::new(File::new(&local_path, mtime), Some(inode))
+    }
+
+    // creates a remote file with the given size, modification date, etag and file id
+    fn create_remote(name: &str, size: u64, mtime: i64, etag: &str, file_id: &str) -> RemoteFile {
+        let mut remote = RemoteFile::new(name.to_string(), false, size, mtime);
+        remote.set_etag(etag.to_string());
+        remote.set_file_id(file_id.to_string());
+        remote
+    }
+
+    #[test]
+    fn test_plan_sync_changes() {
+        let temp_dir = tempdir().unwrap();
+        let a = create_local(&temp_dir, "a.jpg", "abc", 100, 1);
+        let b = create_local(&temp_dir, "b.jpg", "abc", 100, 2);
+        let remote_a = create_remote("a.jpg", 3, 100, "e1", "1");
+        let remote_c = create_remote("c.jpg", 3, 100, "e3", "3");
+
+        // 'a.jpg' was synchronized before and changed remotely, 'b.jpg' is new locally,
+        // 'c.jpg' was deleted locally and 'd.jpg' was deleted remotely
+        let mut synced = BTreeMap::new();
+        synced.insert("a.jpg".to_string(), SyncedFile::new(&a, &create_remote("a.jpg", 3, 100, "e0", "1")));
+        synced.insert("c.jpg".to_string(), SyncedFile::new(&create_local(&temp_dir, "c.jpg", "abc", 100, 3), &remote_c));
+        let d = create_local(&temp_dir, "d.jpg", "abc", 100, 4);
+        synced.insert("d.jpg".to_string(), SyncedFile::new(&d, &create_remote("d.jpg", 3, 100, "e4", "4")));
+
+        let local = BTreeMap::from([("a.jpg".to_string(), a), ("b.jpg".to_string(), b), ("d.jpg".to_string(), d)]);
+        let remote = BTreeMap::from([("a.jpg".to_string(), remote_a), ("c.jpg".to_string(), remote_c)]);
+
+        // assert that every change is applied to the other side
+        let actions = plan_sync(&local, &remote, &synced, SyncConflictPolicy::Newer);
+        assert_eq!(vec![
+            SyncAction::Download("a.jpg".to_string()),
+            SyncAction::Upload("b.jpg".to_string()),
+            SyncAction::DeleteRemote("c.jpg".to_string()),
+            SyncAction::DeleteLocal("d.jpg".to_string())
+        ], actions);
+    }
+
+    #[test]
+    fn test_plan_sync_moves() {
+        let temp_dir = tempdir().unwrap();
+        let a = create_local(&temp_dir, "a.jpg", "abc", 100, 1);
+        let b = create_local(&temp_dir, "b.jpg", "abc", 100, 2);
+        let remote_a = create_remote("a.jpg", 3, 100, "e1", "1");
+        let remote_b = create_remote("b.jpg", 3, 100, "e2", "2");
+
+        let mut synced = BTreeMap::new();
+        synced.insert("a.jpg".to_string(), SyncedFile::new(&a, &remote_a));
+        synced.insert("b.jpg".to_string(), SyncedFile::new(&b, &remote_b));
+
+        // 'a.jpg' was moved locally into a sub folder and 'b.jpg' was renamed remotely
+        let local = BTreeMap::from([("2024/a.jpg".to_string(), a), ("b.jpg".to_string(), b)]);
+        let remote = BTreeMap::from([("a.jpg".to_string(), remote_a), ("c.jpg".to_string(), remote_b)]);
+
+        // assert that the moves are replayed instead of deleting and transferring the files again
+        let actions = plan_sync(&local, &remote, &synced, SyncConflictPolicy::Newer);
+        assert_eq!(vec![
+            SyncAction::MoveRemote { from: "a.jpg".to_string(), to: "2024/a.jpg".to_string() },
+            SyncAction::MoveLocal { from: "b.jpg".to_string(), to: "c.jpg".to_string() }
+        ], actions);
+    }
+
+    #[test]
+    fn test_plan_sync_conflicts() {
+        let temp_dir = tempdir().unwrap();
+        let local = BTreeMap::from([("a.jpg".to_string(), create_local(&temp_dir, "a.jpg", "abc", 200, 1))]);
+        let remote = BTreeMap::from([("a.jpg".to_string(), create_remote("a.jpg", 5, 100, "e1", "1"))]);
+        let synced = BTreeMap::new();
+
+        // assert that a file which was created on both sides is resolved according to the policy
+        assert_eq!(vec![SyncAction::Upload("a.jpg".to_string())], plan_sync(&local, &remote, &synced, SyncConflictPolicy::Newer));
+        assert_eq!(vec![SyncAction::Download("a.jpg".to_string())], plan_sync(&local, &remote, &synced, SyncConflictPolicy::Remote));
+        assert_eq!(vec![SyncAction::Conflict("a.jpg".to_string())], plan_sync(&local, &remote, &synced, SyncConflictPolicy::Skip));
+        assert_eq!(vec![SyncAction::KeepBoth { path: "a.jpg".to_string(), renamed: "a (conflicted copy 1970-01-01 000320).jpg".to_string() }],
+            plan_sync(&local, &remote, &synced, SyncConflictPolicy::KeepBoth));
+
+        // assert that identical files on both sides are not transferred
+        let remote = BTreeMap::from([("a.jpg".to_string(), create_remote("a.jpg", 3, 200, "e1", "1"))]);
+        assert!(plan_sync(&local, &remote, &synced, SyncConflictPolicy::Newer).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use ignore::gitignore::Gitignore;
+
+use crate::filesystem::{File, RemoteFile};
+use crate::nextcloud::NextcloudClient;
+use crate::upload::filter::{self, ScanFilter};
+
+// a local file inside the synchronized folder. The mtime of the file is the modification date of the file system
+#[derive(Clone)]
+pub struct LocalEntry {
+    file: File,
+    inode: Option<u64>
+}
+
+impl LocalEntry {
+    pub fn new(file: File, inode: Option<u64>) -> LocalEntry {
+        LocalEntry {
+            file,
+            inode
+        }
+    }
+
+    pub fn get_file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn get_inode(&self) -> Option<u64> {
+        self.inode
+    }
+}
+
+// files and folders of the remote side by their path relative to the remote root
+pub struct RemoteTree {
+    pub files: BTreeMap<String, RemoteFile>,
+    pub folders: HashSet<String>
+}
+
+#[cfg(unix)]
+fn get_inode(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn get_inode(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+// temporary files of unfinished downloads are never synchronized
+fn is_partial_download(name: &str) -> bool {
+    name.starts_with('.') && (name.ends_with(".part") || name.ends_with(".part.etag"))
+}
+
+// joins a relative path of the synchronized folders and the name of an item
+pub fn join_relative(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        return name.to_string()
+    }
+    format!("{}/{}", parent, name)
+}
+
+// returns the parent of a relative path, which is empty for items in the root
+pub fn get_relative_parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default()
+}
+
+// adds the .nsignore file of a local folder to the .nsignore files of its parents
+fn get_folder_ignores(parent_ignores: &[Rc<Gitignore>], local_folder: &Path) -> Vec<Rc<Gitignore>> {
+    let mut ignores = parent_ignores.to_vec();
+    if let Some(ignore) = filter::read_ignore_file(local_folder) {
+        ignores.push(Rc::new(ignore));
+    }
+    ignores
+}
+
+// returns true if a file or folder is skipped like during the upload, i.e. junk files and paths ignored by a .nsignore file.
+// The same check is used for both sides, so a skipped file is neither uploaded, downloaded nor deleted
+fn is_skipped(scan_filter: &ScanFilter, ignores: &[Rc<Gitignore>], local_root: &Path, relative_path: &str, is_dir: bool) -> bool {
+    let ignore_refs: Vec<&Gitignore> = ignores.iter().map(|ignore| ignore.as_ref()).collect();
+    scan_filter.check(Path::new(relative_path), is_dir).is_some() || filter::is_ignored(&ignore_refs, &local_root.join(relative_path), is_dir)
+}
+
+// travels through the local folder and returns all files by their path relative to 'root'
+pub fn scan_local(root: &Path) -> Result<BTreeMap<String, LocalEntry>, Box<dyn Error>> {
+    let scan_filter = ScanFilter::default();
+    let mut paths_folder: Vec<(PathBuf, String, Vec<Rc<Gitignore>>)> = vec![(root.to_path_buf(), String::new(), Vec::new())];
+    let mut files: BTreeMap<String, LocalEntry> = BTreeMap::new();
+
+    while let Some((current_folder, relative_folder, parent_ignores)) = paths_folder.pop() {
+        let ignores = get_folder_ignores(&parent_ignores, &current_folder);
+        for entry in fs::read_dir(current_folder)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = join_relative(&relative_folder, &name);
+            let file_type = entry.file_type()?;
+            if is_skipped(&scan_filter, &ignores, root, &relative_path, file_type.is_dir()) {
+                continue
+            }
+
+            if file_type.is_dir() {
+                paths_folder.push((entry.path(), relative_path, ignores.clone()));
+                continue
+            }
+            if !file_type.is_file() || is_partial_download(&name) {
+                continue
+            }
+
+            let metadata = entry.metadata()?;
+            let mtime = metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+            files.insert(relative_path, LocalEntry::new(File::new(&entry.path(), mtime), get_inode(&metadata)));
+        }
+    }
+    Ok(files)
+}
+
+// travels through the remote folder and returns all files and folders by their path relative to 'root'. If 'local_root'
+// is set, the files skipped by scan_local in the corresponding local folder are left out as well
+pub fn scan_remote(root: &Path, client: &NextcloudClient, local_root: Option<&Path>) -> Result<RemoteTree, Box<dyn Error>> {
+    let scan_filter = ScanFilter::default();
+    let mut paths_folder: Vec<(String, Vec<Rc<Gitignore>>)> = vec![(String::new(), Vec::new())];
+    let mut tree = RemoteTree {
+        files: BTreeMap::new(),
+        folders: HashSet::new()
+    };
+
+    while let Some((relative_folder, parent_ignores)) = paths_folder.pop() {
+        let ignores = match local_root {
+            Some(local_root) => get_folder_ignores(&parent_ignores, &local_root.join(&relative_folder)),
+            None => parent_ignores
+        };
+        for item in client.ls_files(&root.join(&relative_folder))? {
+            let relative_path = join_relative(&relative_folder, item.get_name());
+            if local_root.is_some_and(|local_root| is_skipped(&scan_filter, &ignores, local_root, &relative_path, item.is_folder())) {
+                continue
+            }
+            if item.is_folder() {
+                tree.folders.insert(relative_path.clone());
+                paths_folder.push((relative_path, ignores.clone()));
+                continue
+            }
+            tree.files.insert(relative_path, item);
+        }
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_local_skips_ignored_files() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("2024/raw")).unwrap();
+        fs::write(temp_dir.path().join("2024/a.jpg"), "a").unwrap();
+        fs::write(temp_dir.path().join("2024/.DS_Store"), "junk").unwrap();
+        fs::write(temp_dir.path().join("2024/raw/a.cr2"), "raw").unwrap();
+        fs::write(temp_dir.path().join("2024/.nsignore"), "raw/\n").unwrap();
+
+        // assert that junk files, the .nsignore file and the paths it ignores are not synchronized
+        let files = scan_local(temp_dir.path()).unwrap();
+        assert_eq!(vec!["2024/a.jpg"], files.keys().collect::<Vec<&String>>());
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::RemoteFile;
+use crate::helpers;
+use crate::sync::scan::LocalEntry;
+
+// state of a file after it was synchronized the last time, which is the base to detect changes on both sides
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SyncedFile {
+    size: u64,
+    mtime: i64,
+    // inode of the local file, which is used to detect local moves (only available on unix)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inode: Option<u64>,
+    etag: String,
+    file_id: String
+}
+
+impl SyncedFile {
+    pub fn new(local: &LocalEntry, remote: &RemoteFile) -> SyncedFile {
+        SyncedFile {
+            size: local.get_file().get_size(),
+            mtime: local.get_file().get_mtime(),
+            inode: local.get_inode(),
+            etag: remote.get_etag().to_string(),
+            file_id: remote.get_file_id().to_string()
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_mtime(&self) -> i64 {
+        self.mtime
+    }
+
+    pub fn get_inode(&self) -> Option<u64> {
+        self.inode
+    }
+
+    pub fn get_etag(&self) -> &str {
+        &self.etag
+    }
+
+    pub fn get_file_id(&self) -> &str {
+        &self.file_id
+    }
+}
+
+// persistent state of a synchronized pair of a local and a remote folder
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncState {
+    #[serde(skip)]
+    path: PathBuf,
+    local_root: PathBuf,
+    remote_root: PathBuf,
+    // synchronized files by their path relative to the roots
+    files: BTreeMap<String, SyncedFile>
+}
+
+impl SyncState {
+    // returns the location of the state of a pair of folders inside 'state_folder'
+    pub fn get_state_path(state_folder: &Path, local_root: &Path, remote_root: &Path) -> PathBuf {
+        let key = format!("{}|{}", local_root.display(), remote_root.display());
+        state_folder.join(format!("{:016x}.json", helpers::hash_fnv1a(key.as_bytes())))
+    }
+
+    // loads the state at 'path' or returns an empty state if the folders were never synchronized before
+    pub fn load(path: &Path, local_root: &Path, remote_root: &Path) -> Result<SyncState, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(SyncState {
+                path: path.to_path_buf(),
+                local_root: local_root.to_path_buf(),
+                remote_root: remote_root.to_path_buf(),
+                files: BTreeMap::new()
+            })
+        }
+
+        let mut state: SyncState = serde_json::from_str(&fs::read_to_string(path)?)?;
+        state.path = path.to_path_buf();
+        Ok(state)
+    }
+
+    pub fn get_files(&self) -> &BTreeMap<String, SyncedFile> {
+        &self.files
+    }
+
+    pub fn set_files(&mut self, files: BTreeMap<String, SyncedFile>) {
+        self.files = files;
+    }
+
+    // writes the state to disk. A temporary file is written first, so a crash never leaves a corrupted state behind
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sync_state_save_load() {
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("a.jpg");
+        fs::write(&local_path, "abc").unwrap();
+        let local = LocalEntry::new(File::new(&local_path, 100), Some(7));
+        let mut remote = RemoteFile::new("a.jpg".to_string(), false, 3, 100);
+        remote.set_etag("etag".to_string());
+        remote.set_file_id("42".to_string());
+
+        // the state of a never synchronized pair of folders is empty
+        let state_path = SyncState::get_state_path(&temp_dir.path().join("sync"), temp_dir.path(), Path::new("/Photos"));
+        let mut state = SyncState::load(&state_path, temp_dir.path(), Path::new("/Photos")).unwrap();
+        assert!(state.get_files().is_empty());
+
+        // save a synchronized file and assert that it is loaded again
+        state.set_files(BTreeMap::from([("a.jpg".to_string(), SyncedFile::new(&local, &remote))]));
+        state.save().unwrap();
+        let state = SyncState::load(&state_path, temp_dir.path(), Path::new("/Photos")).unwrap();
+        let synced = &state.get_files()["a.jpg"];
+        assert_eq!((3, 100, Some(7)), (synced.get_size(), synced.get_mtime(), synced.get_inode()));
+        assert_eq!(("etag", "42"), (synced.get_etag(), synced.get_file_id()));
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use colored::*;
+
+use crate::nextcloud::NextcloudClient;
+use crate::filesystem::{File, Folder};
+use crate::upload::common::{self, UploadOptions};
+use crate::upload::conflict::{resolve_conflicts, RemoteIndex};
+use crate::upload::plan::UploadPlan;
+use crate::output;
+
+// returns the folders of the remote parents of the files which do not exist on nextcloud yet
+fn get_new_folders(files: &[File], remote_root: &Path, mut root: Folder) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut relative_parents: BTreeSet<PathBuf> = BTreeSet::new();
+    for file in files {
+        let relative_parent = file.get_remote_parent().strip_prefix(remote_root)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not inside the folder {:?}", file.get_remote_parent(), remote_root)))?;
+        relative_parents.insert(relative_parent.to_path_buf());
+    }
+
+    let mut new_folders: Vec<PathBuf> = Vec::new();
+    for relative_parent in relative_parents {
+        common::plan_remote_path(&mut root, &relative_parent, &mut new_folders);
+    }
+    Ok(new_folders)
+}
+
+// uploads the files of a plan written by --save-plan. Nextcloud may have changed since planning, so existing files are
+// detected again and handled according to --on-conflict, except for the files the plan explicitly overwrites. Returns the
+// number of files which could not be uploaded
+pub fn apply_plan(plan_path: &Path, client: NextcloudClient, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    let plan = UploadPlan::load(plan_path)?;
+    let remote_root = plan.get_remote_root().to_path_buf();
+
+    output::print("Checking the local files of the plan ... ".green());
+    let files = plan.get_files();
+    output::println("done".green());
+    let num_changed = plan.get_entries().iter().filter(|entry| !entry.get_action().is_skipped()).count() - files.len();
+    if num_changed > 0 {
+        output::println(format!("Skipping {} file(s) which changed or were removed after planning, see the log for details.", num_changed).yellow());
+    }
+
+    // check if the root folder exists and if not create it depending on CREATE_ROOT or the answer of the user
+    if !common::exists_root_folder(&remote_root, &client, options.create_root, options.prompt)? {
+        return Ok(0)
+    }
+
+    // create the cached version of the nextcloud folder structure
+    output::print("Scanning remote folder structure ... ".green());
+    let mut root = Folder::new(remote_root.to_string_lossy().to_string());
+    let mut index = RemoteIndex::new();
+    common::travel_dir_dav(&mut root, &client, &mut index)?;
+    output::println("done".green());
+
+    let new_folders = get_new_folders(&files, &remote_root, root)?;
+    let (overwrite, files): (Vec<File>, Vec<File>) = files.into_iter().partition(|file| file.replaces_existing());
+    let (mut files, skipped_existing) = resolve_conflicts(files, &client, &mut index, options.on_conflict)?;
+    files.extend(overwrite);
+    if output::is_json() {
+        UploadPlan::new(&remote_root, vec![], &files, &[], &skipped_existing).emit_events();
+    }
+    common::execute_upload(files, new_folders, client, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_get_new_folders() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("a.jpg"), "abc").unwrap();
+        let mut files = vec![];
+        for remote_parent in ["/Photos/2024/01", "/Photos/2023/12", "/Photos/2024/01", "/Photos"] {
+            let mut file = File::new(&temp_dir.path().join("a.jpg"), 0);
+            file.set_remote_parent(PathBuf::from(remote_parent));
+            files.push(file);
+        }
+
+        // assert that each missing folder is planned once, parents first
+        let mut root = Folder::new("/Photos".to_string());
+        root.add_sub_folder(Folder::new("2024".to_string()), Path::new("/Photos"));
+        let new_folders = get_new_folders(&files, Path::new("/Photos"), root).unwrap();
+        assert_eq!(vec![PathBuf::from("/Photos/2023"), PathBuf::from("/Photos/2023/12"), PathBuf::from("/Photos/2024/01")], new_folders);
+
+        // assert that remote parents outside the root folder are rejected
+        files[0].set_remote_parent(PathBuf::from("/Other"));
+        assert!(get_new_folders(&files, Path::new("/Photos"), Folder::new("/Photos".to_string())).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use colored::*;
+
+use crate::checksum::{self, ChecksumAlgorithm};
+use crate::filesystem::{File, RemoteFile};
+use crate::nextcloud::NextcloudClient;
+use crate::upload::conflict::RemoteIndex;
+use crate::output;
+
+// number of hex characters of the content hash which are appended to a colliding name
+const HASH_LENGTH: usize = 8;
+
+// decides what happens if several local files of a batch would be uploaded to the same remote path
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CollisionPolicy {
+    // append _1, _2, ... to the name
+    Suffix,
+    // append a short hash of the content to the name
+    Hash,
+    // only upload the first file and report the others
+    Skip
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "suffix" => Ok(CollisionPolicy::Suffix),
+            "hash" => Ok(CollisionPolicy::Hash),
+            "skip" => Ok(CollisionPolicy::Skip),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown collision policy '{}'", s)))
+        }
+    }
+}
+
+// remote names which were chosen for colliding files, so every local file keeps its name when the upload is run again
+pub struct NameRecord {
+    path: PathBuf,
+    // remote name by local path by remote folder
+    names: BTreeMap<String, BTreeMap<String, String>>,
+    changed: bool
+}
+
+impl NameRecord {
+    // loads the record at 'path' or returns an empty record if there were no collisions yet
+    pub fn load(path: &Path) -> Result<NameRecord, Box<dyn Error>> {
+        let names = if path.is_file() {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(NameRecord {
+            path: path.to_path_buf(),
+            names,
+            changed: false
+        })
+    }
+
+    // returns the recorded names of a remote folder by local path
+    fn get_folder(&self, remote_parent: &Path) -> Option<&BTreeMap<String, String>> {
+        self.names.get(&remote_parent.to_string_lossy().to_string())
+    }
+
+    fn set_name(&mut self, remote_parent: &Path, local_path: &Path, remote_name: &str) {
+        let folder = self.names.entry(remote_parent.to_string_lossy().to_string()).or_default();
+        let previous = folder.insert(local_path.to_string_lossy().to_string(), remote_name.to_string());
+        self.changed |= previous.as_deref() != Some(remote_name);
+    }
+
+    // writes the record to disk if a name was added. A temporary file is written first, so a crash never leaves a corrupted record behind
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        if !self.changed {
+            return Ok(())
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.names)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+// inserts 'suffix' between the stem and the extension of a file name
+fn append_to_name(name: &str, suffix: &str) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|val| val.to_str()).unwrap_or(name);
+    match path.extension().and_then(|val| val.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+        None => format!("{}_{}", stem, suffix)
+    }
+}
+
+// returns the first name following the pattern 'name_n.ext' which is not taken
+fn get_suffixed_name(name: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    let mut counter: u64 = 1;
+    loop {
+        let candidate = append_to_name(name, &counter.to_string());
+        if !is_taken(&candidate) {
+            return candidate
+        }
+        counter += 1;
+    }
+}
+
+// resolves the collisions between the files of one remote folder and returns the remote name of each file or None if it is skipped
+fn resolve_folder(files: &[File], remote_parent: &Path, listing: &HashMap<String, RemoteFile>, policy: CollisionPolicy, names: &mut NameRecord) -> Result<Vec<Option<String>>, Box<dyn Error>> {
+    let mut assigned: Vec<Option<String>> = vec![None; files.len()];
+    // names which are taken by a file of this batch or were recorded for a local file in an earlier run
+    let mut claimed: HashMap<String, PathBuf> = HashMap::new();
+
+    // files which collided in an earlier run keep their recorded names
+    if let Some(recorded) = names.get_folder(remote_parent) {
+        for (local_path, remote_name) in recorded {
+            claimed.insert(remote_name.clone(), PathBuf::from(local_path));
+        }
+        for (i, file) in files.iter().enumerate() {
+            assigned[i] = recorded.get(&file.get_local_path().to_string_lossy().to_string()).cloned();
+        }
+    }
+
+    // a file which was already uploaded keeps its name, even if a colliding file is found before it
+    for (i, file) in files.iter().enumerate() {
+        let name = file.get_remote_name().unwrap_or_default();
+        let uploaded = listing.get(&name)
+            .is_some_and(|remote| !remote.is_folder() && remote.get_size() == file.get_size() && remote.get_mtime() == file.get_mtime());
+        if assigned[i].is_none() && uploaded && !claimed.contains_key(&name) {
+            claimed.insert(name.clone(), file.get_local_path().to_path_buf());
+            assigned[i] = Some(name);
+        }
+    }
+
+    for (i, file) in files.iter().enumerate() {
+        if assigned[i].is_some() {
+            continue
+        }
+        let name = file.get_remote_name().unwrap_or_default();
+        let Some(owner) = claimed.get(&name).cloned() else {
+            claimed.insert(name.clone(), file.get_local_path().to_path_buf());
+            assigned[i] = Some(name);
+            continue
+        };
+
+        let is_taken = |candidate: &str| claimed.contains_key(candidate) || listing.contains_key(candidate);
+        let new_name = match policy {
+            CollisionPolicy::Suffix => get_suffixed_name(&name, is_taken),
+            CollisionPolicy::Hash => {
+                let hash = checksum::hash_file(file.get_local_path(), ChecksumAlgorithm::Sha256)?;
+                let hashed_name = append_to_name(&name, &hash[..HASH_LENGTH]);
+                // a file with the same content and name was already planned, so both files still need different names
+                if is_taken(&hashed_name) {
+                    get_suffixed_name(&hashed_name, is_taken)
+                } else {
+                    hashed_name
+                }
+            }
+            CollisionPolicy::Skip => continue
+        };
+
+        // record both files, so neither of them changes its name when new colliding files are added later
+        names.set_name(remote_parent, &owner, &name);
+        names.set_name(remote_parent, file.get_local_path(), &new_name);
+        claimed.insert(new_name.clone(), file.get_local_path().to_path_buf());
+        assigned[i] = Some(new_name);
+    }
+    Ok(assigned)
+}
+
+// detects files of the batch which would be uploaded to the same remote path and gives them unique names according to 'policy'.
+// The chosen names are recorded in 'names', which the caller saves once the upload starts. Returns the files which are
+// uploaded and the colliding files which are skipped
+pub fn resolve_collisions(files: Vec<File>, client: &NextcloudClient, index: &mut RemoteIndex, policy: CollisionPolicy, names: &mut NameRecord) -> Result<(Vec<File>, Vec<File>), Box<dyn Error>> {
+    // group the files by their remote folder and sort them, so the names are assigned in the same order on every run
+    let mut folders: BTreeMap<PathBuf, Vec<File>> = BTreeMap::new();
+    for file in files {
+        folders.entry(file.get_remote_parent().to_path_buf()).or_default().push(file);
+    }
+
+    let mut files_upload: Vec<File> = Vec::new();
+    let mut skipped: Vec<File> = Vec::new();
+    let mut num_renamed: usize = 0;
+    for (remote_parent, mut files) in folders {
+        files.sort_by(|a, b| a.get_local_path().cmp(b.get_local_path()));
+        let listing = index.get_listing(&remote_parent, client)?;
+        let assigned = resolve_folder(&files, &remote_parent, listing, policy, names)?;
+
+        for (mut file, remote_name) in files.into_iter().zip(assigned) {
+            match remote_name {
+                Some(remote_name) => {
+                    if Some(&remote_name) != file.get_remote_name().as_ref() {
+                        file.set_remote_name(remote_name);
+                        num_renamed += 1;
+                    }
+                    files_upload.push(file);
+                }
+                None => skipped.push(file)
+            }
+        }
+    }
+    if num_renamed > 0 {
+        output::println(format!("Uploading {} file(s) under a new name because another file of the upload has the same name.", num_renamed).yellow());
+    }
+    if !skipped.is_empty() {
+        output::println(format!("Skipping {} file(s) because another file of the upload has the same name:", skipped.len()).yellow());
+        for file in skipped.iter() {
+            output::println(format!("{:?}", file.get_local_path()).yellow());
+        }
+    }
+    Ok((files_upload, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // creates a local file with the given content in a sub folder of 'dir' which is uploaded to '/Photos'
+    fn create_file(dir: &Path, folder: &str, content: &str) -> File {
+        fs::create_dir_all(dir.join(folder)).unwrap();
+        let local_path = dir.join(folder).join("IMG_0001.JPG");
+        fs::write(&local_path, content).unwrap();
+        let mut file = File::new(&local_path, 100);
+        file.set_remote_parent(PathBuf::from("/Photos"));
+        file
+    }
+
+    fn get_remote_names(files: &[File]) -> Vec<String> {
+        files.iter().map(|file| file.get_remote_name().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_resolve_collisions_suffix() {
+        let temp_dir = tempdir().unwrap();
+        let names_path = temp_dir.path().join("names.json");
+        let files = vec![create_file(temp_dir.path(), "b", "bb"), create_file(temp_dir.path(), "c", "ccc")];
+
+        // the listing is cached, so the client is never used
+        let client = NextcloudClient::new("http://127.0.0.1:9".to_string(), "testuser".to_string(), "password".to_string());
+        let mut index = RemoteIndex::new();
+        index.add_listing(Path::new("/Photos"), vec![]);
+
+        // assert that the second file gets a suffix
+        let mut names = NameRecord::load(&names_path).unwrap();
+        let (files, _) = resolve_collisions(files, &client, &mut index, CollisionPolicy::Suffix, &mut names).unwrap();
+        assert_eq!(vec!["IMG_0001.JPG", "IMG_0001_1.JPG"], get_remote_names(&files));
+        names.save().unwrap();
+
+        // assert that a new colliding file, which is found first, does not change the recorded names
+        let files = vec![create_file(temp_dir.path(), "a", "a"), create_file(temp_dir.path(), "b", "bb"), create_file(temp_dir.path(), "c", "ccc")];
+        let mut names = NameRecord::load(&names_path).unwrap();
+        let (files, _) = resolve_collisions(files, &client, &mut index, CollisionPolicy::Suffix, &mut names).unwrap();
+        assert_eq!(vec!["IMG_0001_2.JPG", "IMG_0001.JPG", "IMG_0001_1.JPG"], get_remote_names(&files));
+    }
+
+    #[test]
+    fn test_resolve_collisions_hash_and_skip() {
+        let temp_dir = tempdir().unwrap();
+        let client = NextcloudClient::new("http://127.0.0.1:9".to_string(), "testuser".to_string(), "password".to_string());
+        let mut index = RemoteIndex::new();
+        index.add_listing(Path::new("/Photos"), vec![]);
+
+        // assert that the colliding file gets the start of the sha256 hash of its content
+        let files = vec![create_file(temp_dir.path(), "a", "abc"), create_file(temp_dir.path(), "b", "test file")];
+        let mut names = NameRecord::load(&temp_dir.path().join("names.json")).unwrap();
+        let (files, _) = resolve_collisions(files, &client, &mut index, CollisionPolicy::Hash, &mut names).unwrap();
+        assert_eq!(vec!["IMG_0001.JPG", "IMG_0001_9a30a503.JPG"], get_remote_names(&files));
+
+        // assert that only the first file is uploaded when skipping
+        let files = vec![create_file(temp_dir.path(), "c", "abc"), create_file(temp_dir.path(), "d", "test file")];
+        let mut names = NameRecord::load(&temp_dir.path().join("skip.json")).unwrap();
+        let (files, skipped) = resolve_collisions(files, &client, &mut index, CollisionPolicy::Skip, &mut names).unwrap();
+        assert_eq!(vec!["IMG_0001.JPG"], get_remote_names(&files));
+        assert!(files[0].get_local_path().ends_with("c/IMG_0001.JPG"));
+        assert!(skipped[0].get_local_path().ends_with("d/IMG_0001.JPG"));
+    }
+}
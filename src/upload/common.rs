@@ -1,220 +1,512 @@
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::{io, vec};
 use std::error::Error;
 use std::fs;
-use log::error;
+use log::{error, warn};
 use colored::*;
+use ignore::gitignore::Gitignore;
 use dirs::home_dir;
-use std::io::{BufRead, BufReader, Write};
-use reqwest::StatusCode;
+use std::io::{BufRead, BufReader};
 
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread::JoinHandle;
 
-use crate::nextcloud::NextcloudClient;
-use crate::filesystem::File;
-use crate::media::Extractor;
+use crate::nextcloud::{HttpError, NextcloudClient, ProgressCallback};
+use crate::filesystem::{File, Folder};
+use crate::media::{self, Extractor};
+use crate::date_source::DateSource;
 use crate::helpers;
+use crate::session::{Session, UploadState};
+use crate::upload::collision::{resolve_collisions, CollisionPolicy, NameRecord};
+use crate::upload::conflict::{resolve_conflicts, ConflictPolicy, RemoteIndex};
+use crate::upload::failed::{self, FailedUpload};
+use crate::upload::plan::{PlanFormat, UploadPlan};
+use crate::upload::filter::{self, ScanFilter, SkipReason, SkippedFiles};
+use crate::queue::WorkQueue;
+use crate::retry::{self, Decision, RetryPolicy};
+use crate::prompt::{CreateRoot, PromptMode};
+use crate::output::{self, Event};
+
+// reports the progress of the upload as progress bar or as event of the JSON output
+fn report_upload_progress(uploaded_size: u64, total_size: u64) {
+    if output::is_json() {
+        output::emit(&Event::UploadProgress { uploaded_size, total_size });
+    } else {
+        update_progress_bar("Uploading", uploaded_size, total_size);
+    }
+}
 
 // updates the terminal progress bar using the helpers::progress_bar function
-fn update_progress_bar(uploaded_size: u64, total_size: u64) {
+pub fn update_progress_bar(prefix: &str, uploaded_size: u64, total_size: u64) {
     // prettify the progress counter through converting the numbers into the suitable unit
-    let unit: String;
     let mut uploaded_size_rounded = uploaded_size as f64;
     let mut total_size_rounded = total_size as f64;
-    if total_size_rounded >= 1_000_000_000.0 {
+    let unit = if total_size_rounded >= 1_000_000_000.0 {
         total_size_rounded /= 1_000_000_000.0;
         uploaded_size_rounded /= 1_000_000_000.0;
-        unit = "G".to_string();
+        "G".to_string()
     } else {
         total_size_rounded /= 1_000_000.0;
         uploaded_size_rounded /= 1_000_000.0;
-        unit = "M".to_string();
-    }
+        "M".to_string()
+    };
 
     // build the suffix and print the updated progress bar using helpers::progress_bar
     let suffix = format!("{:.2}{}/{:.2}{}", uploaded_size_rounded, &unit, total_size_rounded, &unit);
-    helpers::progress_bar(uploaded_size, total_size, "Uploading", &suffix)
+    helpers::progress_bar(uploaded_size, total_size, prefix, &suffix)
 }
 
-// takes a vec of files and saves their paths to a file 'nextsyncengine-failed_uploads.txt' in the users home dir
-fn save_failed_files_txt(failed_files: &Vec<File>) -> Result<(), Box<io::Error>>  {
-    if let Some(home_dir) = home_dir() {
-        // create a file and open it in append mode
-        let mut failed_upload_txt = std::fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(home_dir.join("nextsyncengine-failed_uploads.txt"))?;
-
-        // go throught the vec of failed files and write all paths to the file
-        for f in failed_files {
-            if let Some(local_path) = f.get_local_path().to_str() {
-                failed_upload_txt.write_all(format!("{}\n", local_path).as_bytes())?;
-            }
-        }
-        println!("\n{}", format!("You can find the paths of the files which failed to upload at {:?}", home_dir.join("nextsyncengine-failed_uploads.txt")).red());
-        Ok(())
-    } else {
-        Err(Box::new(io::Error::new(io::ErrorKind::NotFound, "Could not locate the users home directory!")))
+// saves the failed uploads of the run to a file 'nextsyncengine-failed_uploads-<run id>.jsonl' in the users home dir
+fn save_failed_uploads(failed_uploads: &[FailedUpload], run_id: &str) -> Result<(), Box<dyn Error>> {
+    let home_dir = home_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not locate the users home directory!"))?;
+    let path = failed::get_failed_uploads_path(&home_dir, run_id);
+    for failed in failed_uploads {
+        output::emit(&failed.to_event());
     }
-
+    failed::save_failed_uploads(&path, run_id, failed_uploads)?;
+    output::println(format!("\n{}", format!("You can find the files which failed to upload and the reasons at {:?}. Pass the file to --file to upload them again.", path).red()));
+    Ok(())
 }
 
-// splits a vector into two new vectors each containing one half of the original vector
-fn split_vec_to_vecs(vec_org: Vec<File>, num_threads: usize) -> Vec<Vec<File>> {
-    // calculating the leftover of the division
-    let mut leftover = vec_org.len() % num_threads;
-    let mut splitted_vec_lens = vec![vec_org.len() / num_threads; num_threads];
-
-    // distributing leftover evenly on the lenghts of the sub vectors
-    while leftover > 0 {
-        for iter in splitted_vec_lens.iter_mut() {
-            if leftover == 0 {
-                break
-            }
-            *iter += 1;
-            leftover -= 1;
-        }
-    }
-
-    // extracting the sub vectors based on the before calculated lenghts in splitted_vec_lens
-    let mut current_index: usize = 0;
-    let mut results: Vec<Vec<File>> = vec![];
-    for i in 0..num_threads {
-        if let Some(&len) = splitted_vec_lens.get(i) {
-            if current_index + len > vec_org.len() {
-                error!("Index out of bound when splitting vec_org");
-                panic!()
-            }
-            results.push(vec_org[current_index..(current_index + len)].to_vec());
-            current_index += len;
-        }
-    }
-
-    results
-}
+// wrapper for travel_dir_local() and read_files_from_file. Returns the files and the number of files skipped by 'filter'.
+// Without an extractor the dates are not extracted and the files keep their modification date
+pub fn get_files_for_upload(path: &Path, folder_or_file: bool, extractor: Option<&Extractor>, filter: &ScanFilter) -> Result<(Vec<File>, SkippedFiles), Box<dyn Error>> {
+    output::emit(&Event::ScanStarted { path });
 
-// wrapper for travel_dir_local() and read_files_from_file
-pub fn get_files_for_upload(path: &Path, folder_or_file: bool, extractor: &Extractor) -> Result<Vec<File>, Box<dyn Error>> {
     // determine how the user provided the info which files should be uploaded
     let from_folder = true;
     if folder_or_file == from_folder {
-        return travel_dir_local(path, extractor)
+        return travel_dir_local(path, extractor, filter)
     }
-    read_files_from_file(path, extractor)
+    read_files_from_file(path, extractor, filter)
 }
 
-// creates a list of files from a text file containing local filesystem paths
-fn read_files_from_file(path: &Path, extractor: &Extractor) -> Result<Vec<File>, Box<dyn Error>> {
+// creates a list of files from a text file containing local filesystem paths or the lines of a failed uploads file
+fn read_files_from_file(path: &Path, extractor: Option<&Extractor>, filter: &ScanFilter) -> Result<(Vec<File>, SkippedFiles), Box<dyn Error>> {
     // create the file and its reader
     let mut files: Vec<File> = vec![];
+    let mut skipped = SkippedFiles::default();
     let file = fs::File::open(path)?;
     let reader = BufReader::new(file);
 
     // read the file line for line and create Files based on the local paths contained in the text file
     for line in reader.lines() {
         let line = line?;
-        let file_path = Path::new(line.trim());
-        let mtime = extractor.extract_date_time(file_path)?;
-        files.push(File::new(file_path, mtime));
+        let line = line.trim();
+        if line.is_empty() {
+            continue
+        }
+
+        // lines of a failed uploads file carry the date of the failed run, so it is not extracted again
+        if failed::is_record(line) {
+            let file = failed::read_record(line)?;
+            match filter.check(file.get_local_path(), false) {
+                Some(reason) => skipped.add(reason),
+                None => files.push(file)
+            }
+            continue
+        }
+
+        let file_path = Path::new(line);
+        // the listed paths have no common root, so the patterns are matched against the full path and the name
+        if let Some(reason) = filter.check(file_path, false) {
+            skipped.add(reason);
+            continue
+        }
+        // FIFOs and devices would block or fail the date extraction, missing files are still reported by scan_file
+        if fs::metadata(file_path).is_ok_and(|metadata| !metadata.is_file() && !metadata.is_dir()) {
+            skipped.add(SkipReason::Special);
+            continue
+        }
+        files.push(scan_file(file_path, extractor)?);
     }
-    Ok(files)
+    Ok((files, skipped))
 }
 
-// travels through the local folder and recursively stores all files in a vector
-pub fn travel_dir_local(root_path: &Path, extractor: &Extractor) -> Result<Vec<File>, Box<dyn Error>> {
-    let mut paths_folder: Vec<PathBuf> = Vec::new();
-    paths_folder.push(root_path.to_path_buf());
+// creates a File and determines its date. A file with a date no date source can read is not dropped but marked
+// with an unknown date, so a sorted upload can put it into a separate folder
+fn scan_file(path: &Path, extractor: Option<&Extractor>) -> Result<File, Box<dyn Error>> {
+    if !path.is_file() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("Path: {} is not a file!", path.display()))))
+    }
+
+    let extractor = match extractor {
+        Some(extractor) => extractor,
+        None => {
+            let mtime = media::get_metadata(&path.to_string_lossy())?.get_mtime();
+            return Ok(File::new(path, mtime))
+        }
+    };
+    match extractor.extract_date_time(path) {
+        Ok((date, date_source)) => {
+            let mut file = File::new(path, date.timestamp());
+            file.set_utc_offset(*date.offset());
+            file.set_date_source(date_source);
+            Ok(file)
+        }
+        Err(e) => {
+            warn!("The date of {:?} is unknown: {}", path, e);
+            // the modification date is still sent to nextcloud, even if it was not trusted for sorting
+            let mtime = media::get_metadata(&path.to_string_lossy()).map(|metadata| metadata.get_mtime()).unwrap_or_default();
+            let mut file = File::new(path, mtime);
+            file.set_unknown_date();
+            Ok(file)
+        }
+    }
+}
+
+// prints how many files were dated by each date source, so dates guessed from the file system are noticed before the upload
+pub fn print_date_sources(files: &[File]) {
+    let mut counts: BTreeMap<DateSource, usize> = BTreeMap::new();
+    for source in files.iter().filter_map(|file| file.get_date_source()) {
+        *counts.entry(source).or_default() += 1;
+    }
+    if !counts.is_empty() {
+        let counts: Vec<String> = counts.iter().map(|(source, count)| format!("{} {}", count, source)).collect();
+        output::println(format!("Dates of the files were read from: {}", counts.join(", ")).green());
+    }
+
+    let num_unknown = files.iter().filter(|file| file.has_unknown_date()).count();
+    if num_unknown > 0 {
+        output::println(format!("The date of {} file(s) is unknown, see the log for details.", num_unknown).yellow());
+    }
+}
+
+// folder of the local folder which still has to be scanned
+struct ScanFolder {
+    path: PathBuf,
+    // path without symlinks, which detects symlinks to folders that are already scanned
+    resolved_path: PathBuf,
+    // true if the folder was reached through a symlink
+    linked: bool,
+    // number of folders between the local folder and this folder, 0 for the local folder itself
+    depth: usize,
+    // .nsignore files of the folder and its parents
+    ignores: Vec<Rc<Gitignore>>
+}
+
+// travels through the local folder and recursively stores all files in a vector. Files and folders skipped by 'filter'
+// or a .nsignore file, symlinks which are not followed and special files like FIFOs are counted instead
+pub fn travel_dir_local(root_path: &Path, extractor: Option<&Extractor>, filter: &ScanFilter) -> Result<(Vec<File>, SkippedFiles), Box<dyn Error>> {
+    let root_resolved = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+    let mut visited: HashSet<PathBuf> = HashSet::from([root_resolved.clone()]);
+    let mut paths_folder: Vec<ScanFolder> = vec![ScanFolder { path: root_path.to_path_buf(), resolved_path: root_resolved, linked: false, depth: 0, ignores: Vec::new() }];
 
     let mut files: Vec<File> = Vec::new();
+    let mut skipped = SkippedFiles::default();
 
     // lists the items in a folder and add the subfolders to 'paths_folder' and the files to 'files'
-    while let Some(current_folder) = paths_folder.pop() {
-        let entries = fs::read_dir(current_folder)?;
+    while let Some(mut current_folder) = paths_folder.pop() {
+        if let Some(ignore) = filter::read_ignore_file(&current_folder.path) {
+            current_folder.ignores.push(Rc::new(ignore));
+        }
+        let ignore_refs: Vec<&Gitignore> = current_folder.ignores.iter().map(|ignore| ignore.as_ref()).collect();
+
+        let entries = fs::read_dir(&current_folder.path)?;
         for entry in entries {
             let entry = entry?;
-            let file_type = entry.file_type()?;
-    
+            let path = entry.path();
+            let mut resolved_path = current_folder.resolved_path.join(entry.file_name());
+            let mut linked = current_folder.linked;
+
+            // symlinks are replaced by their target, so the checks below see the folder or file they point to
+            let mut file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                if !filter.get_follow_symlinks() {
+                    skipped.add(SkipReason::Symlink);
+                    continue
+                }
+                match fs::metadata(&path).and_then(|metadata| Ok((metadata, fs::canonicalize(&path)?))) {
+                    Ok((metadata, target)) => {
+                        file_type = metadata.file_type();
+                        resolved_path = target;
+                        linked = true;
+                    }
+                    Err(e) => {
+                        warn!("Skipping the broken symlink {:?}: {}", path, e);
+                        skipped.add(SkipReason::BrokenSymlink);
+                        continue
+                    }
+                }
+            }
+            if !file_type.is_dir() && !file_type.is_file() {
+                skipped.add(SkipReason::Special);
+                continue
+            }
+
+            // skipped folders are not scanned at all
+            let relative_path = path.strip_prefix(root_path).unwrap_or(&path);
+            let reason = filter.check(relative_path, file_type.is_dir())
+                .or_else(|| filter::is_ignored(&ignore_refs, &path, file_type.is_dir()).then_some(SkipReason::Ignored));
+            if let Some(reason) = reason {
+                skipped.add(reason);
+                continue
+            }
+
             if file_type.is_dir() {
-                paths_folder.push(entry.path());
+                let depth = current_folder.depth + 1;
+                if filter.get_max_depth().is_some_and(|max_depth| depth > max_depth) {
+                    skipped.add(SkipReason::TooDeep);
+                    continue
+                }
+                if !visited.insert(resolved_path.clone()) {
+                    warn!("Skipping {:?}, because it links to the already scanned folder {:?}", path, resolved_path);
+                    skipped.add(SkipReason::SymlinkLoop);
+                    continue
+                }
+                paths_folder.push(ScanFolder { path, resolved_path, linked, depth, ignores: current_folder.ignores.clone() });
                 continue
             }
-            let mtime = extractor.extract_date_time(entry.path().as_path())?;
-            files.push(File::new(entry.path().as_path(), mtime));
+            let mut file = scan_file(&path, extractor)?;
+            if linked {
+                file.set_resolved_path(resolved_path);
+            }
+            files.push(file);
         }
     }
-    Ok(files)
+    Ok((files, skipped))
 }
 
-pub fn exists_root_folder(root_folder: &Path, client: &NextcloudClient) -> Result<bool, Box<dyn Error>> {
-    // check if the root folder exists and if not ask the user if he wants to create it
-    match client.exists_folder(root_folder) {
-        Ok(val) => {
-            if !val {
-                print!("{}", format!("The folder {} does not exist on your Nextcloud instance.\nWould you like to create it?\nYes(y) or No(n) ", root_folder.to_str().unwrap_or_default()).yellow());
-                let mut answer = String::new();
-                let _ = io::stdin().read_line(&mut answer);
-                if answer.trim().to_lowercase() == "y" || answer.trim().to_lowercase() == "yes" {
-                    if let Err(e) = client.create_folder(root_folder) {
-                        error!("{}", e);
-                        return Err(e)
-                    }
-                } else {
-                    return Ok(false)
-                }
-            }
-            Ok(true)
+// travels through the remote folder and recursively adds all folders as sub folders to 'root'.
+// The listed files are stored in 'index' to detect files which already exist on nextcloud
+pub fn travel_dir_dav(root: &mut Folder, client: &NextcloudClient, index: &mut RemoteIndex) -> Result<(), Box<dyn Error>> {
+    let mut paths_folder: Vec<PathBuf> = Vec::new();
+    paths_folder.push(Path::new(root.get_name().as_str()).to_path_buf());
+
+    //lists the sub folders in a folder and adds them to 'root' as sub folders and pushes them into 'paths_folder'
+    while let Some(current_folder) = paths_folder.pop() {
+        let items = client.ls_files(&current_folder)?;
+        for sub_folder in items.iter().filter(|item| item.is_folder()) {
+            root.add_sub_folder(Folder::new(sub_folder.get_name().to_string()), &current_folder);
+            paths_folder.push(current_folder.join(sub_folder.get_name()));
         }
-        Err(e) => return Err(e)
+        index.add_listing(&current_folder, items);
     }
+    Ok(())
 }
 
-// starts a upload batch with a fall back from which you can continue if some file uploads fail
-pub fn start_upload(files: Vec<File>, client: NextcloudClient, num_threads: usize) -> Result<(), Box<dyn Error>> {
-    let fallback_client = client.clone();
-    match threaded_upload(files, client, num_threads) {
-        // files uploaded without a fatal error
-        Ok(failed_files) => {
-            // check if any files weren't uploaded
-            if failed_files.len() == 0 {
-                return Ok(())
-            }
+// returns the full remote path of 'relative_path' below 'root' and adds its folders which do not exist on nextcloud yet
+// to 'new_folders'. 'root' caches the known and planned folders, so each missing folder is only added once
+pub fn plan_remote_path(root: &mut Folder, relative_path: &Path, new_folders: &mut Vec<PathBuf>) -> PathBuf {
+    let mut current_path = PathBuf::from(root.get_name());
+    for folder in relative_path.iter() {
+        let folder_path = current_path.join(folder);
+        if !root.has_subfolder(&folder_path) {
+            root.add_sub_folder(Folder::new(folder.to_string_lossy().to_string()), &current_path);
+            new_folders.push(folder_path.clone());
+        }
+        current_path = folder_path;
+    }
+    current_path
+}
 
-            // ask the user if he wants to try uploading again
-            println!("{}", format!("{} file(s) could not be uploaded:", failed_files.len()).red());
-            for file in failed_files.iter() {
-                println!("{}", format!("{:?}", file.get_local_path()).red());
-            }
-            print!("{}", format!("Try again?\nYes(y) or No(n) ").yellow());
-
-            let mut answer = String::new();
-            let _ = io::stdin().read_line(&mut answer);
-            if answer.trim().to_lowercase() == "y" || answer.trim().to_lowercase() == "yes" {
-                match threaded_upload(failed_files, fallback_client, num_threads) {
-                    // files uploaded without a fatal error
-                    Ok(second_failed_files) => {
-                        // check if any files weren't uploaded
-                        if second_failed_files.len() == 0 {
-                            return Ok(())
-                        }
-                        // write those file paths to a file in the users home dir
-                        println!("{}", "Second uploading attempt failed too!".red());
-                        return Ok(save_failed_files_txt(&second_failed_files)?)
-                    }
-                    Err(e) => return Err(e)
-                }
-            }
-            else {
-                // write the file paths to a file in the users home dir if the upload failed on the first attempt
-                return Ok(save_failed_files_txt(&failed_files)?)
+// creates the folders planned by plan_remote_path on nextcloud. Parents are planned before their sub folders, so they are created first
+pub fn create_folders(folders: &[PathBuf], client: &NextcloudClient) -> Result<(), Box<dyn Error>> {
+    for folder in folders {
+        client.create_folder(folder)?;
+        output::emit(&Event::FolderCreated { remote_path: folder });
+    }
+    Ok(())
+}
+
+// checks if the root folder of the upload exists and creates it if it is missing. Returns None if the user does not want
+// to create it. A dry run does not ask but returns false for a missing root folder, which is planned as new folder
+pub fn check_root_folder(root_folder: &Path, client: &NextcloudClient, options: &UploadOptions) -> Result<Option<bool>, Box<dyn Error>> {
+    if options.is_dry_run() {
+        return Ok(Some(client.exists_folder(root_folder)?))
+    }
+    Ok(exists_root_folder(root_folder, client, options.create_root, options.prompt)?.then_some(true))
+}
+
+// checks if the root folder exists and if not creates it depending on CREATE_ROOT and the answer of the user. Returns false
+// if the user does not want to create it and an error if it is not created without asking, so scripts notice the failure
+pub fn exists_root_folder(root_folder: &Path, client: &NextcloudClient, create_root: CreateRoot, prompt: PromptMode) -> Result<bool, Box<dyn Error>> {
+    if client.exists_folder(root_folder)? {
+        return Ok(true)
+    }
+
+    let question = format!("The folder {} does not exist on your Nextcloud instance.\nWould you like to create it?", root_folder.to_str().unwrap_or_default());
+    if create_root.should_create(prompt, &question) {
+        if let Err(e) = client.create_folder(root_folder) {
+            error!("{}", e);
+            return Err(e)
+        }
+        output::emit(&Event::FolderCreated { remote_path: root_folder });
+        return Ok(true)
+    }
+    if create_root == CreateRoot::Ask && prompt == PromptMode::Ask {
+        return Ok(false)
+    }
+    Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("The folder {} does not exist on your Nextcloud instance. Use --yes or CREATE_ROOT=always to create it without asking.", root_folder.display()))))
+}
+
+// options shared by all upload commands
+pub struct UploadOptions {
+    pub num_threads: usize,
+    // handling of files which already exist on nextcloud
+    pub on_conflict: ConflictPolicy,
+    // handling of files of the batch which would be uploaded to the same remote path
+    pub on_collision: CollisionPolicy,
+    // location of the record of the names chosen for colliding files
+    pub names_path: PathBuf,
+    // maximum number of attempts to upload a file before it is given up
+    pub max_attempts: u32,
+    // location of the journal which records the progress of the current upload session
+    pub session_path: PathBuf,
+    // files and folders of the local folder which are not uploaded
+    pub scan_filter: ScanFilter,
+    // prints the upload plan in the given format instead of changing anything on nextcloud
+    pub dry_run: Option<PlanFormat>,
+    // writes the upload plan to this file instead of changing anything on nextcloud
+    pub save_plan: Option<PathBuf>,
+    // answers the questions to the user, e.g. whether failed uploads are tried again
+    pub prompt: PromptMode,
+    // handling of a missing root folder on nextcloud
+    pub create_root: CreateRoot,
+    // number of times the failed uploads are tried again without asking, None to ask the user
+    pub retry_failed: Option<u32>
+}
+
+impl UploadOptions {
+    // returns true if the upload is only planned and nothing is changed on nextcloud
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some() || self.save_plan.is_some()
+    }
+}
+
+// resolves the collisions and conflicts of the files, whose remote parents were assigned, and either prints or saves
+// the plan of a dry run or creates the planned folders on nextcloud and uploads the files
+pub fn upload_planned(files: Vec<File>, new_folders: Vec<PathBuf>, remote_root: &Path, mut index: RemoteIndex, client: NextcloudClient, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    // give files which would be uploaded to the same remote path unique names
+    let mut names = NameRecord::load(&options.names_path)?;
+    let (files, skipped_collisions) = resolve_collisions(files, &client, &mut index, options.on_collision, &mut names)?;
+
+    // skip, overwrite or rename files which already exist on nextcloud
+    let (files, skipped_existing) = resolve_conflicts(files, &client, &mut index, options.on_conflict)?;
+    if output::is_json() {
+        UploadPlan::new(remote_root, vec![], &files, &skipped_collisions, &skipped_existing).emit_events();
+    }
+
+    if options.is_dry_run() {
+        let mut plan = UploadPlan::new(remote_root, new_folders, &files, &skipped_collisions, &skipped_existing);
+        if let Some(format) = options.dry_run {
+            plan.print(format)?;
+        }
+        if let Some(path) = &options.save_plan {
+            output::print("Writing the upload plan ... ".green());
+            plan.save(path)?;
+            output::println("done".green());
+            output::println(format!("Review the plan {:?} and upload it with 'nextsyncengine apply'.", path).green());
+        }
+        return Ok(0)
+    }
+
+    names.save()?;
+    execute_upload(files, new_folders, client, options)
+}
+
+// creates the planned folders on nextcloud and uploads the files
+pub fn execute_upload(files: Vec<File>, new_folders: Vec<PathBuf>, client: NextcloudClient, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    if !new_folders.is_empty() {
+        output::print(format!("Creating {} folder(s) on Nextcloud ... ", new_folders.len()).green());
+        create_folders(&new_folders, &client)?;
+        output::println("done".green());
+    }
+    start_upload(files, client, options)
+}
+
+// starts a upload batch with a fall back from which you can continue if some file uploads fail. Returns the number of
+// files which could not be uploaded
+pub fn start_upload(files: Vec<File>, client: NextcloudClient, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    // warn the user before an unfinished session is replaced by the new one
+    if options.session_path.is_file() {
+        if let Ok(previous) = Session::load(&options.session_path) {
+            let unfinished = previous.get_unfinished_files().len();
+            if unfinished > 0 {
+                warn!("Replacing the unfinished upload session {} with {} remaining file(s)", previous.get_session_id(), unfinished);
+                output::println(format!("The unfinished upload session {} with {} remaining file(s) is replaced by this upload.", previous.get_session_id(), unfinished).yellow());
             }
         }
-        Err(e) => return Err(e)
-    }    
+    }
+
+    // record every planned file in the session journal before anything is uploaded
+    let session = Arc::new(Session::create(&options.session_path, &files)?);
+    upload_session(files, client, session, options)
 }
 
-// starts the uploads in 4 parallel threads
-fn threaded_upload(files: Vec<File>, client: NextcloudClient, num_threads: usize) -> Result<Vec<File>, Box<dyn Error>> {
+// continues the upload session recorded in the session journal
+pub fn resume_upload(client: NextcloudClient, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    if !options.session_path.is_file() {
+        output::println("There is no upload session which could be resumed.".yellow());
+        return Ok(0)
+    }
+
+    let session = Session::load(&options.session_path)?;
+    let files = session.get_unfinished_files();
+    if files.is_empty() {
+        output::println(format!("The upload session {} is already complete.", session.get_session_id()).green());
+        session.remove()?;
+        return Ok(0)
+    }
+
+    let entries = session.get_entries();
+    let done = entries.iter().filter(|entry| entry.get_state() == UploadState::Done).count();
+    output::println(format!("Resuming upload session {}: {} of {} file(s) were already uploaded, {} remaining.", session.get_session_id(), done, entries.len(), files.len()).green());
+    upload_session(files, client, Arc::new(session), options)
+}
+
+// uploads the files of a session and removes the session journal once every file was uploaded
+fn upload_session(files: Vec<File>, client: NextcloudClient, session: Arc<Session>, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    let result = run_upload(files, client, Arc::clone(&session), options);
+
+    if session.get_unfinished_files().is_empty() {
+        if let Ok(session) = Arc::try_unwrap(session) {
+            session.remove()?;
+        }
+    } else {
+        output::println("Run 'nextsyncengine resume' to continue the upload of the remaining files.".yellow());
+    }
+    result
+}
+
+// uploads the files and retries failed uploads, either automatically RETRY_FAILED times or once if the user wants to.
+// Returns the number of files which could not be uploaded
+fn run_upload(files: Vec<File>, client: NextcloudClient, session: Arc<Session>, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    let mut failed_uploads = threaded_upload(files, client.clone(), Arc::clone(&session), options)?;
+    let mut retries: u32 = 0;
+    while !failed_uploads.is_empty() {
+        output::println(format!("{} file(s) could not be uploaded:", failed_uploads.len()).red());
+        for failed in failed_uploads.iter() {
+            output::println(format!("{:?}", failed.get_file().get_local_path()).red());
+        }
+
+        let retry = match options.retry_failed {
+            Some(max_retries) => retries < max_retries,
+            None => retries == 0 && options.prompt.confirm("Try again?")
+        };
+        if !retry {
+            break
+        }
+        retries += 1;
+        if options.retry_failed.is_some() {
+            output::println(format!("Trying again ({} of {}) ...", retries, options.retry_failed.unwrap_or_default()).yellow());
+        }
+        let failed_files: Vec<File> = failed_uploads.into_iter().map(|failed| failed.into_file()).collect();
+        failed_uploads = threaded_upload(failed_files, client.clone(), Arc::clone(&session), options)?;
+    }
+
+    // write the failed uploads to a file in the users home dir
+    if !failed_uploads.is_empty() {
+        save_failed_uploads(&failed_uploads, session.get_session_id())?;
+    }
+    Ok(failed_uploads.len())
+}
+
+// starts the uploads in 'num_threads' parallel threads
+fn threaded_upload(files: Vec<File>, client: NextcloudClient, session: Arc<Session>, options: &UploadOptions) -> Result<Vec<FailedUpload>, Box<dyn Error>> {
     // calculate the totat upload size
     let mut total_size: u64 = 0;
     for file in &files {
@@ -224,89 +516,132 @@ fn threaded_upload(files: Vec<File>, client: NextcloudClient, num_threads: usize
     // create a shared nextcloud_client and a counter to track the upload progress and update the progress bar accordingly 
     let shared_client = Arc::new(client);
     let shared_uploaded_size: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-    let shared_failed_files: Arc<Mutex<Vec<File>>> = Arc::new(Mutex::new(vec![]));
+    let shared_failed_files: Arc<Mutex<Vec<FailedUpload>>> = Arc::new(Mutex::new(vec![]));
+    // the retry policy is shared, so all workers pause while the server is in maintenance mode
+    let retry = Arc::new(RetryPolicy::new(options.max_attempts));
 
-    // split the original vec 'files' in 4 seperate vecs and pass each one of them to a seperate thread
-    let splitted_files: Vec<Vec<File>> = split_vec_to_vecs(files, num_threads);
+    // the workers pull the files from a shared queue, starting with the largest files
+    let num_workers = options.num_threads.min(files.len()).max(1);
+    let queue = Arc::new(WorkQueue::largest_first(files, |file| file.get_size()));
 
     // print initial progress bar
-    update_progress_bar(0, total_size);
+    report_upload_progress(0, total_size);
 
     // spawning the uploading threads
-    let mut threads: Vec<JoinHandle<Result<(), Box<reqwest::Error>>>> = vec![];
-    for v in splitted_files {
+    let mut threads: Vec<JoinHandle<Result<(), Box<HttpError>>>> = vec![];
+    for _ in 0..num_workers {
+        let queue_clone = Arc::clone(&queue);
         let uploaded_size = Arc::clone(&shared_uploaded_size);
         let client_clone = Arc::clone(&shared_client);
         let failed_files_clone = Arc::clone(&shared_failed_files);
+        let session_clone = Arc::clone(&session);
+        let retry_clone = Arc::clone(&retry);
         threads.push(std::thread::spawn(move || {
-            upload_files(v, client_clone, total_size, uploaded_size, failed_files_clone)
+            upload_files(queue_clone, client_clone, session_clone, retry_clone, total_size, uploaded_size, failed_files_clone)
         }));
     }
 
     // joining the uploading threads
-    let mut err: Option<Box<reqwest::Error>> = None;
+    let mut err: Option<Box<HttpError>> = None;
     for thread in threads {
         match thread.join() {
             Ok(Ok(())) => {}
-            
-            // handle a fatal error by writing the failed uploads to a .txt file and returning the error
+            // remember the fatal error of a thread, the other threads are still joined
             Ok(Err(e)) => {
                 err = Some(e);
             }
-            Err(_e) => return Err(Box::new(io::Error::new(io::ErrorKind::Other, "Failed to join upload threads!"))),
+            Err(_e) => return Err(Box::new(io::Error::other("Failed to join upload threads!"))),
         };
     }
-    let failed_files = shared_failed_files.lock().unwrap().to_owned();
+    let failed_uploads = shared_failed_files.lock().unwrap().to_owned();
 
-    // if the threads returned a http error, pass it to the caller function
+    // handle a fatal error by writing the failed uploads to 'nextsyncengine-failed_uploads-<run id>.jsonl' and passing the
+    // error to the caller function
     if let Some(e) = err {
-        save_failed_files_txt(&failed_files)?;
+        save_failed_uploads(&failed_uploads, session.get_session_id())?;
         return Err(e)
     }
-    Ok(failed_files)
+    Ok(failed_uploads)
+}
+
+// records the new state of a file in the session journal. A failing journal must not stop the upload, so errors are only logged
+fn set_session_state(session: &Session, file: &File, state: UploadState) {
+    if let Err(e) = session.set_state(file, state) {
+        error!("Failed to record the state of {:?} in the session journal: {}", file.get_local_path(), e);
+    }
+}
+
+// creates a callback which adds the sent bytes of a file to the shared progress and reports it with 'report'
+pub fn create_progress_callback(report: fn(u64, u64), shared_uploaded_size: Arc<Mutex<u64>>, sent_size: Arc<AtomicU64>, total_size: u64) -> ProgressCallback {
+    Arc::new(move |bytes: u64| {
+        sent_size.fetch_add(bytes, Ordering::Relaxed);
+        let mut uploaded_size = shared_uploaded_size.lock().unwrap();
+        let previous_size = *uploaded_size;
+        *uploaded_size += bytes;
+
+        // only redraw the progress bar if the displayed progress changed to keep the terminal responsive.
+        // The completed progress bar is drawn after the server confirmed the upload
+        if *uploaded_size < total_size && previous_size * 1000 / total_size != *uploaded_size * 1000 / total_size {
+            report(*uploaded_size, total_size);
+        }
+    })
 }
 
-// uploads a vec of files to nextcloud and updates the progress bar
-fn upload_files(files: Vec<File>, client: Arc<NextcloudClient>, total_size: u64, shared_uploaded_size: Arc<Mutex<u64>>, shared_failed_files: Arc<Mutex<Vec<File>>>) -> Result<(), Box<reqwest::Error>> {
-    for (index, file) in files.iter().enumerate() {
-        let size = file.get_size();
-        // uplaoding the current file to nextcloud
-        if let Err(e) = client.upload_file(&file) {
+// uploads the files of the queue to nextcloud until the queue is empty and updates the progress bar
+fn upload_files(queue: Arc<WorkQueue<File>>, client: Arc<NextcloudClient>, session: Arc<Session>, retry: Arc<RetryPolicy>, total_size: u64, shared_uploaded_size: Arc<Mutex<u64>>, shared_failed_files: Arc<Mutex<Vec<FailedUpload>>>) -> Result<(), Box<HttpError>> {
+    while let Some(file) = queue.pop() {
+        // the progress bar advances while the file content is sent to the server
+        let sent_size = Arc::new(AtomicU64::new(0));
+        let progress = create_progress_callback(report_upload_progress, Arc::clone(&shared_uploaded_size), Arc::clone(&sent_size), total_size);
+
+        // uplaoding the current file to nextcloud, failed attempts are repeated automatically
+        set_session_state(&session, &file, UploadState::Uploading);
+        let mut attempts: u32 = 0;
+        let result = retry.run(|| client.is_online().unwrap_or(false), || {
+            attempts += 1;
+            let result = client.upload_file(&file, Some(Arc::clone(&progress)));
+            if result.is_err() {
+                // remove the already sent bytes of the failed attempt from the progress bar
+                *shared_uploaded_size.lock().unwrap() -= sent_size.swap(0, Ordering::Relaxed);
+            }
+            result
+        });
+        if let Err(e) = result {
+            set_session_state(&session, &file, UploadState::Failed);
+
             // determine if the http error is fatal or not
-            if let Some(http_err) = e.downcast_ref::<reqwest::Error>() {
-                if let Some(status) = http_err.status() {
-                    match status {
-                        StatusCode::BAD_GATEWAY |
-                        StatusCode::SERVICE_UNAVAILABLE |
-                        StatusCode::GATEWAY_TIMEOUT |
-                        StatusCode::INSUFFICIENT_STORAGE |
-                        StatusCode::BAD_REQUEST |
-                        StatusCode::UNAUTHORIZED |
-                        StatusCode::FORBIDDEN  => {
-                            // push all remaining files into failed_files and terminate upload process by returning the error
-                            let mut failed_files = shared_failed_files.lock().unwrap();
-                            for i in index..files.len() {
-                                if let Some(f) = files.get(i) {
-                                    failed_files.push(f.clone());
-                                }
-                            }
-                            return Err(e.downcast::<reqwest::Error>().unwrap())
-                        }
-                        _ => {}
-                    }
+            let e = match e.downcast::<HttpError>() {
+                Ok(http_err) if retry::classify(http_err.as_ref()) == Decision::Fatal => {
+                    // push all remaining files into failed_files, which also stops the other workers, and terminate upload process by returning the error
+                    let mut failed_files = shared_failed_files.lock().unwrap();
+                    failed_files.push(FailedUpload::new(file, http_err.as_ref(), attempts));
+                    let stopped = io::Error::other(format!("The upload was stopped by a fatal error: {}", http_err));
+                    failed_files.extend(queue.drain().into_iter().map(|file| FailedUpload::new(file, &stopped, 0)));
+                    return Err(http_err)
                 }
-                // log the none fatal error and push the failed file to failed_files
-                error!("{}", e);
-                let mut failed_files = shared_failed_files.lock().unwrap();
-                failed_files.push(file.clone());
-                continue
-            }
+                Ok(http_err) => http_err as Box<dyn Error>,
+                Err(e) => e
+            };
+            // log the none fatal error
+            error!("{:?}: {}", file.get_local_path(), e);
+            // push the failed file to failed_files
+            let mut failed_files = shared_failed_files.lock().unwrap();
+            failed_files.push(FailedUpload::new(file, e.as_ref(), attempts));
+            continue
         }
 
-        // updating the progress bar
+        set_session_state(&session, &file, UploadState::Done);
+        output::emit(&Event::UploadDone {
+            local_path: file.get_local_path(),
+            remote_path: file.get_remote_path(),
+            size: file.get_size(),
+            attempts
+        });
+
+        // make sure the progress bar shows the uploaded file completely
         let mut uploaded_size = shared_uploaded_size.lock().unwrap();
-        *uploaded_size += size;
-        update_progress_bar(*uploaded_size, total_size);
+        *uploaded_size += file.get_size().saturating_sub(sent_size.load(Ordering::Relaxed));
+        report_upload_progress(*uploaded_size, total_size);
     }
     Ok(())
 }
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::io;
+use colored::*;
+use reqwest::StatusCode;
+
+use crate::filesystem::{File, RemoteFile};
+use crate::nextcloud::{self, NextcloudClient};
+use crate::output;
+
+// decides what happens with a local file if a file with the same name already exists on nextcloud
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConflictPolicy {
+    // never touch an existing file
+    Skip,
+    // always replace the existing file
+    Overwrite,
+    // upload the local file next to the existing file using a free name
+    Rename,
+    // replace the existing file if the local file is newer
+    Newer
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "newer" => Ok(ConflictPolicy::Newer),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown conflict policy '{}'", s)))
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Resolution {
+    Upload,
+    Skip,
+    Rename
+}
+
+// cached listings of remote folders used to detect files which already exist on nextcloud
+pub struct RemoteIndex {
+    folders: HashMap<PathBuf, HashMap<String, RemoteFile>>
+}
+
+impl RemoteIndex {
+    pub fn new() -> RemoteIndex {
+        RemoteIndex {
+            folders: HashMap::new()
+        }
+    }
+
+    // adds the listing of a remote folder to the index
+    pub fn add_listing(&mut self, folder: &Path, items: Vec<RemoteFile>) {
+        let listing = self.folders.entry(folder.to_path_buf()).or_default();
+        for item in items {
+            listing.insert(item.get_name().to_string(), item);
+        }
+    }
+
+    // returns the cached listing of a folder and lists the folder on nextcloud if it is not cached yet
+    pub fn get_listing(&mut self, folder: &Path, client: &NextcloudClient) -> Result<&mut HashMap<String, RemoteFile>, Box<dyn Error>> {
+        if !self.folders.contains_key(folder) {
+            let items = match client.ls_files(folder) {
+                Ok(items) => items,
+                Err(e) => {
+                    // a folder which does not exist yet can not contain any conflicting files
+                    let not_found = nextcloud::get_error_status(e.as_ref()) == Some(StatusCode::NOT_FOUND);
+                    if !not_found {
+                        return Err(e)
+                    }
+                    vec![]
+                }
+            };
+            self.add_listing(folder, items);
+        }
+        Ok(self.folders.get_mut(folder).unwrap())
+    }
+}
+
+// decides how a local file is handled if a remote file with the same name exists
+fn resolve(policy: ConflictPolicy, file: &File, remote: &RemoteFile) -> Resolution {
+    // files with the same size and modification date were already uploaded
+    let identical = !remote.is_folder() && remote.get_size() == file.get_size() && remote.get_mtime() == file.get_mtime();
+
+    match policy {
+        ConflictPolicy::Overwrite => Resolution::Upload,
+        _ if identical => Resolution::Skip,
+        ConflictPolicy::Skip => Resolution::Skip,
+        ConflictPolicy::Rename => Resolution::Rename,
+        ConflictPolicy::Newer => {
+            if !remote.is_folder() && file.get_mtime() > remote.get_mtime() {
+                Resolution::Upload
+            } else {
+                Resolution::Skip
+            }
+        }
+    }
+}
+
+// returns the first free name following the pattern 'name (n).ext' in a folder listing
+fn get_free_name(name: &str, listing: &HashMap<String, RemoteFile>) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|val| val.to_str()).unwrap_or(name);
+    let extension = path.extension().and_then(|val| val.to_str());
+
+    let mut counter: u64 = 1;
+    loop {
+        let candidate = match extension {
+            Some(ext) => format!("{} ({}).{}", stem, counter, ext),
+            None => format!("{} ({})", stem, counter)
+        };
+        if !listing.contains_key(&candidate) {
+            return candidate
+        }
+        counter += 1;
+    }
+}
+
+// compares the files with the existing files on nextcloud and returns the files which will be uploaded and the files
+// which are skipped, because they already exist
+pub fn resolve_conflicts(files: Vec<File>, client: &NextcloudClient, index: &mut RemoteIndex, policy: ConflictPolicy) -> Result<(Vec<File>, Vec<File>), Box<dyn Error>> {
+    let mut files_upload: Vec<File> = Vec::with_capacity(files.len());
+    let mut skipped: Vec<File> = Vec::new();
+    let mut num_renamed: usize = 0;
+
+    for mut file in files {
+        let name = match file.get_remote_name() {
+            Some(name) => name,
+            None => {
+                files_upload.push(file);
+                continue
+            }
+        };
+        let listing = index.get_listing(file.get_remote_parent(), client)?;
+
+        if let Some(remote) = listing.get(&name) {
+            match resolve(policy, &file, remote) {
+                Resolution::Upload => file.set_replaces_existing(),
+                Resolution::Skip => {
+                    skipped.push(file);
+                    continue
+                }
+                Resolution::Rename => {
+                    file.set_remote_name(get_free_name(&name, listing));
+                    num_renamed += 1;
+                }
+            }
+        }
+
+        // register the planned file, so later files of the batch are compared against it too
+        let remote_name = file.get_remote_name().unwrap_or(name);
+        listing.insert(remote_name.clone(), RemoteFile::new(remote_name, false, file.get_size(), file.get_mtime()));
+        files_upload.push(file);
+    }
+
+    if !skipped.is_empty() {
+        output::println(format!("Skipping {} file(s) which already exist on Nextcloud.", skipped.len()).yellow());
+    }
+    if num_renamed > 0 {
+        output::println(format!("Uploading {} file(s) under a new name because a different file with the same name exists on Nextcloud.", num_renamed).yellow());
+    }
+    Ok((files_upload, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use std::fs;
+
+    // creates a local file with the given content and modification date
+    fn create_file(dir: &Path, name: &str, content: &str, mtime: i64) -> File {
+        let local_path = dir.join(name);
+        fs::write(&local_path, content).unwrap();
+        let mut file = File::new(&local_path, mtime);
+        file.set_remote_parent(PathBuf::from("/Photos"));
+        file
+    }
+
+    #[test]
+    fn test_resolve_identical_file() {
+        let temp_dir = tempdir().unwrap();
+        let file = create_file(temp_dir.path(), "a.jpg", "abc", 100);
+        let remote = RemoteFile::new("a.jpg".to_string(), false, 3, 100);
+
+        // assert that identical files are only uploaded again when overwriting
+        assert_eq!(Resolution::Skip, resolve(ConflictPolicy::Skip, &file, &remote));
+        assert_eq!(Resolution::Skip, resolve(ConflictPolicy::Rename, &file, &remote));
+        assert_eq!(Resolution::Skip, resolve(ConflictPolicy::Newer, &file, &remote));
+        assert_eq!(Resolution::Upload, resolve(ConflictPolicy::Overwrite, &file, &remote));
+    }
+
+    #[test]
+    fn test_resolve_different_file() {
+        let temp_dir = tempdir().unwrap();
+        let file = create_file(temp_dir.path(), "a.jpg", "abc", 100);
+        let remote_older = RemoteFile::new("a.jpg".to_string(), false, 5, 50);
+        let remote_newer = RemoteFile::new("a.jpg".to_string(), false, 5, 150);
+
+        // assert that each policy handles a different file with the same name accordingly
+        assert_eq!(Resolution::Skip, resolve(ConflictPolicy::Skip, &file, &remote_older));
+        assert_eq!(Resolution::Upload, resolve(ConflictPolicy::Overwrite, &file, &remote_older));
+        assert_eq!(Resolution::Rename, resolve(ConflictPolicy::Rename, &file, &remote_older));
+        assert_eq!(Resolution::Upload, resolve(ConflictPolicy::Newer, &file, &remote_older));
+        assert_eq!(Resolution::Skip, resolve(ConflictPolicy::Newer, &file, &remote_newer));
+    }
+
+    #[test]
+    fn test_resolve_conflicts_rename() {
+        let temp_dir = tempdir().unwrap();
+        let files = vec![
+            create_file(temp_dir.path(), "a.jpg", "abc", 100),
+            create_file(temp_dir.path(), "b.jpg", "abc", 100)
+        ];
+
+        // the remote folder already contains a different 'a.jpg' and 'a (1).jpg' and an identical 'b.jpg'
+        let mut index = RemoteIndex::new();
+        index.add_listing(Path::new("/Photos"), vec![
+            RemoteFile::new("a.jpg".to_string(), false, 10, 100),
+            RemoteFile::new("a (1).jpg".to_string(), false, 10, 100),
+            RemoteFile::new("b.jpg".to_string(), false, 3, 100)
+        ]);
+
+        // the listing is cached, so the client is never used
+        let client = NextcloudClient::new("http://127.0.0.1:9".to_string(), "testuser".to_string(), "password".to_string());
+        let (files, skipped) = resolve_conflicts(files, &client, &mut index, ConflictPolicy::Rename).unwrap();
+
+        // assert that 'a.jpg' is uploaded under a free name and 'b.jpg' is skipped
+        assert_eq!(1, files.len());
+        assert_eq!(Some("a (2).jpg".to_string()), files[0].get_remote_name());
+        assert_eq!(1, skipped.len());
+        assert!(skipped[0].get_local_path().ends_with("b.jpg"));
+    }
+}
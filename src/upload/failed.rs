@@ -0,0 +1,201 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::date_source::DateSource;
+use crate::filesystem::File;
+use crate::nextcloud;
+use crate::output::Event;
+
+// version of a line of the failed uploads file, which is increased whenever a change breaks reading older files
+const RECORD_VERSION: u32 = 1;
+
+// a file which could not be uploaded and the reason why
+#[derive(Clone)]
+pub struct FailedUpload {
+    file: File,
+    error: String,
+    // status of the last http response, None if the request failed before the server answered
+    http_status: Option<u16>,
+    // number of transfers which were tried, 0 if the file was not tried because the upload was stopped
+    attempts: u32
+}
+
+impl FailedUpload {
+    pub fn new(file: File, e: &(dyn Error + 'static), attempts: u32) -> FailedUpload {
+        FailedUpload {
+            file,
+            error: e.to_string(),
+            http_status: nextcloud::get_error_status(e).map(|status| status.as_u16()),
+            attempts
+        }
+    }
+
+    pub fn get_file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn into_file(self) -> File {
+        self.file
+    }
+
+    // returns the event of the JSON output which reports the failed upload
+    pub fn to_event(&self) -> Event<'_> {
+        Event::UploadFailed {
+            local_path: self.file.get_local_path(),
+            remote_path: self.file.get_remote_path(),
+            error: &self.error,
+            http_status: self.http_status,
+            attempts: self.attempts
+        }
+    }
+}
+
+// a single line of the failed uploads file
+#[derive(Serialize, Deserialize, Debug)]
+struct FailedRecord {
+    version: u32,
+    run_id: String,
+    local_path: PathBuf,
+    remote_parent: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote_name: Option<String>,
+    mtime: i64,
+    size: u64,
+    // date the file is sorted by in RFC 3339 format, None if no date source knew it
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    date_source: Option<String>,
+    error: String,
+    #[serde(default)]
+    http_status: Option<u16>,
+    attempts: u32
+}
+
+// returns the path of the failed uploads file of a run, each run writes its own file
+pub fn get_failed_uploads_path(folder: &Path, run_id: &str) -> PathBuf {
+    folder.join(format!("nextsyncengine-failed_uploads-{}.jsonl", run_id))
+}
+
+// writes one JSON line per failed upload to 'path'. The file can be passed to --file to upload the files again
+pub fn save_failed_uploads(path: &Path, run_id: &str, failed_uploads: &[FailedUpload]) -> Result<(), Box<dyn Error>> {
+    let mut content = String::new();
+    for failed in failed_uploads {
+        let file = &failed.file;
+        let record = FailedRecord {
+            version: RECORD_VERSION,
+            run_id: run_id.to_string(),
+            local_path: file.get_local_path().to_path_buf(),
+            remote_parent: file.get_remote_parent().to_path_buf(),
+            remote_name: file.get_remote_name(),
+            mtime: file.get_mtime(),
+            size: file.get_size(),
+            date: file.get_date().filter(|_| !file.has_unknown_date()).map(|date| date.to_rfc3339()),
+            date_source: file.get_date_source().map(|source| source.to_string()),
+            error: failed.error.clone(),
+            http_status: failed.http_status,
+            attempts: failed.attempts
+        };
+        content.push_str(&serde_json::to_string(&record)?);
+        content.push('\n');
+    }
+    let mut failed_uploads_file = fs::File::create(path)?;
+    failed_uploads_file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+// returns true if a line of a --file list is a record of a failed uploads file instead of a plain path
+pub fn is_record(line: &str) -> bool {
+    line.starts_with('{')
+}
+
+// creates the file of a line of a failed uploads file. The date recorded by the failed run is used, so it is not extracted again
+pub fn read_record(line: &str) -> Result<File, Box<dyn Error>> {
+    let record: FailedRecord = serde_json::from_str(line)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid line in the list of files: {}", e)))?;
+    if record.version > RECORD_VERSION {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "The list of files was written by a newer version of nextsyncengine")))
+    }
+    if !record.local_path.is_file() {
+        return Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("Path: {} is not a file!", record.local_path.display()))))
+    }
+
+    let mut file = File::new(&record.local_path, record.mtime);
+    let date = record.date.as_deref().and_then(|date| DateTime::parse_from_rfc3339(date).ok());
+    match date {
+        Some(date) => file.set_utc_offset(*date.offset()),
+        None => file.set_unknown_date()
+    }
+    if let Some(date_source) = record.date_source.and_then(|source| source.parse::<DateSource>().ok()) {
+        file.set_date_source(date_source);
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nextcloud::NextcloudClient;
+    use chrono::FixedOffset;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_read_failed_uploads() {
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("a.jpg");
+        fs::write(&local_path, "abc").unwrap();
+        let mut file = File::new(&local_path, 1_700_000_000);
+        file.set_remote_parent(PathBuf::from("/Photos/2023/11"));
+        file.set_utc_offset(FixedOffset::east_opt(3600).unwrap());
+        file.set_date_source(DateSource::Filename);
+        let mut unknown = File::new(&local_path, 1_600_000_000);
+        unknown.set_unknown_date();
+
+        // the server has no space left
+        let mut mock = mockito::Server::new();
+        let mkcol_mock = mock.mock("MKCOL", "/remote.php/dav/files/testuser/Photos")
+            .with_status(507)
+            .create();
+        let client = NextcloudClient::new(mock.url(), "testuser".to_string(), "password".to_string());
+        let http_error = client.create_folder(Path::new("/Photos")).unwrap_err();
+        mkcol_mock.assert();
+
+        let failed = vec![
+            FailedUpload::new(file, http_error.as_ref(), 5),
+            FailedUpload::new(unknown, &io::Error::other("connection reset"), 2)
+        ];
+        // assert that the event of the JSON output carries the remote path and the http status
+        let event = serde_json::to_value(failed[0].to_event()).unwrap();
+        assert_eq!("upload_failed", event["event"]);
+        assert_eq!("/Photos/2023/11/a.jpg", event["remote_path"]);
+        assert_eq!(507, event["http_status"]);
+
+        let path = get_failed_uploads_path(temp_dir.path(), "18a2b");
+        save_failed_uploads(&path, "18a2b", &failed).unwrap();
+
+        // assert that each failed upload is written as one line with the error details
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(2, lines.len());
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(1, record["version"]);
+        assert_eq!("18a2b", record["run_id"]);
+        assert_eq!("/Photos/2023/11", record["remote_parent"]);
+        assert_eq!(507, record["http_status"]);
+        assert_eq!(5, record["attempts"]);
+        assert!(is_record(lines[0]));
+        assert!(!is_record(local_path.to_str().unwrap()));
+
+        // assert that the recorded date is used when the line is read again
+        let file = read_record(lines[0]).unwrap();
+        assert_eq!(1_700_000_000, file.get_mtime());
+        assert_eq!(Some(DateSource::Filename), file.get_date_source());
+        assert_eq!("2023-11-14T23:13:20+01:00", file.get_date().unwrap().to_rfc3339());
+        assert!(read_record(lines[1]).unwrap().has_unknown_date());
+        assert!(read_record("{\"local_path\": \"a.jpg\"}").is_err());
+    }
+}
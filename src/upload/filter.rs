@@ -0,0 +1,335 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use colored::*;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::output;
+
+// gitignore-style file which is honored in every scanned folder
+pub const IGNORE_FILE_NAME: &str = ".nsignore";
+
+// files and folders created by operating systems, phones and editors, which are never worth uploading
+const DEFAULT_EXCLUDES: [&str; 23] = [
+    ".DS_Store", "._*", ".Spotlight-V100", ".Trashes", ".fseventsd", ".TemporaryItems",
+    "Thumbs.db", "ehthumbs.db", "desktop.ini", "$RECYCLE.BIN", "System Volume Information",
+    ".trashed-*", ".pending-*", ".thumbnails", "@eaDir",
+    "*~", "~$*", ".~lock.*#", "*.swp", "*.tmp", "*.part", "*.part.etag", "*.crdownload"
+];
+
+// reason why a file or folder is not uploaded
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum SkipReason {
+    // matched by the built-in list of junk files
+    Junk,
+    // matched by --exclude or not matched by --include
+    Excluded,
+    // matched by a .nsignore file
+    Ignored,
+    // name starts with a dot and --skip-hidden is set
+    Hidden,
+    // symlink which is not followed without --follow-symlinks
+    Symlink,
+    // symlink whose target does not exist
+    BrokenSymlink,
+    // symlink to a folder which is already scanned, e.g. to one of its own parents
+    SymlinkLoop,
+    // folder below --max-depth
+    TooDeep,
+    // device, FIFO, socket or other file which is neither a regular file nor a folder
+    Special
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SkipReason::Junk => "junk",
+            SkipReason::Excluded => "excluded by --include/--exclude",
+            SkipReason::Ignored => "ignored by .nsignore",
+            SkipReason::Hidden => "hidden",
+            SkipReason::Symlink => "symlinks (use --follow-symlinks)",
+            SkipReason::BrokenSymlink => "broken symlinks",
+            SkipReason::SymlinkLoop => "symlinks to already scanned folders",
+            SkipReason::TooDeep => "folders below --max-depth",
+            SkipReason::Special => "special files"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// number of files and folders which were skipped during the scan of the local folder
+#[derive(Default, Debug)]
+pub struct SkippedFiles {
+    counts: BTreeMap<SkipReason, usize>
+}
+
+impl SkippedFiles {
+    pub fn add(&mut self, reason: SkipReason) {
+        *self.counts.entry(reason).or_default() += 1;
+    }
+
+    // prints the number of skipped files and folders for each reason
+    pub fn print(&self) {
+        if self.counts.is_empty() {
+            return
+        }
+        let total: usize = self.counts.values().sum();
+        let counts: Vec<String> = self.counts.iter().map(|(reason, count)| format!("{} {}", count, reason)).collect();
+        output::println(format!("Skipped {} file(s) and folder(s): {}", total, counts.join(", ")).yellow());
+    }
+}
+
+// decides which files and folders of the local folder are uploaded based on --include, --exclude,
+// the built-in junk patterns and the .nsignore files, and how the local folder is walked
+#[derive(Clone, Debug)]
+pub struct ScanFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    // None if the built-in patterns were disabled with --no-default-excludes
+    junk: Option<GlobSet>,
+    skip_hidden: bool,
+    follow_symlinks: bool,
+    // number of folder levels below the local folder which are scanned, None for no limit
+    max_depth: Option<usize>
+}
+
+impl Default for ScanFilter {
+    fn default() -> Self {
+        ScanFilter::new(&[], &[], true).unwrap()
+    }
+}
+
+impl ScanFilter {
+    // creates the filter from glob patterns like "*.jpg" or "raw/**". Returns an error if a pattern is invalid
+    pub fn new(include: &[String], exclude: &[String], default_excludes: bool) -> Result<ScanFilter, io::Error> {
+        let junk = if default_excludes {
+            Some(build_glob_set(&DEFAULT_EXCLUDES.map(|pattern| pattern.to_string()))?)
+        } else {
+            None
+        };
+        Ok(ScanFilter {
+            include: Some(build_glob_set(include)?).filter(|_| !include.is_empty()),
+            exclude: Some(build_glob_set(exclude)?).filter(|_| !exclude.is_empty()),
+            junk,
+            skip_hidden: false,
+            follow_symlinks: false,
+            max_depth: None
+        })
+    }
+
+    pub fn set_skip_hidden(&mut self, skip_hidden: bool) {
+        self.skip_hidden = skip_hidden;
+    }
+
+    pub fn get_follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    pub fn set_follow_symlinks(&mut self, follow_symlinks: bool) {
+        self.follow_symlinks = follow_symlinks;
+    }
+
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    // returns why the file or folder is skipped or None if it is uploaded. 'relative_path' is the path below the
+    // scanned folder, patterns match either the whole relative path or the name. --include only applies to files
+    pub fn check(&self, relative_path: &Path, is_dir: bool) -> Option<SkipReason> {
+        let name = relative_path.file_name().map(Path::new).unwrap_or(relative_path);
+        let is_match = |set: &GlobSet| set.is_match(relative_path) || set.is_match(name);
+
+        if name == Path::new(IGNORE_FILE_NAME) || self.junk.as_ref().is_some_and(is_match) {
+            return Some(SkipReason::Junk)
+        }
+        if self.skip_hidden && name.to_string_lossy().starts_with('.') {
+            return Some(SkipReason::Hidden)
+        }
+        if self.exclude.as_ref().is_some_and(is_match) {
+            return Some(SkipReason::Excluded)
+        }
+        if !is_dir && self.include.as_ref().is_some_and(|include| !is_match(include)) {
+            return Some(SkipReason::Excluded)
+        }
+        None
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, io::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid glob pattern '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+}
+
+// reads the .nsignore file of a folder. Returns None if the folder has none
+pub fn read_ignore_file(folder: &Path) -> Option<Gitignore> {
+    let path = folder.join(IGNORE_FILE_NAME);
+    if !path.is_file() {
+        return None
+    }
+    let mut builder = GitignoreBuilder::new(folder);
+    if let Some(e) = builder.add(&path) {
+        warn!("Some lines of {:?} are invalid and ignored: {}", path, e);
+    }
+    match builder.build() {
+        Ok(ignore) => Some(ignore),
+        Err(e) => {
+            warn!("Failed to read {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+// returns true if the .nsignore files of the folder and its parents ignore the path. 'ignores' is ordered from the
+// root to the deepest folder, so a deeper file can whitelist a path ignored by a parent like git does
+pub fn is_ignored(ignores: &[&Gitignore], path: &Path, is_dir: bool) -> bool {
+    for ignore in ignores.iter().rev() {
+        match ignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::Extractor;
+    use crate::upload::common;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_filter() {
+        // assert that junk files and folders are skipped by default
+        let filter = ScanFilter::default();
+        for path in ["a/.DS_Store", "Thumbs.db", "DCIM/.trashed-1700000000-IMG_0001.jpg", "@eaDir", "notes.txt~", "~$report.docx"] {
+            assert_eq!(Some(SkipReason::Junk), filter.check(Path::new(path), false), "{}", path);
+        }
+        assert_eq!(None, filter.check(Path::new("DCIM/IMG_0001.jpg"), false));
+
+        // assert that --include only selects files and --exclude wins over --include
+        let filter = ScanFilter::new(&["*.jpg".to_string()], &["raw/**".to_string(), "*.tmp.jpg".to_string()], false).unwrap();
+        assert_eq!(None, filter.check(Path::new("2024/IMG_0001.jpg"), false));
+        assert_eq!(None, filter.check(Path::new("2024"), true));
+        assert_eq!(Some(SkipReason::Excluded), filter.check(Path::new("2024/IMG_0001.mov"), false));
+        assert_eq!(Some(SkipReason::Excluded), filter.check(Path::new("raw/IMG_0001.jpg"), false));
+        assert_eq!(Some(SkipReason::Excluded), filter.check(Path::new("a.tmp.jpg"), false));
+        assert_eq!(None, filter.check(Path::new("Thumbs.db"), true));
+
+        assert!(ScanFilter::new(&["a[".to_string()], &[], true).is_err());
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let temp_dir = tempdir().unwrap();
+        let sub_dir = temp_dir.path().join("2024");
+        fs::create_dir(&sub_dir).unwrap();
+        fs::write(temp_dir.path().join(IGNORE_FILE_NAME), "*.mov\n/exports/\n").unwrap();
+        fs::write(sub_dir.join(IGNORE_FILE_NAME), "!keep.mov\n").unwrap();
+
+        let root = read_ignore_file(temp_dir.path()).unwrap();
+        let sub = read_ignore_file(&sub_dir).unwrap();
+        assert!(read_ignore_file(&temp_dir.path().join("missing")).is_none());
+
+        // assert that patterns apply to sub folders and deeper files can whitelist paths
+        assert!(is_ignored(&[&root], &temp_dir.path().join("a.mov"), false));
+        assert!(is_ignored(&[&root], &temp_dir.path().join("exports"), true));
+        assert!(!is_ignored(&[&root], &temp_dir.path().join("a.jpg"), false));
+        assert!(is_ignored(&[&root, &sub], &sub_dir.join("b.mov"), false));
+        assert!(!is_ignored(&[&root, &sub], &sub_dir.join("keep.mov"), false));
+    }
+
+    #[test]
+    fn test_travel_dir_local_skipped() {
+        let temp_dir = tempdir().unwrap();
+        for folder in ["2024/@eaDir", "exports"] {
+            fs::create_dir_all(temp_dir.path().join(folder)).unwrap();
+        }
+        for path in ["2024/a.jpg", "2024/.DS_Store", "2024/@eaDir/a.jpg", "2024/b.mov", "exports/c.jpg", "d.tmp.jpg"] {
+            fs::write(temp_dir.path().join(path), "abc").unwrap();
+        }
+        fs::write(temp_dir.path().join(IGNORE_FILE_NAME), "exports/\n").unwrap();
+
+        // assert that junk, excluded and ignored files are counted and skipped folders are not scanned
+        let filter = ScanFilter::new(&[], &["*.mov".to_string(), "*.tmp.*".to_string()], true).unwrap();
+        let (files, skipped) = common::travel_dir_local(temp_dir.path(), Some(&Extractor::new(None)), &filter).unwrap();
+        let paths: Vec<&Path> = files.iter().map(|file| file.get_local_path()).collect();
+        assert_eq!(vec![temp_dir.path().join("2024/a.jpg").as_path()], paths);
+        let expected = BTreeMap::from([(SkipReason::Junk, 3), (SkipReason::Excluded, 2), (SkipReason::Ignored, 1)]);
+        assert_eq!(expected, skipped.counts);
+
+        // assert that a scan without an extractor, like the one of verify, filters the same files but reads no dates
+        let (files, skipped) = common::travel_dir_local(temp_dir.path(), None, &filter).unwrap();
+        assert_eq!(1, files.len());
+        assert_eq!(None, files[0].get_date_source());
+        assert_eq!(expected, skipped.counts);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_travel_dir_local_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("photos");
+        for folder in ["photos/2024/01", "photos/.cache", "other"] {
+            fs::create_dir_all(temp_dir.path().join(folder)).unwrap();
+        }
+        for path in ["photos/a.jpg", "photos/.hidden.jpg", "photos/2024/b.jpg", "photos/2024/01/c.jpg", "other/d.jpg"] {
+            fs::write(temp_dir.path().join(path), "abc").unwrap();
+        }
+        symlink(&root, root.join("2024/loop")).unwrap();
+        symlink(temp_dir.path().join("other"), root.join("other")).unwrap();
+        symlink(temp_dir.path().join("missing.jpg"), root.join("broken.jpg")).unwrap();
+        let status = std::process::Command::new("mkfifo").arg(root.join("fifo.jpg")).status().unwrap();
+        assert!(status.success());
+
+        let scan = |filter: &ScanFilter| {
+            let (files, skipped) = common::travel_dir_local(&root, Some(&Extractor::new(None)), filter).unwrap();
+            let mut paths: Vec<(PathBuf, PathBuf)> = files.iter()
+                .map(|file| (file.get_local_path().strip_prefix(&root).unwrap().to_path_buf(), file.get_resolved_path().to_path_buf()))
+                .collect();
+            paths.sort();
+            (paths.into_iter().map(|(path, _)| path).collect::<Vec<PathBuf>>(), files, skipped.counts)
+        };
+
+        // assert that symlinks are skipped by default and special files never reach the date extraction
+        let (paths, _, counts) = scan(&ScanFilter::new(&[], &[], false).unwrap());
+        assert_eq!(vec![PathBuf::from(".hidden.jpg"), PathBuf::from("2024/01/c.jpg"), PathBuf::from("2024/b.jpg"), PathBuf::from("a.jpg")], paths);
+        assert_eq!(BTreeMap::from([(SkipReason::Symlink, 3), (SkipReason::Special, 1)]), counts);
+
+        // assert that followed symlinks resolve to their target, loops and broken links are skipped
+        let mut filter = ScanFilter::new(&[], &[], false).unwrap();
+        filter.set_follow_symlinks(true);
+        filter.set_skip_hidden(true);
+        filter.set_max_depth(Some(1));
+        let (paths, files, counts) = scan(&filter);
+        assert_eq!(vec![PathBuf::from("2024/b.jpg"), PathBuf::from("a.jpg"), PathBuf::from("other/d.jpg")], paths);
+        let linked = files.iter().find(|file| file.get_local_path().ends_with("other/d.jpg")).unwrap();
+        assert_eq!(fs::canonicalize(temp_dir.path().join("other/d.jpg")).unwrap(), linked.get_resolved_path());
+        let expected = BTreeMap::from([(SkipReason::Hidden, 2), (SkipReason::BrokenSymlink, 1), (SkipReason::TooDeep, 2), (SkipReason::Special, 1)]);
+        assert_eq!(expected, counts);
+
+        // assert that a symlink to a parent folder is not scanned again
+        filter.set_max_depth(None);
+        let (paths, _, counts) = scan(&filter);
+        assert_eq!(4, paths.len());
+        assert_eq!(Some(&1), counts.get(&SkipReason::SymlinkLoop));
+    }
+}
@@ -0,0 +1,329 @@
+use chrono::{DateTime, Datelike, FixedOffset};
+use chrono::format::{Item, StrftimeItems};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// value of a placeholder if no fallback was given and the file has no value for it, e.g. a photo without camera model
+const UNKNOWN: &str = "Unknown";
+
+// folder below the upload root for files whose date is unknown if the user does not configure it
+pub const DEFAULT_UNKNOWN_DATE_FOLDER: &str = "unknown-date";
+
+// characters which are not allowed in folder names and are replaced in the values of placeholders
+const FORBIDDEN_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// a placeholder inside a layout which is replaced by a property of the file
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Year,
+    Month,
+    Day,
+    MonthName,
+    MonthShort,
+    Weekday,
+    Hour,
+    // week and year of the week according to ISO 8601
+    Week,
+    WeekYear,
+    Quarter,
+    // any strftime format, e.g. {date:%Y-%m}
+    Date(String),
+    // class of the file like image or video determined by its extension
+    Type,
+    Ext,
+    // any exif tag, e.g. {exif:LensModel}
+    Exif(String)
+}
+
+impl FromStr for Token {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(format) = s.strip_prefix("date:") {
+            // reject formats chrono can not render, so an invalid layout fails before the upload starts
+            if format.is_empty() || StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid date format '{}' in layout", format)))
+            }
+            return Ok(Token::Date(format.to_string()))
+        }
+        if let Some(tag) = s.strip_prefix("exif:") {
+            if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid exif tag '{}' in layout", tag)))
+            }
+            return Ok(Token::Exif(tag.to_string()))
+        }
+
+        match s {
+            "year" => Ok(Token::Year),
+            "month" => Ok(Token::Month),
+            "day" => Ok(Token::Day),
+            "month_name" => Ok(Token::MonthName),
+            "month_short" => Ok(Token::MonthShort),
+            "weekday" => Ok(Token::Weekday),
+            "hour" => Ok(Token::Hour),
+            "week" => Ok(Token::Week),
+            "week_year" => Ok(Token::WeekYear),
+            "quarter" => Ok(Token::Quarter),
+            "type" => Ok(Token::Type),
+            "ext" => Ok(Token::Ext),
+            "camera_make" => Ok(Token::Exif("Make".to_string())),
+            "camera_model" => Ok(Token::Exif("Model".to_string())),
+            "lens" => Ok(Token::Exif("LensModel".to_string())),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown placeholder '{{{}}}' in layout", s)))
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+    Text(String),
+    Placeholder { token: Token, fallback: Option<String> }
+}
+
+// template of the remote folder structure of sorted uploads, e.g. "{year}/{year}-{month} {month_name}/{camera_model}".
+// Each '/' starts a new folder, placeholders are written in braces and may define a fallback like {camera_model|Other}
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layout {
+    parts: Vec<Part>,
+    // folder of files whose date is unknown, which can not be placed by the layout
+    unknown_date_folder: PathBuf
+}
+
+impl FromStr for Layout {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<Part> = Vec::new();
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                parts.push(Part::Text(rest.to_string()));
+                break
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Missing '}}' in layout '{}'", s)))?;
+
+            // split the optional fallback from the placeholder
+            let content = &rest[start + 1..end];
+            let (name, fallback) = match content.split_once('|') {
+                Some((name, fallback)) => (name, Some(sanitize(fallback))),
+                None => (content, None)
+            };
+            parts.push(Part::Placeholder { token: name.trim().parse()?, fallback });
+            rest = &rest[end + 1..];
+        }
+
+        if parts.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "The layout must not be empty"))
+        }
+        // '.' and '..' would place files outside of the upload root
+        if s.split('/').any(is_relative_folder) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The layout '{}' must not contain the folders '.' or '..'", s)))
+        }
+        Ok(Layout { parts, unknown_date_folder: PathBuf::from(DEFAULT_UNKNOWN_DATE_FOLDER) })
+    }
+}
+
+impl Layout {
+    // returns the layout of the '--depth' option, which was the only way to configure the folder structure before layouts existed
+    pub fn from_depth(depth: &str) -> Result<Layout, io::Error> {
+        match depth.trim().to_lowercase().as_str() {
+            "year" => "{year}".parse(),
+            "month" => "{year}/{month}".parse(),
+            "day" => "{year}/{month}/{day}".parse(),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown depth '{}'", depth)))
+        }
+    }
+
+    pub fn get_unknown_date_folder(&self) -> &Path {
+        &self.unknown_date_folder
+    }
+
+    // sets the folder of files whose date is unknown, e.g. "Unsorted". A '/' creates nested folders
+    pub fn set_unknown_date_folder(&mut self, folder: &str) -> Result<(), io::Error> {
+        let folder: PathBuf = folder.split('/')
+            .map(sanitize)
+            .filter(|folder| !folder.is_empty())
+            .collect();
+        if folder.as_os_str().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "The folder for files with an unknown date must not be empty"))
+        }
+        self.unknown_date_folder = folder;
+        Ok(())
+    }
+
+    // returns the exif tags which are used by the layout and have to be extracted for every file
+    pub fn get_exif_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for part in &self.parts {
+            if let Part::Placeholder { token: Token::Exif(tag), .. } = part {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+        tags
+    }
+
+    // returns the folder of a file relative to the upload root. 'date' is rendered in its own time zone and 'exif'
+    // contains the values of the tags returned by get_exif_tags
+    pub fn render(&self, path: &Path, date: &DateTime<FixedOffset>, exif: &HashMap<String, String>) -> Result<PathBuf, io::Error> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Placeholder { token, fallback } => {
+                    let value = render_token(token, path, date, exif);
+                    if value.is_empty() {
+                        rendered.push_str(fallback.as_deref().unwrap_or(UNKNOWN));
+                    } else {
+                        rendered.push_str(&value);
+                    }
+                }
+            }
+        }
+
+        // every '/' of the rendered layout starts a new folder, empty folder names are dropped
+        let folders: Vec<&str> = rendered.split('/')
+            .map(|folder| folder.trim())
+            .filter(|folder| !folder.is_empty())
+            .collect();
+        if folders.iter().any(|folder| is_relative_folder(folder)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The layout rendered the invalid folder '{}' for {:?}", rendered, path)))
+        }
+        Ok(folders.into_iter().collect())
+    }
+}
+
+fn render_token(token: &Token, path: &Path, date: &DateTime<FixedOffset>, exif: &HashMap<String, String>) -> String {
+    match token {
+        Token::Year => date.year().to_string(),
+        Token::Month => format!("{:02}", date.month()),
+        Token::Day => format!("{:02}", date.day()),
+        Token::MonthName => date.format("%B").to_string(),
+        Token::MonthShort => date.format("%b").to_string(),
+        Token::Weekday => date.format("%A").to_string(),
+        Token::Hour => date.format("%H").to_string(),
+        Token::Week => format!("{:02}", date.iso_week().week()),
+        Token::WeekYear => date.iso_week().year().to_string(),
+        Token::Quarter => ((date.month() - 1) / 3 + 1).to_string(),
+        // a date format may intentionally contain '/' to create multiple folders, so each folder is sanitized on its own
+        Token::Date(format) => date.format(format).to_string().split('/').map(sanitize).collect::<Vec<String>>().join("/"),
+        Token::Type => get_type_class(path).to_string(),
+        Token::Ext => sanitize(&path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase()),
+        Token::Exif(tag) => sanitize(exif.get(tag).map(|value| value.as_str()).unwrap_or_default())
+    }
+}
+
+// replaces characters which are not allowed in folder names
+fn sanitize(value: &str) -> String {
+    value.trim().replace(FORBIDDEN_CHARS, "-").trim_end_matches('.').to_string()
+}
+
+// returns true for the folder names '.' and '..', which do not create a folder but refer to an existing one
+fn is_relative_folder(folder: &str) -> bool {
+    matches!(folder.trim(), "." | "..")
+}
+
+// returns the class of a file based on its extension
+pub fn get_type_class(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif" | "avif" |
+        "dng" | "cr2" | "cr3" | "nef" | "arw" | "orf" | "rw2" | "raf" | "srw" | "pef" => "image",
+        "mp4" | "mov" | "m4v" | "avi" | "mkv" | "mts" | "m2ts" | "3gp" | "wmv" | "webm" | "mpg" | "mpeg" => "video",
+        "mp3" | "m4a" | "wav" | "flac" | "aac" | "ogg" | "opus" | "wma" => "audio",
+        "pdf" | "doc" | "docx" | "odt" | "txt" | "md" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp" => "document",
+        _ => "other"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    // 2024-03-15 12:00:00 UTC, which is the 15th of march in every time zone
+    const MTIME: i64 = 1710504000;
+
+    // returns the date of a timestamp at the given offset to UTC in hours
+    fn date(timestamp: i64, offset: i32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(offset * 3600).unwrap().timestamp_opt(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_render_layout() {
+        let layout: Layout = "{year}/{year}-{month} {month_name}/{camera_model}".parse().unwrap();
+        let exif = HashMap::from([("Model".to_string(), "Canon EOS R5".to_string())]);
+        assert_eq!(vec!["Model".to_string()], layout.get_exif_tags());
+
+        // assert that the placeholders are replaced and each '/' creates a folder
+        let path = layout.render(Path::new("/photos/a.JPG"), &date(MTIME, 0), &exif).unwrap();
+        assert_eq!(PathBuf::from("2024/2024-03 March/Canon EOS R5"), path);
+
+        // assert that missing exif values are replaced by the fallback
+        let layout: Layout = "{type}/{week_year}-W{week}/Q{quarter}/{lens|No Lens}/{ext}".parse().unwrap();
+        let path = layout.render(Path::new("/photos/a.JPG"), &date(MTIME, 0), &HashMap::new()).unwrap();
+        assert_eq!(PathBuf::from("image/2024-W11/Q1/No Lens/jpg"), path);
+
+        // assert that a date format can create multiple folders and that forbidden characters are replaced
+        let layout: Layout = "{date:%Y/%m}/{exif:Make}".parse().unwrap();
+        let exif = HashMap::from([("Make".to_string(), "A/B".to_string())]);
+        let path = layout.render(Path::new("/videos/b.mov"), &date(MTIME, 0), &exif).unwrap();
+        assert_eq!(PathBuf::from("2024/03/A-B"), path);
+    }
+
+    #[test]
+    fn test_parse_invalid_layout() {
+        // assert that unknown placeholders, unclosed braces and invalid date formats are rejected
+        assert!("{year}/{camera}".parse::<Layout>().is_err());
+        assert!("{year/{month}".parse::<Layout>().is_err());
+        assert!("{date:%Q}".parse::<Layout>().is_err());
+        assert!("".parse::<Layout>().is_err());
+
+        // assert that the layout can not leave the upload root
+        assert!("../{year}".parse::<Layout>().is_err());
+        assert!("{year}/ . /{month}".parse::<Layout>().is_err());
+        assert!("{year}/..{month}".parse::<Layout>().is_ok());
+    }
+
+    #[test]
+    fn test_render_sanitizes_date_format() {
+        // assert that each folder of a date format is sanitized, so it can neither contain forbidden characters nor leave the upload root
+        let layout: Layout = "{date:%Y/%H:%M/..}".parse().unwrap();
+        let path = layout.render(Path::new("a.jpg"), &date(MTIME, 0), &HashMap::new()).unwrap();
+        assert_eq!(PathBuf::from("2024/12-00"), path);
+    }
+
+    #[test]
+    fn test_layout_from_depth() {
+        // assert that the depth option keeps creating the same folder structure
+        let path = Layout::from_depth("day").unwrap().render(Path::new("a.jpg"), &date(MTIME, 0), &HashMap::new()).unwrap();
+        assert_eq!(PathBuf::from("2024/03/15"), path);
+        assert!(Layout::from_depth("week").is_err());
+    }
+
+    #[test]
+    fn test_render_midnight_boundary() {
+        // 2024-03-15 23:30:00 UTC is already the 16th in Berlin and still the 15th in New York
+        let layout = Layout::from_depth("day").unwrap();
+        let timestamp = 1710545400;
+        assert_eq!(PathBuf::from("2024/03/15"), layout.render(Path::new("a.jpg"), &date(timestamp, 0), &HashMap::new()).unwrap());
+        assert_eq!(PathBuf::from("2024/03/16"), layout.render(Path::new("a.jpg"), &date(timestamp, 1), &HashMap::new()).unwrap());
+        assert_eq!(PathBuf::from("2024/03/15"), layout.render(Path::new("a.jpg"), &date(timestamp, -5), &HashMap::new()).unwrap());
+
+        // assert that the last day of a year is kept when the photo was taken shortly before midnight
+        let layout: Layout = "{year}/{month}/{day}".parse().unwrap();
+        // 2023-12-31 23:30:00 UTC
+        let new_year = 1704065400;
+        assert_eq!(PathBuf::from("2023/12/31"), layout.render(Path::new("a.jpg"), &date(new_year, 0), &HashMap::new()).unwrap());
+        assert_eq!(PathBuf::from("2024/01/01"), layout.render(Path::new("a.jpg"), &date(new_year, 9), &HashMap::new()).unwrap());
+    }
+}
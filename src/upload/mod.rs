@@ -7,4 +7,25 @@ pub mod sorted;
 // unsorted upload moudule
 pub mod unsorted;
 
-pub mod common;
\ No newline at end of file
+pub mod common;
+
+// detection of files which already exist on nextcloud
+pub mod conflict;
+
+// detection of local files which would be uploaded to the same remote path
+pub mod collision;
+
+// template of the remote folder structure of sorted uploads
+pub mod layout;
+
+// selection of the local files which are uploaded
+pub mod filter;
+
+// plan of an upload which is printed by a dry run
+pub mod plan;
+
+// upload of a plan file written by a dry run
+pub mod apply;
+
+// record of the files which could not be uploaded
+pub mod failed;
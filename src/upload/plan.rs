@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use colored::*;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::{self, ChecksumAlgorithm};
+use crate::filesystem::File;
+use crate::output::{self, Event};
+
+// version of the plan file format, which is increased whenever a change breaks reading older plans
+const PLAN_VERSION: u32 = 1;
+
+// format in which a dry run prints the upload plan
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlanFormat {
+    Table,
+    Json
+}
+
+impl FromStr for PlanFormat {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(PlanFormat::Table),
+            "json" => Ok(PlanFormat::Json),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown plan format '{}'", s)))
+        }
+    }
+}
+
+// what the upload does with a local file
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanAction {
+    // uploaded to a path which is free on nextcloud
+    Upload,
+    // replaces a different file with the same name on nextcloud
+    Overwrite,
+    // uploaded under a new name, because the name is taken on nextcloud or by another file of the upload
+    Rename,
+    // not uploaded, because the file already exists on nextcloud
+    SkipExisting,
+    // not uploaded, because another file of the upload has the same name
+    SkipCollision
+}
+
+impl fmt::Display for PlanAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PlanAction::Upload => "upload",
+            PlanAction::Overwrite => "overwrite",
+            PlanAction::Rename => "rename",
+            PlanAction::SkipExisting => "skip (exists)",
+            PlanAction::SkipCollision => "skip (collision)"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PlanAction {
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, PlanAction::SkipExisting | PlanAction::SkipCollision)
+    }
+}
+
+// a local file of the plan. 'remote_parent', 'remote_name' and 'action' can be edited before the plan is applied
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlanEntry {
+    local_path: PathBuf,
+    remote_parent: PathBuf,
+    remote_name: String,
+    action: PlanAction,
+    size: u64,
+    // modification date which is sent to nextcloud
+    mtime: i64,
+    // hash of the content which detects files that changed after planning, only written to plan files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    // date the file is sorted by in RFC 3339 format, None if no date source knew it
+    date: Option<String>,
+    date_source: Option<String>
+}
+
+impl PlanEntry {
+    fn new(file: &File, action: PlanAction) -> PlanEntry {
+        PlanEntry {
+            local_path: file.get_local_path().to_path_buf(),
+            remote_parent: file.get_remote_parent().to_path_buf(),
+            remote_name: file.get_remote_name().unwrap_or_default(),
+            action,
+            size: file.get_size(),
+            mtime: file.get_mtime(),
+            sha256: None,
+            date: file.get_date().filter(|_| !file.has_unknown_date()).map(|date| date.to_rfc3339()),
+            date_source: file.get_date_source().map(|source| source.to_string())
+        }
+    }
+
+    pub fn get_action(&self) -> PlanAction {
+        self.action
+    }
+
+    pub fn get_remote_path(&self) -> PathBuf {
+        self.remote_parent.join(&self.remote_name)
+    }
+
+    // creates the file which is uploaded. Returns an error if the local file changed after planning
+    pub fn to_file(&self) -> Result<File, Box<dyn Error>> {
+        if !self.local_path.is_file() {
+            return Err(Box::new(io::Error::new(io::ErrorKind::NotFound, format!("{:?} does not exist anymore", self.local_path))))
+        }
+        let mut file = File::new(&self.local_path, self.mtime);
+        if file.get_size() != self.size {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("The size of {:?} changed after planning", self.local_path))))
+        }
+        if let Some(sha256) = &self.sha256 {
+            if !checksum::hash_file(&self.local_path, ChecksumAlgorithm::Sha256)?.eq_ignore_ascii_case(sha256) {
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("The content of {:?} changed after planning", self.local_path))))
+            }
+        }
+        file.set_remote_parent(self.remote_parent.clone());
+        file.set_remote_name(self.remote_name.clone());
+        if self.action == PlanAction::Overwrite {
+            file.set_replaces_existing();
+        }
+        Ok(file)
+    }
+}
+
+// everything an upload would do: the folders it creates on nextcloud and what happens with each local file.
+// A plan is printed by a dry run or written to a file which is uploaded later by the apply command
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadPlan {
+    version: u32,
+    remote_root: PathBuf,
+    // parents are listed before their sub folders
+    new_folders: Vec<PathBuf>,
+    files: Vec<PlanEntry>
+}
+
+impl UploadPlan {
+    // creates the plan from the files which are uploaded and the files which were skipped by resolve_collisions and resolve_conflicts
+    pub fn new(remote_root: &Path, new_folders: Vec<PathBuf>, files: &[File], skipped_collisions: &[File], skipped_existing: &[File]) -> UploadPlan {
+        let mut entries: Vec<PlanEntry> = Vec::with_capacity(files.len() + skipped_collisions.len() + skipped_existing.len());
+        for file in files {
+            let local_name = file.get_local_path().file_name().map(|name| name.to_string_lossy().to_string());
+            let action = if file.replaces_existing() {
+                PlanAction::Overwrite
+            } else if file.get_remote_name() != local_name {
+                PlanAction::Rename
+            } else {
+                PlanAction::Upload
+            };
+            entries.push(PlanEntry::new(file, action));
+        }
+        entries.extend(skipped_collisions.iter().map(|file| PlanEntry::new(file, PlanAction::SkipCollision)));
+        entries.extend(skipped_existing.iter().map(|file| PlanEntry::new(file, PlanAction::SkipExisting)));
+        entries.sort_by(|a, b| a.local_path.cmp(&b.local_path));
+
+        UploadPlan {
+            version: PLAN_VERSION,
+            remote_root: remote_root.to_path_buf(),
+            new_folders,
+            files: entries
+        }
+    }
+
+    // reads a plan file and checks that every file is uploaded below the root folder to a path no other file is uploaded to
+    pub fn load(path: &Path) -> Result<UploadPlan, Box<dyn Error>> {
+        let plan: UploadPlan = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not a valid plan: {}", path, e)))?;
+        if plan.version > PLAN_VERSION {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("The plan {:?} was created by a newer version of nextsyncengine", path))))
+        }
+
+        let mut remote_paths: HashSet<PathBuf> = HashSet::new();
+        for entry in plan.files.iter().filter(|entry| !entry.action.is_skipped()) {
+            let remote_path = entry.get_remote_path();
+            // '.' and '..' would let an edited plan upload to a folder outside of the root folder
+            let is_relative = |name: &str| name == "." || name == "..";
+            let leaves_root = entry.remote_parent.to_string_lossy().split('/').any(is_relative) || is_relative(&entry.remote_name);
+            if leaves_root || !entry.remote_parent.starts_with(&plan.remote_root) || entry.remote_name.is_empty() || entry.remote_name.contains('/') {
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not a valid path below {:?}", remote_path, plan.remote_root))))
+            }
+            if !remote_paths.insert(remote_path.clone()) {
+                return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("Several files of the plan are uploaded to {:?}", remote_path))))
+            }
+        }
+        Ok(plan)
+    }
+
+    // writes the plan to a file. The content of every uploaded file is hashed, so apply can detect files that changed meanwhile
+    pub fn save(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        for entry in self.files.iter_mut().filter(|entry| !entry.action.is_skipped()) {
+            entry.sha256 = Some(checksum::hash_file(&entry.local_path, ChecksumAlgorithm::Sha256)?);
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get_remote_root(&self) -> &Path {
+        &self.remote_root
+    }
+
+    pub fn get_entries(&self) -> &[PlanEntry] {
+        &self.files
+    }
+
+    // returns the files which are uploaded. Files which changed after planning are reported and left out
+    pub fn get_files(&self) -> Vec<File> {
+        let mut files: Vec<File> = Vec::new();
+        for entry in self.files.iter().filter(|entry| !entry.action.is_skipped()) {
+            match entry.to_file() {
+                Ok(file) => files.push(file),
+                Err(e) => warn!("Skipping {:?}: {}", entry.local_path, e)
+            }
+        }
+        files
+    }
+
+    // emits a file_planned event of the JSON output for every file of the plan
+    pub fn emit_events(&self) {
+        for entry in self.files.iter() {
+            output::emit(&Event::FilePlanned {
+                local_path: &entry.local_path,
+                remote_path: entry.get_remote_path(),
+                action: entry.action,
+                size: entry.size,
+                date: entry.date.as_deref(),
+                date_source: entry.date_source.as_deref()
+            });
+        }
+    }
+
+    // prints the plan to stdout
+    pub fn print(&self, format: PlanFormat) -> Result<(), Box<dyn Error>> {
+        match format {
+            PlanFormat::Json => output::println(serde_json::to_string_pretty(self)?),
+            PlanFormat::Table => self.print_table()
+        }
+        Ok(())
+    }
+
+    fn print_table(&self) {
+        if !self.new_folders.is_empty() {
+            output::println("Folders which would be created on Nextcloud:".green());
+            for folder in self.new_folders.iter() {
+                output::println(format!("  {}", folder.display()));
+            }
+        }
+
+        output::println(format!("{:<16} {:<25} {:<9} Local path -> Remote path", "Action", "Date", "Source"));
+        for entry in self.files.iter() {
+            let line = format!("{:<16} {:<25} {:<9} {} -> {}", entry.action.to_string(), entry.date.as_deref().unwrap_or("unknown"),
+                entry.date_source.as_deref().unwrap_or("-"), entry.local_path.display(), entry.get_remote_path().display());
+            match entry.action {
+                PlanAction::Upload => output::println(line),
+                PlanAction::Overwrite | PlanAction::Rename => output::println(line.yellow()),
+                PlanAction::SkipExisting | PlanAction::SkipCollision => output::println(line.dimmed())
+            }
+        }
+
+        let uploaded: Vec<&PlanEntry> = self.files.iter().filter(|entry| !entry.action.is_skipped()).collect();
+        let total_size: u64 = uploaded.iter().map(|entry| entry.size).sum();
+        output::println(format!("Dry run: {} file(s) with {:.2}M would be uploaded to {:?}, {} file(s) skipped and {} folder(s) created. Nothing was changed on Nextcloud.",
+            uploaded.len(), total_size as f64 / 1_000_000.0, self.remote_root, self.files.len() - uploaded.len(), self.new_folders.len()).green());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date_source::DateSource;
+    use chrono::FixedOffset;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn create_file(dir: &Path, name: &str) -> File {
+        let local_path = dir.join(name);
+        fs::write(&local_path, "abc").unwrap();
+        let mut file = File::new(&local_path, 1_700_000_000);
+        file.set_remote_parent(PathBuf::from("/Photos/2023/11"));
+        file
+    }
+
+    #[test]
+    fn test_upload_plan() {
+        let temp_dir = tempdir().unwrap();
+        let mut upload = create_file(temp_dir.path(), "a.jpg");
+        upload.set_date_source(DateSource::Exif);
+        upload.set_utc_offset(FixedOffset::east_opt(3600).unwrap());
+        let mut overwrite = create_file(temp_dir.path(), "b.jpg");
+        overwrite.set_replaces_existing();
+        let mut rename = create_file(temp_dir.path(), "c.jpg");
+        rename.set_remote_name("c (1).jpg".to_string());
+        let mut unknown = create_file(temp_dir.path(), "d.jpg");
+        unknown.set_unknown_date();
+        let existing = create_file(temp_dir.path(), "e.jpg");
+
+        // assert that the action of each file is derived from the resolved collisions and conflicts
+        let plan = UploadPlan::new(Path::new("/Photos"), vec![PathBuf::from("/Photos/2023"), PathBuf::from("/Photos/2023/11")],
+            &[upload, overwrite, rename, unknown], &[], &[existing]);
+        let actions: Vec<PlanAction> = plan.get_entries().iter().map(|entry| entry.get_action()).collect();
+        assert_eq!(vec![PlanAction::Upload, PlanAction::Overwrite, PlanAction::Rename, PlanAction::Upload, PlanAction::SkipExisting], actions);
+
+        // assert the stable field names of the json output
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!("/Photos/2023/11", json["new_folders"][1]);
+        assert_eq!("/Photos/2023/11", json["files"][0]["remote_parent"]);
+        assert_eq!(1_700_000_000, json["files"][0]["mtime"]);
+        assert_eq!("2023-11-14T23:13:20+01:00", json["files"][0]["date"]);
+        assert_eq!("exif", json["files"][0]["date_source"]);
+        assert_eq!("c (1).jpg", json["files"][2]["remote_name"]);
+        assert!(json["files"][3]["date"].is_null());
+        assert_eq!("skip_existing", json["files"][4]["action"]);
+
+        assert_eq!(PlanFormat::Json, "JSON".parse::<PlanFormat>().unwrap());
+        assert!("csv".parse::<PlanFormat>().is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_plan() {
+        let temp_dir = tempdir().unwrap();
+        let plan_path = temp_dir.path().join("plan.json");
+        let files = [create_file(temp_dir.path(), "a.jpg"), create_file(temp_dir.path(), "b.jpg")];
+        let existing = create_file(temp_dir.path(), "c.jpg");
+        let mut plan = UploadPlan::new(Path::new("/Photos"), vec![], &files, &[], &[existing]);
+        plan.save(&plan_path).unwrap();
+
+        // assert that an edited remote name is used and a file which changed after planning is left out
+        let edited = fs::read_to_string(&plan_path).unwrap().replace("\"remote_name\": \"a.jpg\"", "\"remote_name\": \"holiday.jpg\"");
+        fs::write(&plan_path, edited).unwrap();
+        fs::write(temp_dir.path().join("b.jpg"), "xyz").unwrap();
+        let plan = UploadPlan::load(&plan_path).unwrap();
+        assert_eq!(Path::new("/Photos"), plan.get_remote_root());
+        let files = plan.get_files();
+        assert_eq!(1, files.len());
+        assert_eq!(Some("holiday.jpg".to_string()), files[0].get_remote_name());
+        assert_eq!(Path::new("/Photos/2023/11"), files[0].get_remote_parent());
+        assert_eq!(1_700_000_000, files[0].get_mtime());
+
+        // assert that two files with the same remote path and paths outside the root folder are rejected
+        let content = fs::read_to_string(&plan_path).unwrap();
+        fs::write(&plan_path, content.replace("holiday.jpg", "b.jpg")).unwrap();
+        assert!(UploadPlan::load(&plan_path).is_err());
+        fs::write(&plan_path, content.replace("/Photos/2023/11", "/Other")).unwrap();
+        assert!(UploadPlan::load(&plan_path).is_err());
+        fs::write(&plan_path, content.replace("/Photos/2023/11", "/Photos/../Other")).unwrap();
+        assert!(UploadPlan::load(&plan_path).is_err());
+        fs::write(&plan_path, content.replace("/Photos/2023/11", "/Photos/./2023")).unwrap();
+        assert!(UploadPlan::load(&plan_path).is_err());
+        fs::write(&plan_path, content.replace("holiday.jpg", "..")).unwrap();
+        assert!(UploadPlan::load(&plan_path).is_err());
+    }
+}
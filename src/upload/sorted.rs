@@ -1,124 +1,125 @@
-use chrono::{Datelike, Local, TimeZone};
-use chrono::offset::LocalResult;
-
-use std::path::{Path, PathBuf};
 use std::io;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 use colored::*;
 
 use crate::nextcloud::NextcloudClient;
 use crate::filesystem::{File, Folder};
 use crate::media::Extractor;
-use crate::upload::common;
+use crate::upload::common::{self, UploadOptions};
+use crate::upload::conflict::RemoteIndex;
+use crate::upload::layout::Layout;
+use crate::output;
+
+// assigns each file a remote parent based on the layout and returns the folders which have to be created on nextcloud
+fn get_remote_parent(files: &mut Vec<File>, mut root: Folder, layout: &Layout, extractor: &Extractor) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let exif_tags = layout.get_exif_tags();
+    let mut new_folders: Vec<PathBuf> = Vec::new();
 
-// assigns each file a remote parent based on the mtime of the file
-fn get_remote_parent(files: &mut Vec<File>, mut root: Folder, client: &NextcloudClient, depth: &str) -> Result<(), Box<dyn Error>> {
     // goes through the list of files and determines the remote parent of the file
     for file in files {
-        if let LocalResult::Single(mtime) = Local.timestamp_opt(file.get_mtime(), 0) {
-            let day: String;
-            if mtime.day() < 10 {
-                day = format!("0{}", mtime.day());
-            } else  {
-                day = format!("{}", mtime.day());
-            }
-    
-            let month: String;
-            if mtime.month() < 10 {
-                month = format!("0{}", mtime.month());
-    
-            } else {
-                month = format!("{}", mtime.month());
-            }
-            let year = format!("{}", mtime.year());
-            let root_str = root.get_name();
-            let root_path = Path::new(root_str.as_str());
-    
-            let year_path = root_path.join(Path::new(year.as_str()));
-            
-            if !root.has_subfolder(&year_path) {
-                client.create_folder(&year_path)?;
-                root.add_sub_folder(Folder::new(year), root_path);
-            }
-            if depth == "year" {
-                file.set_remote_parent(year_path);
-                continue
-            }
-    
-            let month_path = year_path.join(Path::new(month.as_str()));
-    
-            if !root.has_subfolder(&month_path) {
-                client.create_folder(&month_path)?;
-                root.add_sub_folder(Folder::new(month), &year_path);
-            }
-            if depth == "month" {
-                file.set_remote_parent(month_path);
-                continue
-            }
-    
-            let day_path = month_path.join(Path::new(day.as_str()));
-            if !root.has_subfolder(&day_path) {
-                client.create_folder(&day_path)?;
-                root.add_sub_folder(Folder::new(day), &month_path);
-            }
-            file.set_remote_parent(day_path);
-
-        } else {
-            return Err(Box::new(io::Error::new(io::ErrorKind::Other, "Failed to parse unix timestamp into a DateTime object!")))
+        // a file without date can not be sorted and is collected in a separate folder
+        if file.has_unknown_date() {
+            file.set_remote_parent(common::plan_remote_path(&mut root, layout.get_unknown_date_folder(), &mut new_folders));
+            continue
         }
-    }
-    Ok(())
-}
-
-// travels through the remote folder and recursively adds all folders as sub folders to 'root'
-fn travel_dir_dav(root: &mut Folder, client: &NextcloudClient) -> Result<(), Box<dyn Error>> {
-    let mut paths_folder: Vec<PathBuf> = Vec::new();
-    paths_folder.push(Path::new(root.get_name().as_str()).to_path_buf());
 
-    //lists the sub folders in a folder and adds them to 'root' as sub folders and pushes them into 'paths_folder'
-    while let Some(current_folder) = paths_folder.pop() {
-        let sub_folders = client.ls(&current_folder)?;
-        for sub_folder in sub_folders {
-            if let Some(name) = sub_folder.to_str() {
-                root.add_sub_folder(Folder::new(name.to_string()), &current_folder);
-                paths_folder.push(current_folder.join(sub_folder));
-            }
-        }
+        // a file without the exif tags of the layout is placed in the folder of the fallback value
+        let exif = extractor.extract_tags(file.get_local_path(), &exif_tags)?;
+        // the day of a photo is the day at the place it was taken, not at the place it is uploaded
+        let date = file.get_date().ok_or_else(|| io::Error::other("Failed to parse unix timestamp into a DateTime object!"))?;
+        let relative_parent = layout.render(file.get_local_path(), &date, &exif)?;
+        file.set_remote_parent(common::plan_remote_path(&mut root, &relative_parent, &mut new_folders));
     }
-    Ok(())
+    Ok(new_folders)
 }
 
-pub fn upload_sorted(path_upload: String, from_folder: bool, remote_path: String, depth: String, num_threads: usize, client: NextcloudClient, extractor: Extractor) -> Result<(), Box<dyn Error>> {
-    // check if the root folder exists and if not ask the user if he wants to create it
-    match common::exists_root_folder(Path::new(&remote_path), &client) {
-        Ok(true) => {}
-        Ok(false) => return Ok(()),
-        Err(e) => return Err(e)
-    }
+pub fn upload_sorted(path_upload: String, from_folder: bool, remote_path: String, layout: Layout, client: NextcloudClient, extractor: Extractor, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    // check if the root folder exists and if not create it depending on CREATE_ROOT or the answer of the user
+    let root_exists = match common::check_root_folder(Path::new(&remote_path), &client, options)? {
+        Some(root_exists) => root_exists,
+        None => return Ok(0)
+    };
 
-    // create the cached version of the nextcloud folder structure
-    print!("{}", "Scanning remote folder structure ... ".green());
+    // create the cached version of the nextcloud folder structure. The root folder is only missing in a dry run
     let mut root = Folder::new(remote_path.to_owned());
-    if let Err(e)= travel_dir_dav(&mut root, &client) {
-        return Err(e)
+    let mut index = RemoteIndex::new();
+    let mut new_folders: Vec<PathBuf> = Vec::new();
+    if root_exists {
+        output::print("Scanning remote folder structure ... ".green());
+        common::travel_dir_dav(&mut root, &client, &mut index)?;
+        output::println("done".green());
+    } else {
+        new_folders.push(PathBuf::from(&remote_path));
     }
-    println!("{}", "done".green());
 
-    print!("{}", "Scanning local folder for files ... ".green());
-    // creating the missing folders on nextcloud and uploading the files in 4 threads to nextcloud
-    match common::get_files_for_upload(Path::new(&path_upload), from_folder, &extractor) {
-        Ok(mut files) => {
-            println!("{}", "done".green());
-            
-            print!("{}", "Creating folder structure on Nextcloud ... ".green());
-            get_remote_parent(&mut files, root, &client, &depth)?;
-            println!("{}", "done".green());
+    output::print("Scanning local folder for files ... ".green());
+    // planning the remote folders of the files and uploading the files in parallel threads to nextcloud
+    match common::get_files_for_upload(Path::new(&path_upload), from_folder, Some(&extractor), &options.scan_filter) {
+        Ok((mut files, skipped)) => {
+            output::println("done".green());
+            skipped.print();
+            common::print_date_sources(&files);
+
+            new_folders.extend(get_remote_parent(&mut files, root, &layout, &extractor)?);
 
-            common::start_upload(files, client, num_threads)
+            let num_unknown = files.iter().filter(|file| file.has_unknown_date()).count();
+            if num_unknown > 0 {
+                let folder = Path::new(&remote_path).join(layout.get_unknown_date_folder());
+                output::println(format!("Uploading {} file(s) with an unknown date to {:?}.", num_unknown, folder).yellow());
+            }
+
+            common::upload_planned(files, new_folders, Path::new(&remote_path), index, client, options)
         }
         
         // passing error to caller function
-        Err(e) => return Err(e)
+        Err(e) => Err(e)
+
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date_source::DateSource;
+    use crate::upload::filter::ScanFilter;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_get_remote_parent_unknown_date() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("IMG-20230514-WA0003.jpg"), "abc").unwrap();
+        fs::write(temp_dir.path().join("IMG_0001.jpg"), "abc").unwrap();
+
+        // assert that a file no date source knows is kept and marked instead of aborting the scan
+        let mut extractor = Extractor::new(None);
+        extractor.set_date_sources(vec![DateSource::Filename]);
+        extractor.set_timezone("UTC".parse().unwrap());
+        let (mut files, _) = common::get_files_for_upload(temp_dir.path(), true, Some(&extractor), &ScanFilter::default()).unwrap();
+        files.sort_by(|a, b| a.get_local_path().cmp(b.get_local_path()));
+        assert_eq!(vec![false, true], files.iter().map(|file| file.has_unknown_date()).collect::<Vec<bool>>());
 
+        // assert that the file with an unknown date is uploaded to the configured folder and nothing is created while planning
+        let mut layout = Layout::from_depth("month").unwrap();
+        layout.set_unknown_date_folder("Unsorted").unwrap();
+        let new_folders = get_remote_parent(&mut files, Folder::new("/Photos".to_string()), &layout, &extractor).unwrap();
+        let remote_parents: Vec<&Path> = files.iter().map(|file| file.get_remote_parent()).collect();
+        assert_eq!(vec![Path::new("/Photos/2023/05"), Path::new("/Photos/Unsorted")], remote_parents);
+        assert_eq!(vec![PathBuf::from("/Photos/2023"), PathBuf::from("/Photos/2023/05"), PathBuf::from("/Photos/Unsorted")], new_folders);
+
+        // assert that the planned folders are created parents first
+        let mut mock = mockito::Server::new();
+        let server_url = mock.url();
+        let mkcol_mocks = ["2023", "2023/05", "Unsorted"].map(|folder| mock
+            .mock("MKCOL", format!("/remote.php/dav/files/testuser/Photos/{}", folder).as_str())
+            .with_status(201)
+            .expect(1)
+            .create());
+        let client = NextcloudClient::new(server_url, "testuser".to_string(), "password".to_string());
+        common::create_folders(&new_folders, &client).unwrap();
+        for mkcol_mock in mkcol_mocks {
+            mkcol_mock.assert();
+        }
+        assert!(layout.set_unknown_date_folder(" / ").is_err());
     }
-}
\ No newline at end of file
+}
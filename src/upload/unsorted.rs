@@ -1,8 +1,12 @@
 use crate::nextcloud::NextcloudClient;
 use crate::media::Extractor;
-use crate::upload::common;
-use crate::filesystem::File;
+use crate::upload::common::{self, UploadOptions};
+use crate::upload::conflict::RemoteIndex;
+use crate::filesystem::{File, Folder};
+use crate::output;
 
+use std::collections::BTreeMap;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::error::Error;
 use colored::*;
@@ -14,30 +18,109 @@ fn get_remote_parent(files: &mut Vec<File>, root_folder: PathBuf) {
     }
 }
 
-// Todo: implement keeping the original structure
-// uploads a folder to Nextcloud keeping the original structure
-pub fn upload_unsorted(path_upload: String, from_folder: bool, remote_path: String, num_threads: usize, client: NextcloudClient, extractor: Extractor) -> Result<(), Box<dyn Error>> {
-    // check if the root folder exists and if not ask the user if he wants to create it
-    match common::exists_root_folder(Path::new(&remote_path), &client) {
-        Ok(true) => {}
-        Ok(false) => return Ok(()),
-        Err(e) => return Err(e)
+// assigns each file the remote folder matching its local folder relative to 'local_root' and returns the folders which
+// have to be created on nextcloud
+fn get_remote_parent_preserved(files: &mut [File], local_root: &Path, mut root: Folder) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // collect the distinct local folders first, so every folder is planned once in a stable order
+    let mut relative_parents: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+    for file in files.iter() {
+        relative_parents.insert(get_relative_parent(file, local_root)?, PathBuf::new());
+    }
+    let mut new_folders: Vec<PathBuf> = Vec::new();
+    for (relative_parent, remote_parent) in relative_parents.iter_mut() {
+        *remote_parent = common::plan_remote_path(&mut root, relative_parent, &mut new_folders);
+    }
+
+    for file in files.iter_mut() {
+        let remote_parent = relative_parents[&get_relative_parent(file, local_root)?].clone();
+        file.set_remote_parent(remote_parent);
+    }
+    Ok(new_folders)
+}
+
+// returns the folder of a file relative to the local root folder of the upload
+fn get_relative_parent(file: &File, local_root: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let parent = file.get_local_path().parent().unwrap_or(local_root);
+    match parent.strip_prefix(local_root) {
+        Ok(relative_parent) => Ok(relative_parent.to_path_buf()),
+        Err(_) => Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not inside the folder {:?}", file.get_local_path(), local_root))))
+    }
+}
+
+// uploads a folder to Nextcloud, either into a single remote folder or keeping the original structure if 'preserve_structure' is set
+pub fn upload_unsorted(path_upload: String, from_folder: bool, remote_path: String, preserve_structure: bool, client: NextcloudClient, extractor: Extractor, options: &UploadOptions) -> Result<usize, Box<dyn Error>> {
+    // check if the root folder exists and if not create it depending on CREATE_ROOT or the answer of the user
+    let root_exists = match common::check_root_folder(Path::new(&remote_path), &client, options)? {
+        Some(root_exists) => root_exists,
+        None => return Ok(0)
+    };
+
+    // the original structure can only be rebuilt relative to a local folder
+    if preserve_structure && !from_folder {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput, "Keeping the original folder structure requires --local instead of --file")))
     }
 
-    let root_folder = PathBuf::from(remote_path);
+    // create the cached version of the nextcloud folder structure, which is only needed to recreate the local folders.
+    // The root folder is only missing in a dry run
+    let mut index = RemoteIndex::new();
+    let mut root = Folder::new(remote_path.to_owned());
+    let mut new_folders: Vec<PathBuf> = Vec::new();
+    if !root_exists {
+        new_folders.push(PathBuf::from(&remote_path));
+    } else if preserve_structure {
+        output::print("Scanning remote folder structure ... ".green());
+        common::travel_dir_dav(&mut root, &client, &mut index)?;
+        output::println("done".green());
+    }
+
+    output::print("Scanning local folder for files ... ".green());
+    // planning the remote folders of the files and uploading the files in parallel threads to nextcloud
+    match common::get_files_for_upload(Path::new(&path_upload), from_folder, Some(&extractor), &options.scan_filter) {
+        Ok((mut files, skipped)) => {
+            output::println("done".green());
+            skipped.print();
+            common::print_date_sources(&files);
 
-    print!("{}", "Scanning local folder for files ... ".green());
-    // creating the missing folders on nextcloud and uploading the files in 4 threads to nextcloud
-    match common::get_files_for_upload(Path::new(&path_upload), from_folder, &extractor) {
-        Ok(mut files) => {
-            println!("{}", "done".green());
+            if preserve_structure {
+                new_folders.extend(get_remote_parent_preserved(&mut files, Path::new(&path_upload), root)?);
+            } else {
+                get_remote_parent(&mut files, PathBuf::from(&remote_path));
+            }
 
-            get_remote_parent(&mut files, root_folder);
-            return common::start_upload(files, client, num_threads)
+            common::upload_planned(files, new_folders, Path::new(&remote_path), index, client, options)
         }
         
         // passing error to caller function
-        Err(e) => return Err(e)
+        Err(e) => Err(e)
+
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_get_remote_parent_preserved() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("2023/Holiday")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("2024")).unwrap();
+        let mut files = vec![];
+        for path in ["a.jpg", "2023/Holiday/IMG_0001.JPG", "2024/IMG_0001.JPG", "2024/b.jpg"] {
+            fs::write(temp_dir.path().join(path), "abc").unwrap();
+            files.push(File::new(&temp_dir.path().join(path), 0));
+        }
+
+        // the folder 2024 already exists on nextcloud
+        let mut root = Folder::new("/Photos".to_string());
+        root.add_sub_folder(Folder::new("2024".to_string()), Path::new("/Photos"));
+
+        // assert that the files keep their local structure and each missing folder is planned once
+        let new_folders = get_remote_parent_preserved(&mut files, temp_dir.path(), root).unwrap();
+        let remote_parents: Vec<&Path> = files.iter().map(|file| file.get_remote_parent()).collect();
+        assert_eq!(vec![Path::new("/Photos"), Path::new("/Photos/2023/Holiday"), Path::new("/Photos/2024"), Path::new("/Photos/2024")], remote_parents);
+        assert_eq!(vec![PathBuf::from("/Photos/2023"), PathBuf::from("/Photos/2023/Holiday")], new_folders);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use colored::*;
+use log::{error, warn};
+
+use crate::checksum::{self, ChecksumAlgorithm};
+use crate::filesystem::{File, RemoteFile};
+use crate::helpers;
+use crate::nextcloud::NextcloudClient;
+use crate::upload::common;
+use crate::upload::filter::ScanFilter;
+use crate::output;
+
+// result of comparing a local file with the files of the same name on nextcloud
+#[derive(PartialEq, Debug)]
+enum Verification {
+    // the checksum of the local file matches the checksum stored on nextcloud
+    Verified,
+    // none of the remote files with the same name has the checksum of the local file
+    Mismatch(PathBuf),
+    // no file with the same name or the same content exists on nextcloud
+    Missing,
+    // nextcloud did not store a checksum which can be compared with the local file
+    NoChecksum
+}
+
+// remote files with their full remote path
+type RemoteFiles = Vec<(PathBuf, RemoteFile)>;
+
+// remote files grouped by file name and by size
+#[derive(Default)]
+struct RemoteIndex {
+    by_name: HashMap<String, RemoteFiles>,
+    by_size: HashMap<u64, RemoteFiles>
+}
+
+// lists the remote folder recursively and groups the remote files by their name and size.
+// Files are matched by name, because upload:sorted and upload:unsorted both keep the local file name. Files which were
+// renamed during the upload, e.g. by --on-conflict rename or --on-collision, are matched by size and checksum
+fn travel_dir_dav(root: &Path, client: &NextcloudClient) -> Result<RemoteIndex, Box<dyn Error>> {
+    let mut paths_folder: Vec<PathBuf> = vec![root.to_path_buf()];
+    let mut remote_files = RemoteIndex::default();
+
+    while let Some(current_folder) = paths_folder.pop() {
+        for item in client.ls_files(&current_folder)? {
+            let path = current_folder.join(item.get_name());
+            if item.is_folder() {
+                paths_folder.push(path);
+                continue
+            }
+            remote_files.by_size.entry(item.get_size()).or_default().push((path.clone(), item.clone()));
+            remote_files.by_name.entry(item.get_name().to_string()).or_default().push((path, item));
+        }
+    }
+    Ok(remote_files)
+}
+
+// compares the checksum of a local file with the checksums of the remote files with the same name. If none of them matches,
+// the remote files of the same size are checked, so a renamed upload is found as well. These only count if they match,
+// a different file of the same size is not a mismatch
+fn verify_file(file: &File, candidates: &[(PathBuf, RemoteFile)], same_size: &[(PathBuf, RemoteFile)]) -> Result<Verification, Box<dyn Error>> {
+    let name = file.get_remote_name().unwrap_or_default();
+    let renamed = same_size.iter().filter(|(_, remote)| remote.get_name() != name);
+
+    // the local file is only hashed once per algorithm, even if several remote files are compared with it
+    let mut local_hashes: HashMap<&str, String> = HashMap::new();
+    let mut mismatch: Option<PathBuf> = None;
+    for (path, remote) in candidates.iter().chain(renamed) {
+        let algorithm = match ChecksumAlgorithm::ALL.into_iter().find(|algorithm| remote.get_checksum(algorithm.get_name()).is_some()) {
+            Some(algorithm) => algorithm,
+            None => continue
+        };
+
+        let local_hash = match local_hashes.get(algorithm.get_name()) {
+            Some(hash) => hash.clone(),
+            None => {
+                let hash = checksum::hash_file(file.get_local_path(), algorithm)?;
+                local_hashes.insert(algorithm.get_name(), hash.clone());
+                hash
+            }
+        };
+
+        if remote.get_checksum(algorithm.get_name()) == Some(local_hash.as_str()) {
+            return Ok(Verification::Verified)
+        }
+        if remote.get_name() == name {
+            mismatch.get_or_insert(path.clone());
+        }
+    }
+
+    match mismatch {
+        Some(path) => Ok(Verification::Mismatch(path)),
+        None if candidates.is_empty() => Ok(Verification::Missing),
+        None => Ok(Verification::NoChecksum)
+    }
+}
+
+// compares the local files with the checksums nextcloud stored for the uploaded files and reports any mismatches. Returns
+// the number of files which differ from their copy on nextcloud or do not exist on nextcloud
+pub fn verify(path_local: String, from_folder: bool, remote_path: String, client: &NextcloudClient, filter: &ScanFilter) -> Result<usize, Box<dyn Error>> {
+    output::print("Scanning local folder for files ... ".green());
+    // the files are filtered like during the upload, so skipped files are not reported as missing. Their dates are not needed
+    let (files, _) = common::get_files_for_upload(Path::new(&path_local), from_folder, None, filter)?;
+    output::println("done".green());
+
+    output::print("Listing remote folder ... ".green());
+    let remote_files = travel_dir_dav(Path::new(&remote_path), client)?;
+    output::println("done".green());
+
+    let mut num_verified: usize = 0;
+    let mut num_mismatch: usize = 0;
+    let mut num_missing: usize = 0;
+    let mut num_no_checksum: usize = 0;
+
+    for (i, file) in files.iter().enumerate() {
+        let name = file.get_remote_name().unwrap_or_default();
+        let candidates = remote_files.by_name.get(&name).map(|candidates| candidates.as_slice()).unwrap_or_default();
+        let same_size = remote_files.by_size.get(&file.get_size()).map(|candidates| candidates.as_slice()).unwrap_or_default();
+
+        match verify_file(file, candidates, same_size)? {
+            Verification::Verified => num_verified += 1,
+            Verification::Mismatch(remote) => {
+                num_mismatch += 1;
+                error!("Checksum mismatch: {:?} differs from {:?} on Nextcloud", file.get_local_path(), remote);
+            }
+            Verification::Missing => {
+                num_missing += 1;
+                warn!("{:?} does not exist on Nextcloud", file.get_local_path());
+            }
+            Verification::NoChecksum => {
+                num_no_checksum += 1;
+                warn!("Nextcloud has no checksum for {:?}", file.get_local_path());
+            }
+        }
+        helpers::progress_bar(i as u64 + 1, files.len() as u64, "Verifying:", "");
+    }
+    output::println("");
+
+    output::println(format!("{} file(s) verified.", num_verified).green());
+    if num_mismatch > 0 {
+        output::println(format!("{} file(s) differ from their copy on Nextcloud, see process.log for details.", num_mismatch).red());
+    }
+    if num_missing > 0 {
+        output::println(format!("{} file(s) do not exist on Nextcloud.", num_missing).yellow());
+    }
+    if num_no_checksum > 0 {
+        output::println(format!("{} file(s) could not be verified because Nextcloud has no checksum for them.", num_no_checksum).yellow());
+    }
+    Ok(num_mismatch + num_missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    // creates a remote file which carries the given checksums
+    fn create_remote_file(name: &str, checksums: &str) -> (PathBuf, RemoteFile) {
+        let mut remote = RemoteFile::new(name.to_string(), false, 4, 0);
+        remote.set_checksums(checksum::parse_checksums(checksums));
+        (PathBuf::from("/Photos").join(name), remote)
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("test.txt");
+        fs::write(&local_path, "test").unwrap();
+        let file = File::new(&local_path, 0);
+
+        // assert that a matching checksum of any supported algorithm verifies the file
+        let verified = create_remote_file("test.txt", "SHA1:a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
+        assert_eq!(Verification::Verified, verify_file(&file, std::slice::from_ref(&verified), &[]).unwrap());
+
+        // assert that a different checksum is reported together with the remote path
+        let mismatch = create_remote_file("test.txt", "MD5:00000000000000000000000000000000");
+        assert_eq!(Verification::Mismatch(PathBuf::from("/Photos/test.txt")), verify_file(&file, std::slice::from_ref(&mismatch), &[]).unwrap());
+
+        // assert that a single matching file with the same name is enough
+        assert_eq!(Verification::Verified, verify_file(&file, &[mismatch.clone(), verified], &[]).unwrap());
+
+        // assert that files without checksums or remote files can not be verified
+        assert_eq!(Verification::NoChecksum, verify_file(&file, &[create_remote_file("test.txt", "ADLER32:045d01c1")], &[]).unwrap());
+        assert_eq!(Verification::Missing, verify_file(&file, &[], &[]).unwrap());
+    }
+
+    #[test]
+    fn test_verify_renamed_file() {
+        let temp_dir = tempdir().unwrap();
+        let local_path = temp_dir.path().join("test.txt");
+        fs::write(&local_path, "test").unwrap();
+        let file = File::new(&local_path, 0);
+
+        // assert that a file uploaded under a new name is found by its size and checksum
+        let renamed = create_remote_file("test (1).txt", "SHA1:a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
+        assert_eq!(Verification::Verified, verify_file(&file, &[], std::slice::from_ref(&renamed)).unwrap());
+
+        // assert that the renamed copy also wins over a different file which took the original name
+        let mismatch = create_remote_file("test.txt", "MD5:00000000000000000000000000000000");
+        assert_eq!(Verification::Verified, verify_file(&file, std::slice::from_ref(&mismatch), &[mismatch.clone(), renamed]).unwrap());
+
+        // assert that a different file of the same size is not reported as mismatch
+        let other = create_remote_file("other.txt", "MD5:00000000000000000000000000000000");
+        assert_eq!(Verification::Missing, verify_file(&file, &[], &[other]).unwrap());
+    }
+}